@@ -0,0 +1,38 @@
+CREATE TABLE device_state_history (
+    id BIGSERIAL PRIMARY KEY,
+    device_id BIGINT NOT NULL REFERENCES device(id) ON DELETE CASCADE,
+    power_state INT4 NOT NULL,
+    battery_percentage INT8,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX device_state_history_device_id_recorded_at_idx
+    ON device_state_history (device_id, recorded_at);
+
+-- Every writer of `device` (discovery, server functions, actions and scripts)
+-- goes through these triggers, so the history can't drift from the device row.
+CREATE FUNCTION record_device_state() RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO device_state_history (device_id, power_state, battery_percentage)
+    VALUES (NEW.id, NEW.power_state, NEW.battery_percentage);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER device_state_history_insert
+    AFTER INSERT ON device
+    FOR EACH ROW
+    EXECUTE FUNCTION record_device_state();
+
+CREATE TRIGGER device_state_history_update
+    AFTER UPDATE OF power_state, battery_percentage ON device
+    FOR EACH ROW
+    WHEN (
+        OLD.power_state IS DISTINCT FROM NEW.power_state
+        OR OLD.battery_percentage IS DISTINCT FROM NEW.battery_percentage
+    )
+    EXECUTE FUNCTION record_device_state();
+
+INSERT INTO device_state_history (device_id, power_state, battery_percentage, recorded_at)
+SELECT id, power_state, battery_percentage, last_seen
+FROM device;
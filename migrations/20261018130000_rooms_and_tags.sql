@@ -0,0 +1,11 @@
+CREATE TABLE room (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    zone TEXT
+);
+
+ALTER TABLE device
+    ADD COLUMN room_id BIGINT REFERENCES room(id) ON DELETE SET NULL,
+    ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}';
+
+CREATE INDEX device_tags_idx ON device USING GIN (tags);
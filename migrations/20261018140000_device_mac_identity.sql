@@ -0,0 +1,35 @@
+-- Devices with a MAC are identified by (mac_address, child_id) and may change
+-- IP, so (ip, child_id) only has to be unique for devices without one.
+ALTER TABLE device DROP CONSTRAINT unique_ip_child_id;
+
+CREATE UNIQUE INDEX unique_ip_child_id_without_mac
+    ON device (ip, coalesced_child_id)
+    WHERE mac_address IS NULL;
+
+-- Point existing actions at device ids instead of IPs
+UPDATE config
+SET data = jsonb_set(
+    data,
+    '{actions}',
+    (
+        SELECT COALESCE(
+            jsonb_agg(
+                CASE
+                    WHEN device.id IS NOT NULL THEN jsonb_set(
+                        action,
+                        '{function_args}',
+                        (action->'function_args' - 'ip') || jsonb_build_object('device_id', device.id)
+                    )
+                    ELSE action
+                END
+                ORDER BY position
+            ),
+            '[]'::jsonb
+        )
+        FROM jsonb_array_elements(data->'actions') WITH ORDINALITY AS actions(action, position)
+        LEFT JOIN device
+            ON device.ip = action->'function_args'->>'ip'
+            AND device.child_id IS NULL
+    )
+)
+WHERE jsonb_typeof(data->'actions') = 'array';
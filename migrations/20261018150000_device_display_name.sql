@@ -0,0 +1,5 @@
+-- Set by the user and never touched by discovery, which keeps `name` in sync
+-- with what the device reports
+ALTER TABLE device
+    ADD COLUMN display_name TEXT,
+    ADD COLUMN icon TEXT;
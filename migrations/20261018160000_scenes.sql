@@ -0,0 +1,13 @@
+CREATE TABLE scene (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- One captured state per device, see `DeviceState`
+CREATE TABLE scene_device (
+    scene_id BIGINT NOT NULL REFERENCES scene(id) ON DELETE CASCADE,
+    device_id BIGINT NOT NULL REFERENCES device(id) ON DELETE CASCADE,
+    state JSONB NOT NULL,
+    PRIMARY KEY (scene_id, device_id)
+);
@@ -0,0 +1,13 @@
+CREATE TABLE energy_reading (
+    id BIGSERIAL PRIMARY KEY,
+    device_id BIGINT NOT NULL REFERENCES device(id) ON DELETE CASCADE,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    voltage DOUBLE PRECISION,
+    current DOUBLE PRECISION,
+    power DOUBLE PRECISION,
+    -- Lifetime counter reported by the device, it resets with the device
+    total_kwh DOUBLE PRECISION
+);
+
+CREATE INDEX energy_reading_device_id_recorded_at_idx
+    ON energy_reading (device_id, recorded_at);
@@ -0,0 +1,16 @@
+-- How long a device can go unseen before it's considered offline, per
+-- integration. NULL for integrations whose devices aren't polled
+ALTER TABLE integration ADD COLUMN offline_after_secs INTEGER;
+
+UPDATE integration
+SET offline_after_secs = CASE name
+    WHEN 'tplink' THEN 900
+    WHEN 'ring' THEN 900
+    WHEN 'roku' THEN 10800
+    WHEN 'tuya' THEN 10800
+    WHEN 'eufy' THEN 10800
+END;
+
+-- Derived from `last_seen` and the threshold above by the availability job,
+-- stored so going offline and coming back can be told apart
+ALTER TABLE device ADD COLUMN online BOOLEAN NOT NULL DEFAULT TRUE;
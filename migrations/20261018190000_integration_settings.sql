@@ -0,0 +1,12 @@
+-- How often each integration's background job polls, rediscovers devices and
+-- refreshes its credentials. Edited from the integrations page
+ALTER TABLE integration
+ADD COLUMN settings JSONB NOT NULL DEFAULT '{"poll_interval_secs": 3600}';
+
+UPDATE integration
+SET settings = CASE name
+    WHEN 'tplink' THEN '{"poll_interval_secs": 60, "discovery_interval_secs": 300}'::jsonb
+    WHEN 'ring' THEN '{"poll_interval_secs": 300, "auth_interval_secs": 18000}'::jsonb
+    WHEN 'eufy' THEN '{"poll_interval_secs": 3600, "auth_interval_secs": 18000}'::jsonb
+    ELSE settings
+END;
@@ -0,0 +1,10 @@
+-- Integration credentials and tokens, encrypted with the master key by
+-- `SecretStore`. Tokens in the plaintext `auth` table are moved here on start
+CREATE TABLE integration_secret (
+    integration TEXT NOT NULL,
+    name TEXT NOT NULL,
+    nonce BYTEA NOT NULL,
+    ciphertext BYTEA NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (integration, name)
+);
@@ -0,0 +1,8 @@
+-- Power strip outlets were stored with the strip's deviceId prepended to the
+-- outlet's id, which most firmware already reports prefixed, doubling it
+UPDATE device
+SET child_id = substr(child_id, (length(child_id) - 2) / 2 + 1)
+WHERE device_type = 'kasa-power-strip'
+    AND length(child_id) > 4
+    AND left(child_id, (length(child_id) - 2) / 2)
+        = substr(child_id, (length(child_id) - 2) / 2 + 1, (length(child_id) - 2) / 2);
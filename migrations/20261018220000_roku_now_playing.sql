@@ -0,0 +1,8 @@
+-- What each Roku is showing, polled by the Roku job
+ALTER TABLE device
+ADD COLUMN now_playing JSONB;
+
+-- The Roku job now polls playback and only rediscovers devices hourly
+UPDATE integration
+SET settings = '{"poll_interval_secs": 15, "discovery_interval_secs": 3600}'
+WHERE name = 'roku' AND settings = '{"poll_interval_secs": 3600}'::jsonb;
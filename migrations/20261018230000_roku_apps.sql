@@ -0,0 +1,12 @@
+-- The channels installed on each Roku, refreshed by the Roku job. Icons are
+-- stored once in ipld_blobs and shared between Rokus with the same channel
+CREATE TABLE roku_app (
+    device_id BIGINT NOT NULL REFERENCES device(id) ON DELETE CASCADE,
+    app_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    app_type TEXT NOT NULL,
+    version TEXT NOT NULL,
+    icon_cid BYTEA REFERENCES ipld_blobs(cid),
+    position INT NOT NULL,
+    PRIMARY KEY (device_id, app_id)
+);
@@ -0,0 +1,13 @@
+-- Everything published on the event bus apart from mish state changes,
+-- written by the audit log task
+CREATE TABLE event_log (
+    id BIGSERIAL PRIMARY KEY,
+    event_type TEXT NOT NULL,
+    -- Not a foreign key so entries outlive the device they are about
+    device_id BIGINT,
+    payload JSONB NOT NULL,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX event_log_recorded_at_idx ON event_log (recorded_at);
+CREATE INDEX event_log_device_id_recorded_at_idx ON event_log (device_id, recorded_at);
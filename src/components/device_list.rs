@@ -1,7 +1,10 @@
 use {
     crate::{
         components::{
-            checkbox::Checkbox, device_list_card::DeviceListCard, device_modal::Modal,
+            checkbox::Checkbox,
+            device_list_card::DeviceListCard,
+            device_modal::Modal,
+            layout::{ToastContext, toast_on_error},
             refresh_button::Refresh_Button,
         },
         integrations::iron_nest::types::{Device, DeviceType},
@@ -23,6 +26,7 @@ pub fn DeviceList(
     devices: Resource<Result<Vec<Device>, ServerFnError>>,
     on_device_click: Callback<i64>,
 ) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
     let (modal, toggle_modal) = signal(false);
     let (current_device, set_current_device) = signal(None);
 
@@ -33,7 +37,7 @@ pub fn DeviceList(
                 <Refresh_Button on_change=Box::new({
                     move || {
                         spawn_local(async move {
-                            refresh_devices().await.unwrap();
+                            toast_on_error(toast, refresh_devices().await);
                         });
                     }
                 }) />
@@ -165,13 +169,14 @@ pub fn DeviceListItem(device: Device) -> impl IntoView {
 
 #[component]
 pub fn SmartPlugItem(device: Device) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
     let toggle_action = Action::new({
         let ip = device.ip.clone();
         move |value| {
             let ip = ip.clone();
             let value = *value;
             async move {
-                handle_smart_plug_toggle(value, ip).await.unwrap();
+                toast_on_error(toast, handle_smart_plug_toggle(value, ip).await);
             }
         }
     });
@@ -186,13 +191,14 @@ pub fn SmartPlugItem(device: Device) -> impl IntoView {
 
 #[component]
 pub fn SmartDimmerItem(device: Device) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
     let toggle_action = Action::new({
         let ip = device.ip.clone();
         move |value| {
             let ip = ip.clone();
             let value = *value;
             async move {
-                handle_smart_plug_toggle(value, ip).await.unwrap();
+                toast_on_error(toast, handle_smart_plug_toggle(value, ip).await);
             }
         }
     });
@@ -206,17 +212,19 @@ pub fn SmartDimmerItem(device: Device) -> impl IntoView {
 
 #[component]
 pub fn SmartPowerStripItem(device: Device) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
     let toggle_action = Action::new({
         let ip = device.ip.clone();
-        let child_id = device.child_id.clone().unwrap();
+        let child_id = device.child_id.clone().unwrap_or_default();
         move |value| {
             let ip = ip.clone();
             let child_id = child_id.clone();
             let value = *value;
             async move {
-                handle_smart_power_strip_toggle(value, ip, child_id)
-                    .await
-                    .unwrap();
+                toast_on_error(
+                    toast,
+                    handle_smart_power_strip_toggle(value, ip, child_id).await,
+                );
             }
         }
     });
@@ -249,13 +257,14 @@ pub fn StoplightItem(device: Device) -> impl IntoView {
 
 #[component]
 pub fn SmartLightItem(device: Device) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
     let toggle_action = Action::new({
         let ip = device.ip.clone();
         move |value| {
             let ip = ip.clone();
             let value = *value;
             async move {
-                handle_smart_light_toggle(value, ip).await.unwrap();
+                toast_on_error(toast, handle_smart_light_toggle(value, ip).await);
             }
         }
     });
@@ -270,13 +279,14 @@ pub fn SmartLightItem(device: Device) -> impl IntoView {
 
 #[component]
 pub fn RokuTvItem(device: Device) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
     let toggle_action = Action::new({
         let ip = device.ip.clone();
         move |value| {
             let ip = ip.clone();
             let value = *value;
             async move {
-                handle_roku_tv_toggle(value, ip).await.unwrap();
+                toast_on_error(toast, handle_roku_tv_toggle(value, ip).await);
             }
         }
     });
@@ -5,7 +5,7 @@ use {
 
 #[component]
 pub fn DeviceListCard(device: Device, children: Children) -> impl IntoView {
-    let icon = match device.device_type {
+    let type_icon = match device.device_type {
         DeviceType::KasaPlug => view! {
             <svg
                 xmlns="http://www.w3.org/2000/svg"
@@ -178,8 +178,32 @@ pub fn DeviceListCard(device: Device, children: Children) -> impl IntoView {
         }.into_any(),
     };
 
+    let icon = match device.icon.clone() {
+        Some(icon) => view! { <span class="w-6 h-6 text-center">{icon}</span> }.into_any(),
+        None => type_icon,
+    };
+    let label = device.label().to_owned();
+    // Unavailable devices are greyed out with their last contact in place of
+    // the ip
+    let (card_class, badge_class, detail) = if device.online {
+        (
+            "col-span-1 divide-y divide-gray-200 rounded-lg bg-white shadow cursor-pointer hover:shadow-indigo hover:shadow-[rgba(79,70,229,0.5)_0px_0px_4px_4px]",
+            "inline-flex flex-shrink-0 items-center rounded-full bg-green-50 px-1.5 py-0.5 text-xs font-medium text-green-700 ring-1 ring-inset ring-green-600/20",
+            device.ip,
+        )
+    } else {
+        (
+            "col-span-1 divide-y divide-gray-200 rounded-lg bg-gray-100 shadow cursor-pointer opacity-60",
+            "inline-flex flex-shrink-0 items-center rounded-full bg-gray-50 px-1.5 py-0.5 text-xs font-medium text-gray-600 ring-1 ring-inset ring-gray-500/20",
+            format!(
+                "Unavailable since {}",
+                device.last_seen.format("%b %d %H:%M")
+            ),
+        )
+    };
+
     view! {
-        <li class="col-span-1 divide-y divide-gray-200 rounded-lg bg-white shadow cursor-pointer hover:shadow-indigo hover:shadow-[rgba(79,70,229,0.5)_0px_0px_4px_4px]">
+        <li class=card_class>
             <div class="flex w-full items-center justify-between space-x-6 p-6">
                 <div class="flex-1 truncate">
                     <div class="flex items-center space-x-3">
@@ -187,13 +211,11 @@ pub fn DeviceListCard(device: Device, children: Children) -> impl IntoView {
                             class="truncate text-sm font-medium text-gray-900"
                             title=format!("{:?}", device.last_seen)
                         >
-                            {device.name}
+                            {label}
                         </h3>
-                        <span class="inline-flex flex-shrink-0 items-center rounded-full bg-green-50 px-1.5 py-0.5 text-xs font-medium text-green-700 ring-1 ring-inset ring-green-600/20">
-                            {icon}
-                        </span>
+                        <span class=badge_class>{icon}</span>
                     </div>
-                    <p class="mt-1 truncate text-sm text-gray-500">{device.ip}</p>
+                    <p class="mt-1 truncate text-sm text-gray-500">{detail}</p>
                 </div>
                 {children()}
             </div>
@@ -1,11 +1,30 @@
 use {
     super::checkbox::Checkbox,
     crate::{
-        components::{color_picker::ColorPicker, slider::Slider},
-        integrations::iron_nest::types::{Device, DeviceType},
-        server::tplink::{
-            handle_smart_dimmer_brightness, handle_smart_light_brightness, handle_smart_light_hsl,
-            handle_smart_light_toggle, handle_smart_plug_toggle,
+        components::{
+            color_picker::ColorPicker,
+            layout::{Toast, ToastContext, toast_on_error},
+            slider::Slider,
+        },
+        integrations::{
+            iron_nest::types::{Device, DeviceCommand, DeviceState, DeviceType},
+            tplink::types::{
+                CountdownRule, KasaDeviceInfo, KasaFamily, PreferredState, RuleTime, ScheduleRule,
+                WifiNetwork,
+            },
+        },
+        server::{
+            devices::handle_device_command,
+            roku::handle_roku_tv_toggle,
+            tplink::{
+                delete_kasa_rule, get_kasa_device_info, get_kasa_light, get_kasa_rules,
+                get_kasa_wifi_networks, get_power_strip_outlets, handle_smart_dimmer_brightness,
+                handle_smart_light_brightness, handle_smart_light_hsl, handle_smart_light_toggle,
+                handle_smart_plug_toggle, handle_smart_power_strip_toggle, reboot_kasa_device,
+                rename_kasa_device, save_kasa_countdown_rule, save_kasa_light_preset,
+                save_kasa_schedule_rule, set_kasa_led, set_kasa_wifi, set_power_strip_outlets,
+                unbind_kasa_cloud,
+            },
         },
     },
     leptos::{prelude::*, task::spawn_local},
@@ -26,8 +45,34 @@ pub fn DeviceView(device: Device) -> impl IntoView {
     }
 }
 
+#[derive(Clone, Copy, PartialEq)]
+enum ModalTab {
+    Controls,
+    Schedules,
+    Manage,
+}
+
+/// Plugs, dimmers and power strip outlets run their own schedules, which keep
+/// working while IronNest is down.
+fn has_on_device_rules(device: &Device) -> bool {
+    match device.device_type {
+        DeviceType::KasaPlug | DeviceType::KasaDimmer => true,
+        DeviceType::KasaPowerStrip => device.child_id.is_some(),
+        _ => false,
+    }
+}
+
 #[component]
 pub fn Modal(toggle_modal: WriteSignal<bool>, device: ReadSignal<Option<Device>>) -> impl IntoView {
+    let (tab, set_tab) = signal(ModalTab::Controls);
+    let tab_class = move |this: ModalTab| {
+        if tab.get() == this {
+            "font-bold text-gray-900"
+        } else {
+            "text-gray-500"
+        }
+    };
+
     view! {
         <div class="relative z-50" aria-labelledby="modal-title" role="dialog" aria-modal="true">
             <div class="fixed inset-0 bg-gray-500 bg-opacity-75 transition-opacity"></div>
@@ -55,10 +100,55 @@ pub fn Modal(toggle_modal: WriteSignal<bool>, device: ReadSignal<Option<Device>>
                                                         class="text-base font-semibold leading-6 text-gray-900"
                                                         id="modal-title"
                                                     >
-                                                        {data.name.to_owned()}
+                                                        {data.label().to_owned()}
                                                     </h3>
+                                                    {KasaFamily::of(data.device_type)
+                                                        .is_some()
+                                                        .then(|| {
+                                                            view! {
+                                                                <div class="mt-2 flex justify-center gap-4 text-sm">
+                                                                    <button
+                                                                        class=move || tab_class(ModalTab::Controls)
+                                                                        on:click=move |_| set_tab.set(ModalTab::Controls)
+                                                                    >
+                                                                        "Controls"
+                                                                    </button>
+                                                                    {has_on_device_rules(&data)
+                                                                        .then(|| {
+                                                                            view! {
+                                                                                <button
+                                                                                    class=move || tab_class(ModalTab::Schedules)
+                                                                                    on:click=move |_| set_tab.set(ModalTab::Schedules)
+                                                                                >
+                                                                                    "Schedules"
+                                                                                </button>
+                                                                            }
+                                                                        })}
+                                                                    <button
+                                                                        class=move || tab_class(ModalTab::Manage)
+                                                                        on:click=move |_| set_tab.set(ModalTab::Manage)
+                                                                    >
+                                                                        "Manage"
+                                                                    </button>
+                                                                </div>
+                                                            }
+                                                        })}
                                                     <div class="mt-2">
-                                                        <DeviceView device=data />
+                                                        {move || match tab.get() {
+                                                            ModalTab::Schedules if has_on_device_rules(&data) => {
+                                                                view! { <KasaRulesView device_id=data.id /> }
+                                                                    .into_any()
+                                                            }
+                                                            ModalTab::Manage
+                                                                if KasaFamily::of(data.device_type).is_some() => {
+                                                                view! { <KasaManageView device=data.clone() /> }
+                                                                    .into_any()
+                                                            }
+                                                            _ => {
+                                                                view! { <DeviceView device=data.clone() /> }
+                                                                    .into_any()
+                                                            }
+                                                        }}
                                                     </div>
                                                 </div>
                                             }
@@ -77,15 +167,190 @@ pub fn Modal(toggle_modal: WriteSignal<bool>, device: ReadSignal<Option<Device>>
     }
 }
 
+/// Reports how a management action went and reloads the device's info.
+fn run_kasa_action(
+    toast: ToastContext,
+    info: Resource<Result<KasaDeviceInfo, ServerFnError>>,
+    label: &'static str,
+    action: impl Future<Output = Result<(), ServerFnError>> + 'static,
+) {
+    spawn_local(async move {
+        match action.await {
+            Ok(()) => {
+                toast.set(Some(Toast(format!("{label} done"))));
+                info.refetch();
+            }
+            Err(e) => toast.set(Some(Toast(format!("{label} error: {e}")))),
+        }
+    });
+}
+
+/// Renaming, rebooting, the status LED, the TP-Link cloud binding and the
+/// Wi-Fi network of a Kasa device.
+#[component]
+pub fn KasaManageView(device: Device) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
+    let device_id = device.id;
+    let info = Resource::new(|| (), move |_| get_kasa_device_info(device_id));
+    let alias = RwSignal::new(String::new());
+    let confirm_unbind = RwSignal::new(false);
+    let ssid = RwSignal::new(String::new());
+    let password = RwSignal::new(String::new());
+    let networks = RwSignal::new(Vec::<WifiNetwork>::new());
+
+    Effect::new(move |_| {
+        if let Some(Ok(info)) = info.get() {
+            alias.set(info.alias);
+        }
+    });
+
+    let rename = move |_| {
+        let action = rename_kasa_device(device_id, alias.get_untracked());
+        run_kasa_action(toast, info, "Rename", action);
+    };
+    let reboot = move |_| run_kasa_action(toast, info, "Reboot", reboot_kasa_device(device_id));
+    let set_led = move |on: bool| {
+        run_kasa_action(toast, info, "Status LED", set_kasa_led(device_id, on));
+    };
+    let unbind = move |_| {
+        if confirm_unbind.get_untracked() {
+            confirm_unbind.set(false);
+            run_kasa_action(toast, info, "Cloud unbind", unbind_kasa_cloud(device_id));
+        } else {
+            confirm_unbind.set(true);
+        }
+    };
+    let scan = move |_| {
+        spawn_local(async move {
+            match get_kasa_wifi_networks(device_id).await {
+                Ok(found) => networks.set(found),
+                Err(e) => toast.set(Some(Toast(format!("Wi-Fi scan error: {e}")))),
+            }
+        });
+    };
+    let join = move |_| {
+        let ssid = ssid.get_untracked().trim().to_string();
+        // Networks that weren't found by a scan are assumed to be WPA2
+        let key_type = networks.with_untracked(|networks| {
+            networks
+                .iter()
+                .find(|network| network.ssid == ssid)
+                .map_or(3, |network| network.key_type)
+        });
+        let network = WifiNetwork { ssid, key_type };
+        let action = set_kasa_wifi(device_id, network, password.get_untracked());
+        run_kasa_action(toast, info, "Wi-Fi", action);
+    };
+
+    let input_class = "rounded-md border-0 py-1 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 sm:text-sm";
+    view! {
+        <div class="flex flex-col gap-3 text-sm text-left">
+            <Suspense fallback=|| ()>
+                {move || {
+                    info.get()
+                        .map(|data| match data {
+                            Ok(info) => {
+                                view! {
+                                    <p class="text-gray-500">{info.model.clone()}</p>
+                                    {info
+                                        .led_off
+                                        .map(|off| {
+                                            view! {
+                                                <label class="flex items-center gap-2">
+                                                    <input
+                                                        type="checkbox"
+                                                        prop:checked=!off
+                                                        on:change=move |ev| set_led(event_target_checked(&ev))
+                                                    />
+                                                    "Status LED"
+                                                </label>
+                                            }
+                                        })}
+                                    {info
+                                        .cloud
+                                        .map(|cloud| {
+                                            if cloud.binded == 0 {
+                                                view! { <p>"Not bound to a TP-Link account"</p> }.into_any()
+                                            } else {
+                                                view! {
+                                                    <div class="flex items-center justify-between gap-2">
+                                                        <span>{format!("Bound to {}", cloud.username)}</span>
+                                                        <button class="text-red-600" on:click=unbind>
+                                                            {move || {
+                                                                if confirm_unbind.get() { "Confirm unbind" } else { "Unbind" }
+                                                            }}
+                                                        </button>
+                                                    </div>
+                                                }
+                                                    .into_any()
+                                            }
+                                        })}
+                                }
+                                    .into_any()
+                            }
+                            Err(e) => view! { <p>{format!("Device error: {e}")}</p> }.into_any(),
+                        })
+                }}
+            </Suspense>
+            <div class="flex gap-2">
+                <input type="text" class=format!("{input_class} flex-1") bind:value=alias />
+                <button class="bg-indigo-600 text-white px-3 py-1 rounded" on:click=rename>
+                    "Rename"
+                </button>
+            </div>
+            <button class="bg-gray-200 px-3 py-1 rounded" on:click=reboot>
+                "Reboot"
+            </button>
+            <div class="flex flex-col gap-2 border-t pt-3">
+                <span class="font-semibold">"Wi-Fi"</span>
+                <button class="text-indigo-600 self-start" on:click=scan>
+                    "Scan for networks"
+                </button>
+                <div class="flex flex-wrap gap-2">
+                    {move || {
+                        networks
+                            .get()
+                            .into_iter()
+                            .map(|network| {
+                                let label = network.ssid.clone();
+                                view! {
+                                    <button
+                                        class="rounded bg-gray-100 px-2"
+                                        on:click=move |_| ssid.set(network.ssid.clone())
+                                    >
+                                        {label}
+                                    </button>
+                                }
+                            })
+                            .collect::<Vec<_>>()
+                    }}
+                </div>
+                <input type="text" placeholder="Network" class=input_class bind:value=ssid />
+                <input
+                    type="password"
+                    autocomplete="off"
+                    placeholder="Password"
+                    class=input_class
+                    bind:value=password
+                />
+                <button class="bg-indigo-600 text-white px-3 py-1 rounded" on:click=join>
+                    "Join network"
+                </button>
+            </div>
+        </div>
+    }
+}
+
 #[component]
 pub fn SmartLightView(device: Device) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
     let toggle_action = Action::new({
         let ip = device.ip.clone();
         move |value| {
             let ip = ip.clone();
             let value = *value;
             async move {
-                handle_smart_light_toggle(value, ip).await.unwrap();
+                toast_on_error(toast, handle_smart_light_toggle(value, ip).await);
             }
         }
     });
@@ -98,7 +363,7 @@ pub fn SmartLightView(device: Device) -> impl IntoView {
                 move |value| {
                     let ip = ip.clone();
                     spawn_local(async move {
-                        handle_smart_light_brightness(value, ip).await.unwrap();
+                        toast_on_error(toast, handle_smart_light_brightness(value, ip).await);
                     });
                 }
             }) />
@@ -113,25 +378,181 @@ pub fn SmartLightView(device: Device) -> impl IntoView {
                         let ip = ip.clone();
                         spawn_local(async move {
                             leptos::logging::log!("parsing: {value}");
-                            handle_smart_light_hsl(ip, value).await.unwrap();
+                            toast_on_error(toast, handle_smart_light_hsl(ip, value).await);
                         });
                     }
                 })
             />
 
+            {(device.device_type == DeviceType::KasaLight)
+                .then(|| view! { <KasaLightControls device_id=device.id /> })}
         </div>
     }
 }
 
+/// The state a preset shows, for fading to it.
+fn preset_state(preset: &PreferredState) -> DeviceState {
+    let color = preset.color_temp == 0;
+    DeviceState {
+        on: true,
+        brightness: u8::try_from(preset.brightness).ok(),
+        hue: color.then(|| u16::try_from(preset.hue).ok()).flatten(),
+        saturation: color
+            .then(|| u8::try_from(preset.saturation).ok())
+            .flatten(),
+        color_temp: u16::try_from(preset.color_temp).ok(),
+    }
+}
+
+fn preset_style(preset: &PreferredState) -> String {
+    if preset.color_temp == 0 {
+        format!(
+            "background-color: hsl({}, {}%, 50%)",
+            preset.hue, preset.saturation
+        )
+    } else {
+        "background-color: #fdf4dc".to_string()
+    }
+}
+
+fn preset_label(preset: &PreferredState) -> String {
+    if preset.color_temp == 0 {
+        format!("{}%", preset.brightness)
+    } else {
+        format!("{}K {}%", preset.color_temp, preset.brightness)
+    }
+}
+
+/// White temperature within the bulb's range, its saved presets and how long
+/// changes made here fade over.
+#[component]
+pub fn KasaLightControls(device_id: i64) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
+    let light = Resource::new(|| (), move |_| get_kasa_light(device_id));
+    let fade_secs = RwSignal::new("0".to_string());
+
+    let fade_to = move |state: DeviceState| {
+        let transition_ms = fade_secs
+            .get_untracked()
+            .parse::<f64>()
+            .map(|secs| (secs.max(0.) * 1000.) as u32)
+            .unwrap_or_default();
+        spawn_local(async move {
+            let command = DeviceCommand::FadeTo {
+                state,
+                transition_ms,
+            };
+            toast_on_error(toast, handle_device_command(device_id, command).await);
+        });
+    };
+    let save_preset = move |index: u8| {
+        spawn_local(async move {
+            match save_kasa_light_preset(device_id, index).await {
+                Ok(()) => light.refetch(),
+                Err(e) => toast.set(Some(Toast(format!("Save preset error: {e}")))),
+            }
+        });
+    };
+
+    view! {
+        <Suspense fallback=|| ()>
+            {move || {
+                light
+                    .get()
+                    .map(|data| match data {
+                        Ok(light) => {
+                            view! {
+                                <div class="flex flex-col gap-2 text-sm">
+                                    {light
+                                        .color_temp_range
+                                        .map(|(min, max)| {
+                                            let current = light
+                                                .color_temp
+                                                .filter(|k| *k > 0)
+                                                .unwrap_or(min);
+                                            view! {
+                                                <label class="flex items-center gap-2">
+                                                    {format!("{min}K")}
+                                                    <input
+                                                        type="range"
+                                                        min=min
+                                                        max=max
+                                                        step="100"
+                                                        value=current
+                                                        on:change=move |ev| {
+                                                            if let Ok(color_temp) = event_target_value(&ev)
+                                                                .parse::<u16>()
+                                                            {
+                                                                fade_to(DeviceState {
+                                                                    on: true,
+                                                                    color_temp: Some(color_temp),
+                                                                    ..DeviceState::default()
+                                                                });
+                                                            }
+                                                        }
+                                                    />
+                                                    {format!("{max}K")}
+                                                </label>
+                                            }
+                                        })}
+                                    <div class="flex flex-wrap gap-2">
+                                        {light
+                                            .presets
+                                            .into_iter()
+                                            .map(|preset| {
+                                                let state = preset_state(&preset);
+                                                let index = preset.index;
+                                                view! {
+                                                    <div class="flex flex-col items-center">
+                                                        <button
+                                                            class="h-8 w-8 rounded-full ring-1 ring-gray-300"
+                                                            style=preset_style(&preset)
+                                                            title=preset_label(&preset)
+                                                            on:click=move |_| fade_to(state.clone())
+                                                        ></button>
+                                                        <button
+                                                            class="text-xs text-gray-500"
+                                                            on:click=move |_| save_preset(index)
+                                                        >
+                                                            "Save"
+                                                        </button>
+                                                    </div>
+                                                }
+                                            })
+                                            .collect::<Vec<_>>()}
+                                    </div>
+                                    <label class="flex items-center gap-2">
+                                        "Fade"
+                                        <input
+                                            type="number"
+                                            min="0"
+                                            step="0.5"
+                                            class="w-16 rounded-md border-0 py-1 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 sm:text-sm"
+                                            bind:value=fade_secs
+                                        />
+                                        "s"
+                                    </label>
+                                </div>
+                            }
+                                .into_any()
+                        }
+                        Err(e) => view! { <p>{format!("Light error: {e}")}</p> }.into_any(),
+                    })
+            }}
+        </Suspense>
+    }
+}
+
 #[component]
 pub fn SmartPlugView(device: Device) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
     let toggle_action = Action::new({
         let ip = device.ip.clone();
         move |value| {
             let ip = ip.clone();
             let value = *value;
             async move {
-                handle_smart_plug_toggle(value, ip).await.unwrap();
+                toast_on_error(toast, handle_smart_plug_toggle(value, ip).await);
             }
         }
     });
@@ -141,13 +562,14 @@ pub fn SmartPlugView(device: Device) -> impl IntoView {
 
 #[component]
 pub fn SmartDimmerView(device: Device) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
     let toggle_action = Action::new({
         let ip = device.ip.clone();
         move |value| {
             let ip = ip.clone();
             let value = *value;
             async move {
-                handle_smart_plug_toggle(value, ip).await.unwrap();
+                toast_on_error(toast, handle_smart_plug_toggle(value, ip).await);
             }
         }
     });
@@ -160,7 +582,7 @@ pub fn SmartDimmerView(device: Device) -> impl IntoView {
                 move |value| {
                     let ip = ip.clone();
                     spawn_local(async move {
-                        handle_smart_dimmer_brightness(value, ip).await.unwrap();
+                        toast_on_error(toast, handle_smart_dimmer_brightness(value, ip).await);
                     });
                 }
             }) />
@@ -170,18 +592,113 @@ pub fn SmartDimmerView(device: Device) -> impl IntoView {
 
 #[component]
 pub fn SmartPowerStripView(device: Device) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
     let toggle_action = Action::new({
         let ip = device.ip.clone();
+        let child_id = device.child_id.clone().unwrap_or_default();
         move |value| {
             let ip = ip.clone();
+            let child_id = child_id.clone();
             let value = *value;
             async move {
-                handle_smart_plug_toggle(value, ip).await.unwrap();
+                toast_on_error(
+                    toast,
+                    handle_smart_power_strip_toggle(value, ip, child_id).await,
+                );
             }
         }
     });
 
-    view! { <Checkbox value=device.power_state == 1 on_click=Some(toggle_action) on_click_fn=None /> }
+    view! {
+        <div class="flex flex-col gap-2">
+            <Checkbox value=device.power_state == 1 on_click=Some(toggle_action) on_click_fn=None />
+            <PowerStripOutlets device_id=device.id />
+        </div>
+    }
+}
+
+/// The outlets of the strip the outlet `device_id` belongs to, with their power
+/// draw when the strip meters each outlet, and buttons switching all of them.
+#[component]
+pub fn PowerStripOutlets(device_id: i64) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
+    let outlets = Resource::new(|| (), move |_| get_power_strip_outlets(device_id));
+
+    let set_all = move |on: bool| {
+        spawn_local(async move {
+            match set_power_strip_outlets(device_id, on).await {
+                Ok(()) => outlets.refetch(),
+                Err(e) => toast.set(Some(Toast(format!("Power strip error: {e}")))),
+            }
+        });
+    };
+    let set_outlet = move |outlet_id: i64, on: bool| {
+        spawn_local(async move {
+            let command = DeviceCommand::SetPower { on };
+            match handle_device_command(outlet_id, command).await {
+                Ok(()) => outlets.refetch(),
+                Err(e) => toast.set(Some(Toast(format!("Outlet error: {e}")))),
+            }
+        });
+    };
+
+    view! {
+        <Suspense fallback=|| ()>
+            {move || {
+                outlets
+                    .get()
+                    .map(|data| match data {
+                        Ok(outlets) => {
+                            view! {
+                                <div class="flex flex-col gap-1 text-sm">
+                                    {outlets
+                                        .into_iter()
+                                        .map(|outlet| {
+                                            let id = outlet.device.id;
+                                            let on = outlet.device.power_state == 1;
+                                            view! {
+                                                <div class="flex items-center justify-between gap-4">
+                                                    <span class=if id == device_id {
+                                                        "font-bold"
+                                                    } else {
+                                                        ""
+                                                    }>{outlet.device.label().to_string()}</span>
+                                                    <span class="text-gray-500">
+                                                        {outlet.power.map(|watts| format!("{watts:.1} W"))}
+                                                    </span>
+                                                    <button
+                                                        class="text-indigo-600"
+                                                        on:click=move |_| set_outlet(id, !on)
+                                                    >
+                                                        {if on { "On" } else { "Off" }}
+                                                    </button>
+                                                </div>
+                                            }
+                                        })
+                                        .collect::<Vec<_>>()}
+                                    <div class="flex gap-2">
+                                        <button
+                                            class="bg-indigo-600 text-white px-3 py-1 rounded"
+                                            on:click=move |_| set_all(true)
+                                        >
+                                            "All on"
+                                        </button>
+                                        <button
+                                            class="bg-gray-200 px-3 py-1 rounded"
+                                            on:click=move |_| set_all(false)
+                                        >
+                                            "All off"
+                                        </button>
+                                    </div>
+                                </div>
+                            }
+                                .into_any()
+                        }
+                        Err(e) => view! { <p>{format!("Outlets error: {e}")}</p> }.into_any(),
+                    })
+            }}
+        </Suspense>
+    }
 }
 
 #[component]
@@ -191,13 +708,14 @@ pub fn RingDoorbellView(device: Device) -> impl IntoView {
 
 #[component]
 pub fn RokuTvView(device: Device) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
     let toggle_action = Action::new({
         let ip = device.ip.clone();
         move |value| {
             let ip = ip.clone();
             let value = *value;
             async move {
-                handle_smart_plug_toggle(value, ip).await.unwrap();
+                toast_on_error(toast, handle_roku_tv_toggle(value, ip).await);
             }
         }
     });
@@ -209,3 +727,311 @@ pub fn RokuTvView(device: Device) -> impl IntoView {
 pub fn StoplightView(device: Device) -> impl IntoView {
     view! { <div>"Power State: " {device.battery_percentage}</div> }
 }
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+fn action_label(on: bool) -> &'static str {
+    if on { "On" } else { "Off" }
+}
+
+fn days_label(wday: &[u8; 7]) -> String {
+    if wday.iter().all(|day| *day == 1) {
+        return "Every day".to_string();
+    }
+    WEEKDAYS
+        .iter()
+        .zip(wday)
+        .filter(|(_, on)| **on == 1)
+        .map(|(day, _)| *day)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Schedule rules and the countdown stored on the device, read and written
+/// directly so changes don't depend on IronNest staying up.
+#[component]
+pub fn KasaRulesView(device_id: i64) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
+    let rules = Resource::new(|| (), move |_| get_kasa_rules(device_id));
+
+    // The rule being edited, which keeps the id and fields the form doesn't
+    // show. A default rule is added as a new one.
+    let editing = RwSignal::new(ScheduleRule::default());
+    let name = RwSignal::new(String::new());
+    let time = RwSignal::new("07:00".to_string());
+    let time_opt = RwSignal::new(RuleTime::Minutes);
+    let turn_on = RwSignal::new(true);
+    let days = RwSignal::new([true; 7]);
+    let edit = move |rule: ScheduleRule| {
+        name.set(rule.name.clone());
+        time.set(format!("{:02}:{:02}", rule.smin / 60, rule.smin % 60));
+        time_opt.set(rule.stime_opt);
+        turn_on.set(rule.sact == 1);
+        days.set(rule.wday.map(|day| day == 1));
+        editing.set(rule);
+    };
+    let reset = move || edit(ScheduleRule::default());
+
+    let save = move |rule: ScheduleRule| {
+        spawn_local(async move {
+            match save_kasa_schedule_rule(device_id, rule).await {
+                Ok(()) => {
+                    reset();
+                    rules.refetch();
+                }
+                Err(e) => toast.set(Some(Toast(format!("Save schedule error: {e}")))),
+            }
+        });
+    };
+    let save_form = move |_| {
+        let smin = time
+            .get_untracked()
+            .split_once(':')
+            .and_then(|(hours, minutes)| {
+                Some(hours.parse::<u16>().ok()? * 60 + minutes.parse::<u16>().ok()?)
+            })
+            .unwrap_or_default();
+        save(ScheduleRule {
+            name: name.get_untracked(),
+            enable: 1,
+            wday: days.get_untracked().map(u8::from),
+            repeat: 1,
+            stime_opt: time_opt.get_untracked(),
+            smin,
+            sact: i8::from(turn_on.get_untracked()),
+            ..editing.get_untracked()
+        });
+    };
+    let remove = move |countdown: bool, id: String| {
+        spawn_local(async move {
+            if let Err(e) = delete_kasa_rule(device_id, countdown, id).await {
+                toast.set(Some(Toast(format!("Delete rule error: {e}"))));
+            }
+            rules.refetch();
+        });
+    };
+
+    let countdown_minutes = RwSignal::new("30".to_string());
+    let countdown_on = RwSignal::new(false);
+    let start_countdown = move |_| {
+        let Ok(minutes) = countdown_minutes.get_untracked().parse::<u32>() else {
+            toast.set(Some(Toast(
+                "Countdown minutes must be a number".to_string(),
+            )));
+            return;
+        };
+        let rule = CountdownRule {
+            name: "IronNest countdown".to_string(),
+            enable: 1,
+            delay: minutes * 60,
+            act: u8::from(countdown_on.get_untracked()),
+            ..CountdownRule::default()
+        };
+        spawn_local(async move {
+            match save_kasa_countdown_rule(device_id, rule).await {
+                Ok(()) => rules.refetch(),
+                Err(e) => toast.set(Some(Toast(format!("Countdown error: {e}")))),
+            }
+        });
+    };
+
+    let input_class = "rounded-md border-0 py-1 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 sm:text-sm";
+    view! {
+        <div class="flex flex-col gap-2 text-left text-sm">
+            <Suspense fallback=|| {
+                view! { <p>"Loading schedules..."</p> }
+            }>
+                {move || {
+                    rules
+                        .get()
+                        .map(|data| match data {
+                            Ok(rules) => {
+                                view! {
+                                    <ul class="space-y-1">
+                                        {rules
+                                            .schedule
+                                            .into_iter()
+                                            .map(|rule| {
+                                                let id = rule.id.clone().unwrap_or_default();
+                                                let toggled = ScheduleRule {
+                                                    enable: u8::from(rule.enable == 0),
+                                                    ..rule.clone()
+                                                };
+                                                view! {
+                                                    <li class="flex items-center gap-2">
+                                                        <input
+                                                            type="checkbox"
+                                                            prop:checked=rule.enable == 1
+                                                            on:change=move |_| save(toggled.clone())
+                                                        />
+                                                        <span class="font-medium">
+                                                            {rule.time_label()}
+                                                        </span>
+                                                        <span>{action_label(rule.sact == 1)}</span>
+                                                        <span class="text-gray-500">
+                                                            {days_label(&rule.wday)}
+                                                        </span>
+                                                        <span class="flex-grow text-gray-500">
+                                                            {rule.name.clone()}
+                                                        </span>
+                                                        <button
+                                                            class="text-indigo-600"
+                                                            on:click=move |_| edit(rule.clone())
+                                                        >
+                                                            "Edit"
+                                                        </button>
+                                                        <button
+                                                            class="text-red-600"
+                                                            on:click=move |_| remove(false, id.clone())
+                                                        >
+                                                            "Delete"
+                                                        </button>
+                                                    </li>
+                                                }
+                                            })
+                                            .collect::<Vec<_>>()}
+                                    </ul>
+                                    <div>
+                                        {rules
+                                            .count_down
+                                            .into_iter()
+                                            .map(|rule| {
+                                                let id = rule.id.clone().unwrap_or_default();
+                                                view! {
+                                                    <p class="flex items-center gap-2">
+                                                        <span>
+                                                            {format!(
+                                                                "Turns {} in {} min",
+                                                                action_label(rule.act == 1).to_lowercase(),
+                                                                rule.remain.unwrap_or(rule.delay).div_ceil(60),
+                                                            )}
+                                                        </span>
+                                                        <button
+                                                            class="text-red-600"
+                                                            on:click=move |_| remove(true, id.clone())
+                                                        >
+                                                            "Cancel"
+                                                        </button>
+                                                    </p>
+                                                }
+                                            })
+                                            .collect::<Vec<_>>()}
+                                    </div>
+                                }
+                                    .into_any()
+                            }
+                            Err(e) => view! { <p>{format!("Schedules error: {e}")}</p> }.into_any(),
+                        })
+                }}
+            </Suspense>
+            <hr />
+            <div class="flex flex-wrap items-center gap-2">
+                <select
+                    class=input_class
+                    on:change=move |ev| {
+                        time_opt
+                            .set(
+                                match event_target_value(&ev).as_str() {
+                                    "sunrise" => RuleTime::Sunrise,
+                                    "sunset" => RuleTime::Sunset,
+                                    _ => RuleTime::Minutes,
+                                },
+                            )
+                    }
+                >
+                    <option value="time" selected=move || time_opt.get() == RuleTime::Minutes>
+                        "At"
+                    </option>
+                    <option value="sunrise" selected=move || time_opt.get() == RuleTime::Sunrise>
+                        "Sunrise"
+                    </option>
+                    <option value="sunset" selected=move || time_opt.get() == RuleTime::Sunset>
+                        "Sunset"
+                    </option>
+                </select>
+                <input
+                    type="time"
+                    class=input_class
+                    prop:disabled=move || time_opt.get() != RuleTime::Minutes
+                    bind:value=time
+                />
+                <select
+                    class=input_class
+                    on:change=move |ev| turn_on.set(event_target_value(&ev) == "on")
+                >
+                    <option value="on" selected=move || turn_on.get()>
+                        "Turn on"
+                    </option>
+                    <option value="off" selected=move || !turn_on.get()>
+                        "Turn off"
+                    </option>
+                </select>
+                <input type="text" placeholder="Name" class=input_class bind:value=name />
+            </div>
+            <div class="flex gap-2">
+                {WEEKDAYS
+                    .iter()
+                    .enumerate()
+                    .map(|(index, day)| {
+                        view! {
+                            <label class="flex items-center gap-1">
+                                <input
+                                    type="checkbox"
+                                    prop:checked=move || days.with(|days| days[index])
+                                    on:change=move |ev| {
+                                        let checked = event_target_checked(&ev);
+                                        days.update(|days| days[index] = checked);
+                                    }
+                                />
+                                {*day}
+                            </label>
+                        }
+                    })
+                    .collect::<Vec<_>>()}
+            </div>
+            <div class="flex gap-2">
+                <button class="bg-indigo-600 text-white px-2 py-1 rounded" on:click=save_form>
+                    {move || {
+                        if editing.with(|rule| rule.id.is_some()) { "Save rule" } else { "Add rule" }
+                    }}
+                </button>
+                {move || {
+                    editing
+                        .with(|rule| rule.id.is_some())
+                        .then(|| {
+                            view! {
+                                <button class="text-gray-500" on:click=move |_| reset()>
+                                    "Cancel"
+                                </button>
+                            }
+                        })
+                }}
+            </div>
+            <hr />
+            <div class="flex items-center gap-2">
+                <span>"Countdown"</span>
+                <input
+                    type="number"
+                    min="1"
+                    class=format!("w-16 {input_class}")
+                    bind:value=countdown_minutes
+                />
+                <span>"min, then"</span>
+                <select
+                    class=input_class
+                    on:change=move |ev| countdown_on.set(event_target_value(&ev) == "on")
+                >
+                    <option value="off" selected=move || !countdown_on.get()>
+                        "turn off"
+                    </option>
+                    <option value="on" selected=move || countdown_on.get()>
+                        "turn on"
+                    </option>
+                </select>
+                <button class="bg-indigo-600 text-white px-2 py-1 rounded" on:click=start_countdown>
+                    "Start"
+                </button>
+            </div>
+        </div>
+    }
+}
@@ -1,6 +1,9 @@
 use {
     crate::{
-        components::checkbox::Checkbox,
+        components::{
+            checkbox::Checkbox,
+            layout::{ToastContext, toast_on_error},
+        },
         integrations::iron_nest::types::{Device, DeviceType},
         server::{
             roku::handle_roku_tv_toggle,
@@ -66,7 +69,7 @@ pub fn DeviceListPanel(
 pub fn DeviceCard(device: Device) -> impl IntoView {
     view! {
         <div class="bg-white text-black p-2 rounded-lg shadow-md flex flex-col items-center justify-between">
-            <p class="text-sm font-medium text-nowrap w-full">{device.name.clone()}</p>
+            <p class="text-sm font-medium text-nowrap w-full">{device.label().to_owned()}</p>
             <div class="mt-2">
                 {match device.device_type {
                     DeviceType::KasaPlug => {
@@ -99,13 +102,14 @@ pub fn DeviceCard(device: Device) -> impl IntoView {
 
 #[component]
 pub fn SmartPlugItem(device: Device) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
     let toggle_action = Action::new({
         let ip = device.ip.clone();
         move |value| {
             let ip = ip.clone();
             let value = *value;
             async move {
-                handle_smart_plug_toggle(value, ip).await.unwrap();
+                toast_on_error(toast, handle_smart_plug_toggle(value, ip).await);
             }
         }
     });
@@ -115,13 +119,14 @@ pub fn SmartPlugItem(device: Device) -> impl IntoView {
 
 #[component]
 pub fn SmartDimmerItem(device: Device) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
     let toggle_action = Action::new({
         let ip = device.ip.clone();
         move |value| {
             let ip = ip.clone();
             let value = *value;
             async move {
-                handle_smart_plug_toggle(value, ip).await.unwrap();
+                toast_on_error(toast, handle_smart_plug_toggle(value, ip).await);
             }
         }
     });
@@ -131,17 +136,19 @@ pub fn SmartDimmerItem(device: Device) -> impl IntoView {
 
 #[component]
 pub fn SmartPowerStripItem(device: Device) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
     let toggle_action = Action::new({
         let ip = device.ip.clone();
-        let child_id = device.child_id.clone().unwrap();
+        let child_id = device.child_id.clone().unwrap_or_default();
         move |value| {
             let ip = ip.clone();
             let child_id = child_id.clone();
             let value = *value;
             async move {
-                handle_smart_power_strip_toggle(value, ip, child_id)
-                    .await
-                    .unwrap();
+                toast_on_error(
+                    toast,
+                    handle_smart_power_strip_toggle(value, ip, child_id).await,
+                );
             }
         }
     });
@@ -161,13 +168,14 @@ pub fn StoplightItem() -> impl IntoView {
 
 #[component]
 pub fn SmartLightItem(device: Device) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
     let toggle_action = Action::new({
         let ip = device.ip.clone();
         move |value| {
             let ip = ip.clone();
             let value = *value;
             async move {
-                handle_smart_light_toggle(value, ip).await.unwrap();
+                toast_on_error(toast, handle_smart_light_toggle(value, ip).await);
             }
         }
     });
@@ -177,13 +185,14 @@ pub fn SmartLightItem(device: Device) -> impl IntoView {
 
 #[component]
 pub fn RokuTvItem(device: Device) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
     let toggle_action = Action::new({
         let ip = device.ip.clone();
         move |value| {
             let ip = ip.clone();
             let value = *value;
             async move {
-                handle_roku_tv_toggle(value, ip).await.unwrap();
+                toast_on_error(toast, handle_roku_tv_toggle(value, ip).await);
             }
         }
     });
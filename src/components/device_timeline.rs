@@ -0,0 +1,110 @@
+use {
+    crate::{
+        integrations::iron_nest::types::DeviceStateHistory,
+        server::devices::get_device_state_history,
+    },
+    chrono::{DateTime, Local, Utc},
+    leptos::prelude::*,
+};
+
+fn power_state_label(power_state: i32) -> &'static str {
+    if power_state == 1 { "On" } else { "Off" }
+}
+
+/// Splits the history into `(power_state, start, end)` segments, the last one
+/// running until `now`.
+fn timeline_segments(
+    history: &[DeviceStateHistory],
+    now: DateTime<Utc>,
+) -> Vec<(i32, DateTime<Utc>, DateTime<Utc>)> {
+    history
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let end = history.get(i + 1).map_or(now, |next| next.recorded_at);
+            (entry.power_state, entry.recorded_at, end)
+        })
+        .collect()
+}
+
+#[component]
+pub fn DeviceTimeline(device_id: i64) -> impl IntoView {
+    let history = Resource::new(
+        move || device_id,
+        |device_id| get_device_state_history(device_id, None, None),
+    );
+
+    view! {
+        <Suspense fallback=|| {
+            view! { <p class="text-sm text-gray-500">"Loading history..."</p> }
+        }>
+            {move || {
+                history
+                    .get()
+                    .map(|data| match data {
+                        Ok(history) if history.is_empty() => {
+                            view! {
+                                <p class="text-sm text-gray-500">"No state changes in the last 24 hours"</p>
+                            }
+                                .into_any()
+                        }
+                        Ok(history) => {
+                            let now = Utc::now();
+                            let segments = timeline_segments(&history, now);
+                            let total = (now - history[0].recorded_at).num_seconds().max(1) as f64;
+                            view! {
+                                <div class="space-y-2">
+                                    <div class="flex h-4 w-full overflow-hidden rounded bg-gray-100">
+                                        {segments
+                                            .iter()
+                                            .map(|(power_state, start, end)| {
+                                                let width = (*end - *start).num_seconds() as f64
+                                                    / total * 100.0;
+                                                let class = if *power_state == 1 {
+                                                    "h-full bg-green-500"
+                                                } else {
+                                                    "h-full bg-gray-300"
+                                                };
+                                                view! {
+                                                    <div
+                                                        class=class
+                                                        style=format!("width: {width}%")
+                                                        title=power_state_label(*power_state)
+                                                    ></div>
+                                                }
+                                            })
+                                            .collect::<Vec<_>>()}
+                                    </div>
+                                    <ul class="text-xs text-gray-600">
+                                        {history
+                                            .into_iter()
+                                            .rev()
+                                            .map(|entry| {
+                                                view! {
+                                                    <li>
+                                                        {entry
+                                                            .recorded_at
+                                                            .with_timezone(&Local)
+                                                            .format("%b %d %H:%M:%S")
+                                                            .to_string()} " - "
+                                                        {power_state_label(entry.power_state)}
+                                                        {entry
+                                                            .battery_percentage
+                                                            .map(|battery| format!(" ({battery}% battery)"))}
+                                                    </li>
+                                                }
+                                            })
+                                            .collect::<Vec<_>>()}
+                                    </ul>
+                                </div>
+                            }
+                                .into_any()
+                        }
+                        Err(e) => {
+                            view! { <p>{format!("History error: {e}")}</p> }.into_any()
+                        }
+                    })
+            }}
+        </Suspense>
+    }
+}
@@ -0,0 +1,158 @@
+use {
+    crate::{
+        integrations::iron_nest::types::{Device, EnergyPeriod, EnergyUsage},
+        server::energy::{get_energy_usage, get_latest_energy_readings},
+    },
+    chrono::{DateTime, Utc},
+    leptos::prelude::*,
+    std::collections::BTreeMap,
+};
+
+/// Total usage of all devices per period, oldest first.
+fn totals_by_period(usage: &[EnergyUsage]) -> Vec<(DateTime<Utc>, f64)> {
+    let mut totals = BTreeMap::new();
+    for entry in usage {
+        *totals.entry(entry.period_start).or_insert(0.) += entry.kwh;
+    }
+    totals.into_iter().collect()
+}
+
+/// Current power draw of each device with an emeter, and daily or monthly
+/// energy totals.
+#[component]
+pub fn EnergyPanel(devices: Resource<Result<Vec<Device>, ServerFnError>>) -> impl IntoView {
+    let (period, set_period) = signal(EnergyPeriod::Day);
+    let latest = Resource::new(|| (), |_| get_latest_energy_readings());
+    let usage = Resource::new(
+        move || period.get(),
+        |period| get_energy_usage(period, None),
+    );
+
+    let label = move |device_id: i64| {
+        devices
+            .get()
+            .and_then(Result::ok)
+            .and_then(|devices| {
+                devices
+                    .into_iter()
+                    .find(|device| device.id == device_id)
+                    .map(|device| device.label().to_string())
+            })
+            .unwrap_or_else(|| format!("#{device_id}"))
+    };
+
+    view! {
+        <div class="col-span-3 h-[264px] panel bg-white p-2 rounded-md shadow-lg overflow-y-auto">
+            <div class="flex justify-between items-center">
+                <h2 class="text-lg text-black">"Energy"</h2>
+                <div class="flex gap-2 text-sm">
+                    <button
+                        class=move || {
+                            if period.get() == EnergyPeriod::Day { "font-bold" } else { "" }
+                        }
+                        on:click=move |_| set_period.set(EnergyPeriod::Day)
+                    >
+                        "Daily"
+                    </button>
+                    <button
+                        class=move || {
+                            if period.get() == EnergyPeriod::Month { "font-bold" } else { "" }
+                        }
+                        on:click=move |_| set_period.set(EnergyPeriod::Month)
+                    >
+                        "Monthly"
+                    </button>
+                </div>
+            </div>
+            <Suspense fallback=|| {
+                view! { <p>"Loading energy..."</p> }
+            }>
+                {move || {
+                    let latest = latest.get().map(|res| res.unwrap_or_default()).unwrap_or_default();
+                    usage
+                        .get()
+                        .map(|data| match data {
+                            Ok(usage) => {
+                                let current_period = usage.iter().map(|entry| entry.period_start).max();
+                                let totals = totals_by_period(&usage);
+                                let max_total = totals
+                                    .iter()
+                                    .map(|(_, kwh)| *kwh)
+                                    .fold(0., f64::max)
+                                    .max(f64::EPSILON);
+                                let date_format = match period.get_untracked() {
+                                    EnergyPeriod::Day => "%b %d",
+                                    EnergyPeriod::Month => "%b %Y",
+                                };
+                                view! {
+                                    <table class="w-full text-sm">
+                                        <thead>
+                                            <tr class="text-left text-gray-500">
+                                                <th>"Device"</th>
+                                                <th>"Now"</th>
+                                                <th>
+                                                    {match period.get_untracked() {
+                                                        EnergyPeriod::Day => "Today",
+                                                        EnergyPeriod::Month => "This month",
+                                                    }}
+                                                </th>
+                                            </tr>
+                                        </thead>
+                                        <tbody>
+                                            {latest
+                                                .into_iter()
+                                                .map(|reading| {
+                                                    let kwh = usage
+                                                        .iter()
+                                                        .find(|entry| {
+                                                            entry.device_id == reading.device_id
+                                                                && Some(entry.period_start) == current_period
+                                                        })
+                                                        .map(|entry| entry.kwh)
+                                                        .unwrap_or_default();
+                                                    view! {
+                                                        <tr>
+                                                            <td>{label(reading.device_id)}</td>
+                                                            <td>
+                                                                {reading
+                                                                    .power
+                                                                    .map(|power| format!("{power:.1} W"))
+                                                                    .unwrap_or_default()}
+                                                            </td>
+                                                            <td>{format!("{kwh:.2} kWh")}</td>
+                                                        </tr>
+                                                    }
+                                                })
+                                                .collect::<Vec<_>>()}
+                                        </tbody>
+                                    </table>
+                                    <ul class="mt-2 space-y-0.5 text-xs">
+                                        {totals
+                                            .into_iter()
+                                            .rev()
+                                            .map(|(period_start, kwh)| {
+                                                let width = format!("width: {}%", kwh / max_total * 100.);
+                                                view! {
+                                                    <li class="flex items-center gap-2">
+                                                        <span class="w-16 text-gray-500">
+                                                            {period_start.format(date_format).to_string()}
+                                                        </span>
+                                                        <div class="flex-grow bg-gray-100 h-3 rounded">
+                                                            <div class="bg-indigo-500 h-3 rounded" style=width></div>
+                                                        </div>
+                                                        <span class="w-20 text-right">{format!("{kwh:.2} kWh")}</span>
+                                                    </li>
+                                                }
+                                            })
+                                            .collect::<Vec<_>>()}
+                                    </ul>
+                                }
+                                    .into_any()
+                            }
+                            Err(e) => view! { <p>{format!("Energy error: {e}")}</p> }.into_any(),
+                        })
+                }}
+            </Suspense>
+        </div>
+    }
+}
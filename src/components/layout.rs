@@ -29,6 +29,14 @@ pub struct Toast(pub String);
 
 pub type ToastContext = RwSignal<Option<Toast>>;
 
+/// Shows the error of a server function called from an event handler, so a
+/// device that didn't respond isn't silently shown in the wrong state.
+pub fn toast_on_error<T>(toast: ToastContext, result: Result<T, ServerFnError>) {
+    if let Err(e) = result {
+        toast.set(Some(Toast(e.to_string())));
+    }
+}
+
 #[component]
 pub fn App() -> impl IntoView {
     // Provides context that manages stylesheets, titles, meta tags, etc.
@@ -37,7 +45,7 @@ pub fn App() -> impl IntoView {
     let toast = ToastContext::new(None);
     Effect::new(move |_| {
         if toast.get().is_some() {
-            Timeout::new(500, move || {
+            Timeout::new(3000, move || {
                 toast.set(None);
             })
         } else {
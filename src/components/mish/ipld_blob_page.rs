@@ -60,35 +60,16 @@ pub async fn get_ipld_blob_query(
 
 #[server(name = SetIpldBlob, encoding = "cbor")]
 async fn set_ipld_blob(content: String) -> Result<String, ServerFnError> {
+    use crate::integrations::iron_nest::insert_ipld_blob;
+
     let pool = use_context::<sqlx::PgPool>().unwrap();
     let content = hex::decode(content).unwrap();
-    let cid = set_mish_state_query(&pool, content).await?;
+    // TODO support dag-json and raw?
+    let cid = insert_ipld_blob(&pool, &content).await?;
     println!("cid: {}", cid);
     Ok(cid.to_string())
 }
 
-#[cfg(feature = "ssr")]
-pub async fn set_mish_state_query(
-    pool: &sqlx::PgPool,
-    content: Vec<u8>,
-) -> Result<Cid, sqlx::Error> {
-    use multihash_codetable::{Code, MultihashDigest};
-    // TODO support dag-json and raw?
-    let cid = Cid::new_v1(ipld_codecs::RAW, Code::Sha2_256.digest(&content));
-    let query = "
-        INSERT INTO ipld_blobs (cid, content)
-        VALUES ($1, $2)
-        ON CONFLICT (cid) DO NOTHING
-    ";
-    sqlx::query(query)
-        .bind(cid.to_bytes())
-        .bind(content)
-        .execute(pool)
-        .await
-        .unwrap();
-    Ok(cid)
-}
-
 #[component]
 pub fn IpldBlobPage() -> impl IntoView {
     #[derive(Params, PartialEq)]
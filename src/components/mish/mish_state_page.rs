@@ -63,16 +63,18 @@ pub async fn get_mish_state_query(
 
 #[server(SetMishState)]
 async fn set_mish_state(name: String, state: String) -> Result<(), ServerFnError> {
-    use crate::integrations::iron_nest::mish::MishStateModification;
+    use crate::integrations::iron_nest::{
+        events::{Event, EventBus},
+        mish::MishStateModification,
+    };
     let pool = use_context::<sqlx::PgPool>().unwrap();
-    let mish_state_modification_bus_sender =
-        use_context::<tokio::sync::mpsc::UnboundedSender<MishStateModification>>().unwrap();
+    let event_bus = use_context::<EventBus>().unwrap();
     let state = hex::decode(state).unwrap();
     let state = serde_json::from_slice(&state).unwrap();
     set_mish_state_query(&pool, &name, &state).await?;
-    mish_state_modification_bus_sender
-        .send(MishStateModification::CreateOrUpdate { name, state })
-        .unwrap();
+    event_bus.publish(Event::MishStateChanged(
+        MishStateModification::CreateOrUpdate { name, state },
+    ));
     Ok(())
 }
 
@@ -98,14 +100,16 @@ pub async fn set_mish_state_query(
 
 #[server(DeleteMishState)]
 async fn delete_mish_state(name: String) -> Result<(), ServerFnError> {
-    use crate::integrations::iron_nest::mish::MishStateModification;
+    use crate::integrations::iron_nest::{
+        events::{Event, EventBus},
+        mish::MishStateModification,
+    };
     let pool = use_context::<sqlx::PgPool>().expect("PgPool context should be set");
-    let mish_state_modification_bus_sender =
-        use_context::<tokio::sync::mpsc::UnboundedSender<MishStateModification>>().unwrap();
+    let event_bus = use_context::<EventBus>().unwrap();
     delete_mish_state_query(&pool, &name).await?;
-    mish_state_modification_bus_sender
-        .send(MishStateModification::Delete { name })
-        .unwrap();
+    event_bus.publish(Event::MishStateChanged(MishStateModification::Delete {
+        name,
+    }));
     Ok(())
 }
 
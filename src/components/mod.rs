@@ -6,15 +6,19 @@ pub mod device_list;
 pub mod device_list_card;
 pub mod device_modal;
 pub mod device_panel;
+pub mod device_timeline;
+pub mod energy_panel;
 pub mod layout;
 pub mod login_form;
 pub mod mish;
 pub mod navbar;
+pub mod now_playing_panel;
 pub mod pages;
 pub mod planned_meals;
 pub mod refresh_button;
 pub mod ring_cameras;
 pub mod roku_tv_remote;
+pub mod scenes_panel;
 pub mod select;
 pub mod slider;
 pub mod text_input;
@@ -0,0 +1,130 @@
+use {
+    crate::{
+        components::refresh_button::Refresh_Button,
+        integrations::roku::types::{NowPlaying, PlaybackState},
+        server::roku::get_now_playing,
+    },
+    leptos::prelude::*,
+    std::time::Duration,
+};
+
+/// How often the panel re-reads what the Roku job last stored.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Formats a playback time as "1:02:03", or "2:03" under an hour.
+fn format_ms(ms: u64) -> String {
+    let secs = ms / 1000;
+    let (hours, minutes, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{secs:02}")
+    } else {
+        format!("{minutes}:{secs:02}")
+    }
+}
+
+fn progress(now_playing: &NowPlaying) -> impl IntoView + use<> {
+    let position = now_playing.position_ms.unwrap_or_default();
+    match now_playing.duration_ms.filter(|duration| *duration > 0) {
+        Some(duration) => {
+            let width = format!(
+                "width: {}%",
+                (position as f64 / duration as f64 * 100.).min(100.)
+            );
+            view! {
+                <div class="flex items-center gap-2 text-xs text-gray-500">
+                    <span>{format_ms(position)}</span>
+                    <div class="flex-grow bg-gray-100 h-2 rounded">
+                        <div class="bg-indigo-500 h-2 rounded" style=width></div>
+                    </div>
+                    <span>{format_ms(duration)}</span>
+                </div>
+            }
+            .into_any()
+        }
+        None => now_playing
+            .position_ms
+            .map(|position| {
+                view! { <p class="text-xs text-gray-500">{format_ms(position)}</p> }
+            })
+            .into_any(),
+    }
+}
+
+/// The app, title and playback progress of every Roku, as last polled by the
+/// Roku job.
+#[component]
+pub fn NowPlayingPanel() -> impl IntoView {
+    let now_playing = Resource::new(|| (), |_| get_now_playing());
+
+    Effect::new(move |_| {
+        if let Ok(handle) =
+            set_interval_with_handle(move || now_playing.refetch(), REFRESH_INTERVAL)
+        {
+            on_cleanup(move || handle.clear());
+        }
+    });
+
+    view! {
+        <div class="col-span-3 h-[264px] panel bg-white p-2 rounded-md shadow-lg overflow-y-auto">
+            <div class="flex justify-between items-center">
+                <h2 class="text-lg text-black">"Now playing"</h2>
+                <Refresh_Button on_change=Box::new(move || now_playing.refetch()) />
+            </div>
+            <Suspense fallback=|| {
+                view! { <p>"Loading now playing..."</p> }
+            }>
+                {move || {
+                    now_playing
+                        .get()
+                        .map(|data| match data {
+                            Ok(rokus) if rokus.is_empty() => {
+                                view! { <p class="text-sm text-gray-500">"No Rokus polled yet"</p> }
+                                    .into_any()
+                            }
+                            Ok(rokus) => {
+                                view! {
+                                    <ul class="mt-2 space-y-3">
+                                        {rokus
+                                            .into_iter()
+                                            .map(|roku| {
+                                                let now_playing = roku.now_playing;
+                                                let state_class = match now_playing.state {
+                                                    PlaybackState::Playing => "text-green-600",
+                                                    PlaybackState::Paused | PlaybackState::Buffering => {
+                                                        "text-yellow-600"
+                                                    }
+                                                    PlaybackState::Idle => "text-gray-500",
+                                                };
+                                                view! {
+                                                    <li>
+                                                        <div class="flex justify-between text-sm">
+                                                            <span class="font-bold">{roku.name}</span>
+                                                            <span class=state_class>
+                                                                {now_playing.state.to_string()}
+                                                            </span>
+                                                        </div>
+                                                        <p class="text-sm">
+                                                            {now_playing.app_name.clone().unwrap_or_default()}
+                                                        </p>
+                                                        {now_playing
+                                                            .title
+                                                            .clone()
+                                                            .map(|title| {
+                                                                view! { <p class="text-sm text-gray-700">{title}</p> }
+                                                            })}
+                                                        {progress(&now_playing)}
+                                                    </li>
+                                                }
+                                            })
+                                            .collect::<Vec<_>>()}
+                                    </ul>
+                                }
+                                    .into_any()
+                            }
+                            Err(e) => view! { <p>{format!("Now playing error: {e}")}</p> }.into_any(),
+                        })
+                }}
+            </Suspense>
+        </div>
+    }
+}
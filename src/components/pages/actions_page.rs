@@ -294,6 +294,9 @@ pub fn ActionsPage() -> impl IntoView {
                                                                                         label="Function name".to_string()
                                                                                         name="function_name".to_string()
                                                                                         data=vec![
+                                                                                            "device_command".to_owned(),
+                                                                                            "group_command".to_owned(),
+                                                                                            "activate_scene".to_owned(),
                                                                                             "tplink_set_light_brightness".to_owned(),
                                                                                             "tplink_set_dimmer_brightness".to_owned(),
                                                                                             "tplink_turn_light_on_off".to_owned(),
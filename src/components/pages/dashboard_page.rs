@@ -2,12 +2,11 @@ use {
     crate::{
         components::{
             command_box::CommandBox, device_list::DeviceList, device_panel::DeviceListPanel,
+            energy_panel::EnergyPanel, now_playing_panel::NowPlayingPanel,
             planned_meals::PlannedMeals, ring_cameras::RingCameraPanel,
-            roku_tv_remote::RokuTvRemote,
-        },
-        integrations::{
-            instacart::types::ScheduledMeal, ring::types::RingCamera, roku::types::AppsAppWithIcon,
+            roku_tv_remote::RokuTvRemote, scenes_panel::ScenesPanel,
         },
+        integrations::{instacart::types::ScheduledMeal, ring::types::RingCamera},
         server::dashboard_page::get_devices,
     },
     leptos::prelude::*,
@@ -33,16 +32,12 @@ pub struct DashboardValues {
     pub ws_url: String,
     pub location_name: String,
     pub cameras: Vec<RingCamera>,
-    pub roku_apps: Vec<AppsAppWithIcon>,
     pub scheduled_meals: Vec<ScheduledMeal>,
 }
 
 #[server(GetDashboardValues)]
 pub async fn get_dashboard_values() -> Result<DashboardValues, ServerFnError> {
-    use {
-        // crate::integrations::roku::{roku_get_apps, roku_get_channel_icon},
-        sqlx::{PgPool, Postgres, Row},
-    };
+    use sqlx::{PgPool, Postgres, Row};
 
     let pool = use_context::<PgPool>().unwrap();
 
@@ -52,20 +47,6 @@ pub async fn get_dashboard_values() -> Result<DashboardValues, ServerFnError> {
     .fetch_all(&pool)
     .await?;
 
-    // @TODO add error handling and make roku apps dynamic from roku devices in device table
-    // let apps = roku_get_apps("10.0.0.217").await;
-    // let mut apps_with_icon = Vec::new();
-
-    // for app in apps.apps.into_iter() {
-    //     apps_with_icon.push(AppsAppWithIcon {
-    //         icon: roku_get_channel_icon("10.0.0.217", &app.id).await,
-    //         name: app.name,
-    //         id: app.id,
-    //         app_type: app.app_type,
-    //         version: app.version,
-    //     });
-    // }
-
     let mut cameras = Vec::new();
     for ring_camera_row in ring_camera_rows {
         let video_events_query = "
@@ -121,7 +102,6 @@ pub async fn get_dashboard_values() -> Result<DashboardValues, ServerFnError> {
         location_name: "".to_string(),
         cameras,
         ws_url: "".to_string(),
-        roku_apps: Vec::new(),
         scheduled_meals: vec![
             ScheduledMeal {
                 recipie_name: "Pancakes & Eggs".to_owned(),
@@ -196,6 +176,14 @@ pub fn DashboardPage() -> impl IntoView {
                     device_ids: None,
                 },
             ),
+            (
+                "now_playing".to_string(),
+                PanelDataInner {
+                    component_type: "now_playing".to_string(),
+                    camera_id: None,
+                    device_ids: None,
+                },
+            ),
             (
                 "meals".to_string(),
                 PanelDataInner {
@@ -212,6 +200,22 @@ pub fn DashboardPage() -> impl IntoView {
                     device_ids: None,
                 },
             ),
+            (
+                "scenes".to_string(),
+                PanelDataInner {
+                    component_type: "scenes".to_string(),
+                    camera_id: None,
+                    device_ids: None,
+                },
+            ),
+            (
+                "energy".to_string(),
+                PanelDataInner {
+                    component_type: "energy".to_string(),
+                    camera_id: None,
+                    device_ids: None,
+                },
+            ),
             (
                 "ring1".to_string(),
                 PanelDataInner {
@@ -262,9 +266,12 @@ pub fn DashboardPage() -> impl IntoView {
     let component_order = RwSignal::new(vec![
         "ring2".to_string(),
         "roku1".to_string(),
+        "now_playing".to_string(),
         "meals".to_string(),
         "ring1".to_string(),
         "command".to_string(),
+        "scenes".to_string(),
+        "energy".to_string(),
         "toggles".to_string(),
         "toggles2".to_string(),
     ]);
@@ -428,14 +435,17 @@ pub fn DashboardPage() -> impl IntoView {
                                                                 leptos::logging::log!("for: id: {id}");
                                                                 match panel_data.inner.get().component_type.as_str() {
                                                                     "roku" => {
-                                                                        view! { <RokuTvRemote dashboard_values=dashboard_values /> }
+                                                                        view! { <RokuTvRemote devices=devices /> }
                                                                             .into_any()
                                                                     }
                                                                     "meals" => {
                                                                         view! { <PlannedMeals dashboard_values=dashboard_values /> }
                                                                             .into_any()
                                                                     }
+                                                                    "now_playing" => view! { <NowPlayingPanel /> }.into_any(),
                                                                     "command" => view! { <CommandBox /> }.into_any(),
+                                                                    "scenes" => view! { <ScenesPanel devices=devices /> }.into_any(),
+                                                                    "energy" => view! { <EnergyPanel devices=devices /> }.into_any(),
                                                                     "ring" => {
                                                                         let camera_id = panel_data
                                                                             .inner
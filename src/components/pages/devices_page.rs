@@ -1,4 +1,20 @@
-use {crate::integrations::iron_nest::types::Device, leptos::prelude::*};
+use {
+    crate::{
+        components::{
+            device_timeline::DeviceTimeline,
+            layout::{Toast, ToastContext},
+        },
+        integrations::iron_nest::types::{Device, DeviceCommand, DeviceGroup, DeviceType, Room},
+        server::{
+            devices::rename_device,
+            rooms::{
+                AddRoom, DeleteRoom, get_rooms, handle_group_command, set_device_room,
+                set_device_tags,
+            },
+        },
+    },
+    leptos::{prelude::*, task::spawn_local},
+};
 
 #[server(GetDevices)]
 pub async fn get_devices() -> Result<Vec<Device>, ServerFnError> {
@@ -10,8 +26,9 @@ pub async fn get_devices() -> Result<Vec<Device>, ServerFnError> {
     let pool = use_context::<PgPool>().unwrap();
 
     let query = "
-        SELECT id, name, device_type, ip, power_state, battery_percentage, last_seen, mac_address, child_id 
+        SELECT id, name, device_type, ip, power_state, battery_percentage, last_seen, mac_address, child_id, room_id, tags, display_name, icon, online
         FROM device
+        ORDER BY room_id NULLS LAST, COALESCE(display_name, name)
     ";
     sqlx::query_as::<Postgres, Device>(query)
         .fetch_all(&pool)
@@ -21,9 +38,25 @@ pub async fn get_devices() -> Result<Vec<Device>, ServerFnError> {
 
 #[component]
 pub fn DevicesPage() -> impl IntoView {
-    let devices = Resource::new(|| (), |_| get_devices());
+    let add_room_action = ServerAction::<AddRoom>::new();
+    let delete_room_action = ServerAction::<DeleteRoom>::new();
+    let rooms = Resource::new(
+        move || {
+            (
+                add_room_action.version().get(),
+                delete_room_action.version().get(),
+            )
+        },
+        |_| get_rooms(),
+    );
+    let devices = Resource::new(
+        move || delete_room_action.version().get(),
+        |_| get_devices(),
+    );
+
     view! {
         <main class="lg:pl-20">
+            <RoomsPanel rooms=rooms add_room_action=add_room_action delete_room_action=delete_room_action />
             <div class="lg:pl-4 -mx-4 -my-2 overflow-x-auto sm:-mx-6 lg:-mx-8 hidden md:block">
                 <div class="bg-white inline-block min-w-full py-2 align-middle sm:px-6 lg:px-8">
                     <table class="w-full divide-y divide-gray-300">
@@ -47,14 +80,32 @@ pub fn DevicesPage() -> impl IntoView {
                                 >
                                     "IP"
                                 </th>
+                                <th
+                                    scope="col"
+                                    class="px-3 py-3 text-left text-xs font-medium uppercase tracking-wide text-gray-500"
+                                >
+                                    "MAC"
+                                </th>
                                 <th
                                     scope="col"
                                     class="px-3 py-3 text-left text-xs font-medium uppercase tracking-wide text-gray-500"
                                 >
                                     "Power State"
                                 </th>
+                                <th
+                                    scope="col"
+                                    class="px-3 py-3 text-left text-xs font-medium uppercase tracking-wide text-gray-500"
+                                >
+                                    "Room"
+                                </th>
+                                <th
+                                    scope="col"
+                                    class="px-3 py-3 text-left text-xs font-medium uppercase tracking-wide text-gray-500"
+                                >
+                                    "Tags"
+                                </th>
                                 <th scope="col" class="relative py-3 pl-3 pr-4 sm:pr-0">
-                                    <span class="sr-only">Edit</span>
+                                    <span class="sr-only">History</span>
                                 </th>
                             </tr>
                         </thead>
@@ -67,6 +118,7 @@ pub fn DevicesPage() -> impl IntoView {
                                 }
                             }>
                                 {move || {
+                                    let rooms = rooms.get().and_then(Result::ok).unwrap_or_default();
                                     devices
                                         .get()
                                         .map(|data| {
@@ -74,10 +126,14 @@ pub fn DevicesPage() -> impl IntoView {
                                                 Ok(data) => {
                                                     data.into_iter()
                                                         .map(|device| {
+                                                            let device_id = device.id;
+                                                            let (show_history, set_show_history) = signal(
+                                                                false,
+                                                            );
                                                             view! {
                                                                 <tr>
                                                                     <td class="whitespace-nowrap py-4 pl-4 pr-3 text-sm font-medium text-gray-900 sm:pl-0">
-                                                                        {device.name}
+                                                                        <DeviceNameEditor device=device.clone() />
                                                                     </td>
                                                                     <th
                                                                         scope="col"
@@ -91,13 +147,52 @@ pub fn DevicesPage() -> impl IntoView {
                                                                     >
                                                                         {device.ip}
                                                                     </th>
+                                                                    <th
+                                                                        scope="col"
+                                                                        class="px-3 py-3 text-left text-xs font-medium uppercase tracking-wide text-gray-500"
+                                                                    >
+                                                                        {device.mac_address.unwrap_or_default()}
+                                                                    </th>
                                                                     <th
                                                                         scope="col"
                                                                         class="px-3 py-3 text-left text-xs font-medium uppercase tracking-wide text-gray-500"
                                                                     >
                                                                         {device.power_state}
                                                                     </th>
+                                                                    <td class="px-3 py-3">
+                                                                        <RoomSelect
+                                                                            device_id=device_id
+                                                                            room_id=device.room_id
+                                                                            rooms=rooms.clone()
+                                                                        />
+                                                                    </td>
+                                                                    <td class="px-3 py-3">
+                                                                        <TagsInput device_id=device_id tags=device.tags />
+                                                                    </td>
+                                                                    <td class="relative whitespace-nowrap py-4 pl-3 pr-4 text-right text-sm font-medium sm:pr-0">
+                                                                        <button
+                                                                            class="text-indigo-600 hover:text-indigo-900"
+                                                                            on:click=move |_| {
+                                                                                set_show_history.update(|show| *show = !*show)
+                                                                            }
+                                                                        >
+                                                                            "History"
+                                                                        </button>
+                                                                    </td>
                                                                 </tr>
+                                                                {move || {
+                                                                    show_history
+                                                                        .get()
+                                                                        .then(|| {
+                                                                            view! {
+                                                                                <tr>
+                                                                                    <td colspan="8" class="pb-4">
+                                                                                        <DeviceTimeline device_id=device_id />
+                                                                                    </td>
+                                                                                </tr>
+                                                                            }
+                                                                        })
+                                                                }}
                                                             }
                                                         })
                                                         .collect::<Vec<_>>()
@@ -123,3 +218,267 @@ pub fn DevicesPage() -> impl IntoView {
         </main>
     }
 }
+
+#[component]
+pub fn RoomsPanel(
+    rooms: Resource<Result<Vec<Room>, ServerFnError>>,
+    add_room_action: ServerAction<AddRoom>,
+    delete_room_action: ServerAction<DeleteRoom>,
+) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
+    let run_group_command = move |group: DeviceGroup, on: bool| {
+        spawn_local(async move {
+            let message =
+                match handle_group_command(group.clone(), DeviceCommand::SetPower { on }).await {
+                    Ok(result) if result.failed.is_empty() => {
+                        format!("Turned {} {group}", if on { "on" } else { "off" })
+                    }
+                    Ok(result) => format!("{} devices in {group} failed", result.failed.len()),
+                    Err(e) => format!("Group command error: {e}"),
+                };
+            toast.set(Some(Toast(message)));
+        });
+    };
+
+    view! {
+        <div class="bg-white py-4">
+            <h2 class="text-lg text-black">"Rooms"</h2>
+            <hr class="mb-2" />
+            <Suspense fallback=|| {
+                view! { <p>"Loading rooms..."</p> }
+            }>
+                {move || {
+                    rooms
+                        .get()
+                        .map(|data| match data {
+                            Ok(rooms) => {
+                                view! {
+                                    <ul class="space-y-2">
+                                        {rooms
+                                            .into_iter()
+                                            .map(|room| {
+                                                let on_group = DeviceGroup::Room(room.name.clone());
+                                                let off_group = on_group.clone();
+                                                view! {
+                                                    <li class="flex items-center gap-2 text-sm">
+                                                        <span class="font-medium">{room.name}</span>
+                                                        {room
+                                                            .zone
+                                                            .map(|zone| {
+                                                                view! {
+                                                                    <span class="text-gray-500">{format!("({zone})")}</span>
+                                                                }
+                                                            })}
+                                                        <button
+                                                            class="text-indigo-600"
+                                                            on:click=move |_| run_group_command(on_group.clone(), true)
+                                                        >
+                                                            "All on"
+                                                        </button>
+                                                        <button
+                                                            class="text-indigo-600"
+                                                            on:click=move |_| run_group_command(off_group.clone(), false)
+                                                        >
+                                                            "All off"
+                                                        </button>
+                                                        <button
+                                                            class="text-red-600"
+                                                            on:click=move |_| {
+                                                                delete_room_action.dispatch(DeleteRoom { id: room.id });
+                                                            }
+                                                        >
+                                                            "Delete"
+                                                        </button>
+                                                    </li>
+                                                }
+                                            })
+                                            .collect::<Vec<_>>()}
+                                    </ul>
+                                }
+                                    .into_any()
+                            }
+                            Err(e) => view! { <p>{format!("Rooms error: {e}")}</p> }.into_any(),
+                        })
+                }}
+            </Suspense>
+            <ActionForm action=add_room_action>
+                <div class="flex items-end gap-2 mt-2">
+                    <input
+                        type="text"
+                        name="name"
+                        placeholder="Room name"
+                        class="rounded-md border-0 py-1.5 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 sm:text-sm"
+                    />
+                    <input
+                        type="text"
+                        name="zone"
+                        placeholder="Zone (optional)"
+                        class="rounded-md border-0 py-1.5 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 sm:text-sm"
+                    />
+                    <button type="submit" class="bg-indigo-600 text-white px-4 py-2 rounded">
+                        "Add Room"
+                    </button>
+                </div>
+            </ActionForm>
+            {move || {
+                add_room_action
+                    .value()
+                    .get()
+                    .and_then(|value| value.err())
+                    .map(|e| view! { <p class="text-red-600">{format!("Add room error: {e}")}</p> })
+            }}
+        </div>
+    }
+}
+
+#[component]
+pub fn RoomSelect(device_id: i64, room_id: Option<i64>, rooms: Vec<Room>) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
+    view! {
+        <select
+            class="rounded-md border-0 py-1.5 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 sm:text-sm"
+            on:change=move |ev| {
+                let room_id = event_target_value(&ev).parse::<i64>().ok();
+                spawn_local(async move {
+                    if let Err(e) = set_device_room(device_id, room_id).await {
+                        toast.set(Some(Toast(format!("Set room error: {e}"))));
+                    }
+                });
+            }
+        >
+            <option value="" selected=room_id.is_none()>
+                "No room"
+            </option>
+            {rooms
+                .into_iter()
+                .map(|room| {
+                    view! {
+                        <option value=room.id.to_string() selected=room_id == Some(room.id)>
+                            {room.name}
+                        </option>
+                    }
+                })
+                .collect::<Vec<_>>()}
+        </select>
+    }
+}
+
+#[component]
+pub fn TagsInput(device_id: i64, tags: Vec<String>) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
+    view! {
+        <input
+            type="text"
+            placeholder="bedroom, lights"
+            value=tags.join(", ")
+            class="rounded-md border-0 py-1.5 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 sm:text-sm"
+            on:change=move |ev| {
+                let tags = event_target_value(&ev)
+                    .split(',')
+                    .map(str::to_string)
+                    .collect::<Vec<_>>();
+                spawn_local(async move {
+                    if let Err(e) = set_device_tags(device_id, tags).await {
+                        toast.set(Some(Toast(format!("Set tags error: {e}"))));
+                    }
+                });
+            }
+        />
+    }
+}
+
+#[component]
+pub fn DeviceNameEditor(device: Device) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
+    let device_id = device.id;
+    let can_push = matches!(
+        device.device_type,
+        DeviceType::KasaPlug
+            | DeviceType::KasaLight
+            | DeviceType::KasaDimmer
+            | DeviceType::KasaPowerStrip
+    );
+    let (label, set_label) = signal(device.label().to_owned());
+    let (icon, set_icon) = signal(device.icon.clone().unwrap_or_default());
+    let (editing, set_editing) = signal(false);
+    let display_name = RwSignal::new(device.display_name.clone().unwrap_or_default());
+    let icon_input = RwSignal::new(device.icon.clone().unwrap_or_default());
+    let push_to_device = RwSignal::new(false);
+    let discovered_name = device.name.clone();
+
+    let save = move |_| {
+        let discovered_name = discovered_name.clone();
+        let name = display_name.get_untracked();
+        let new_icon = icon_input.get_untracked();
+        let push = push_to_device.get_untracked();
+        spawn_local(async move {
+            match rename_device(device_id, Some(name.clone()), Some(new_icon.clone()), push).await {
+                Ok(()) => {
+                    let name = name.trim();
+                    set_label.set(if name.is_empty() {
+                        discovered_name
+                    } else {
+                        name.to_owned()
+                    });
+                    set_icon.set(new_icon.trim().to_owned());
+                    set_editing.set(false);
+                }
+                Err(e) => toast.set(Some(Toast(format!("Rename error: {e}")))),
+            }
+        });
+    };
+
+    view! {
+        {move || {
+            if editing.get() {
+                view! {
+                    <div class="flex items-center gap-2">
+                        <input
+                            type="text"
+                            placeholder="Icon"
+                            class="w-12 rounded-md border-0 py-1.5 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 sm:text-sm"
+                            bind:value=icon_input
+                        />
+                        <input
+                            type="text"
+                            placeholder=device.name.clone()
+                            class="rounded-md border-0 py-1.5 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 sm:text-sm"
+                            bind:value=display_name
+                        />
+                        {can_push
+                            .then(|| {
+                                view! {
+                                    <label class="text-xs text-gray-500">
+                                        <input type="checkbox" class="mr-1" bind:checked=push_to_device />
+                                        "Rename on device"
+                                    </label>
+                                }
+                            })}
+                        <button class="text-indigo-600" on:click=save.clone()>
+                            "Save"
+                        </button>
+                        <button class="text-gray-500" on:click=move |_| set_editing.set(false)>
+                            "Cancel"
+                        </button>
+                    </div>
+                }
+                    .into_any()
+            } else {
+                view! {
+                    <div class="flex items-center gap-1">
+                        <span>{move || icon.get()}</span>
+                        <span>{move || label.get()}</span>
+                        <span class="ml-1 text-xs text-gray-400">{format!("#{device_id}")}</span>
+                        <button
+                            class="ml-2 text-xs text-indigo-600"
+                            on:click=move |_| set_editing.set(true)
+                        >
+                            "Edit"
+                        </button>
+                    </div>
+                }
+                    .into_any()
+            }
+        }}
+    }
+}
@@ -1,15 +1,241 @@
 use {
     crate::{
-        components::checkbox::Checkbox,
-        server::integrations_page::{get_integrations, toggle_integration},
+        components::{
+            checkbox::Checkbox,
+            layout::{Toast, ToastContext},
+        },
+        integrations::iron_nest::types::{
+            IntegrationHealth, IntegrationSettings, IntegrationStatus, SecretField, secret_fields,
+        },
+        server::integrations_page::{
+            get_integration_secret_names, get_integration_statuses, get_integrations,
+            refresh_integration, restart_integration, set_integration_secret, toggle_integration,
+            update_integration_settings,
+        },
     },
-    leptos::prelude::*,
+    chrono::{DateTime, Utc},
+    leptos::{prelude::*, task::spawn_local},
 };
 
+fn format_time(time: Option<DateTime<Utc>>) -> String {
+    time.map(|time| time.format("%b %d %H:%M").to_string())
+        .unwrap_or_else(|| "Never".to_string())
+}
+
+/// Badge next to the enable toggle summarizing how the background job is
+/// doing.
+fn health_badge(status: Option<&IntegrationStatus>) -> (&'static str, String) {
+    match status {
+        None => ("bg-gray-100 text-gray-600", "No job".to_string()),
+        Some(status) if !status.running => ("bg-gray-100 text-gray-600", "Stopped".to_string()),
+        Some(status) => match &status.health {
+            IntegrationHealth::Unknown => ("bg-gray-100 text-gray-600", "Starting".to_string()),
+            IntegrationHealth::Healthy => ("bg-green-100 text-green-700", "Healthy".to_string()),
+            IntegrationHealth::Unhealthy(reason) => ("bg-red-100 text-red-700", reason.clone()),
+        },
+    }
+}
+
+/// A seconds input for one of the intervals in `settings`, if the integration
+/// has it.
+fn interval_input(
+    settings: RwSignal<IntegrationSettings>,
+    label: &'static str,
+    get: fn(&IntegrationSettings) -> Option<u32>,
+    set: fn(&mut IntegrationSettings, u32),
+) -> Option<impl IntoView> {
+    get(&settings.get_untracked())?;
+    Some(view! {
+        <label class="flex justify-between items-center gap-x-4 py-1">
+            <span class="text-gray-500">{label}</span>
+            <input
+                type="number"
+                min=IntegrationSettings::MIN_INTERVAL_SECS
+                class="w-28 rounded-md border-0 py-1 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 sm:text-sm"
+                prop:value=move || settings.with(|settings| get(settings).unwrap_or_default())
+                on:input=move |ev| {
+                    if let Ok(secs) = event_target_value(&ev).parse() {
+                        settings.update(|settings| set(settings, secs));
+                    }
+                }
+            />
+        </label>
+    })
+}
+
+/// Editable intervals of an integration's background job, in seconds, and
+/// the hosts TP-Link discovery scans.
 #[component]
-pub fn IntegrationsPage() -> impl IntoView {
-    let integrations = Resource::new(|| (), |_| get_integrations());
+fn IntegrationSettingsForm(
+    id: i64,
+    name: String,
+    settings: IntegrationSettings,
+    on_saved: Callback<()>,
+) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
+    // Only TP-Link discovery probes hosts by unicast
+    let scans = name == "tplink";
+    let scan_targets = RwSignal::new(settings.scan_targets.join(", "));
+    let settings = RwSignal::new(settings);
+
+    let save = move |_| {
+        let name = name.clone();
+        settings.update(|settings| {
+            settings.scan_targets = scan_targets.with_untracked(|targets| {
+                targets
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|target| !target.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            });
+        });
+        spawn_local(async move {
+            match update_integration_settings(id, settings.get_untracked()).await {
+                Ok(()) => {
+                    toast.set(Some(Toast(format!("Saved {name} settings"))));
+                    on_saved.run(());
+                }
+                Err(e) => toast.set(Some(Toast(format!("{name} settings error: {e}")))),
+            }
+        });
+    };
+
+    view! {
+        <div class="px-6 pb-4 text-sm leading-6">
+            {interval_input(
+                settings,
+                "Poll every (s)",
+                |settings| Some(settings.poll_interval_secs),
+                |settings, secs| settings.poll_interval_secs = secs,
+            )}
+            {interval_input(
+                settings,
+                "Discover every (s)",
+                |settings| settings.discovery_interval_secs,
+                |settings, secs| settings.discovery_interval_secs = Some(secs),
+            )}
+            {interval_input(
+                settings,
+                "Refresh auth every (s)",
+                |settings| settings.auth_interval_secs,
+                |settings, secs| settings.auth_interval_secs = Some(secs),
+            )}
+            {scans
+                .then(|| {
+                    view! {
+                        <label class="flex justify-between items-center gap-x-4 py-1">
+                            <span class="text-gray-500">"Also scan"</span>
+                            <input
+                                type="text"
+                                placeholder="10.0.20.0/24, 10.0.30.5"
+                                title="Hosts or CIDR ranges on other subnets, probed by unicast on each discovery"
+                                class="w-56 rounded-md border-0 py-1 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 sm:text-sm"
+                                bind:value=scan_targets
+                            />
+                        </label>
+                    }
+                })}
+            <button class="mt-1 bg-indigo-600 text-white px-3 py-1 rounded" on:click=save>
+                "Save settings"
+            </button>
+        </div>
+    }
+}
+
+/// Inputs for the credentials an integration needs. Stored values are never
+/// shown, only whether they're set.
+#[component]
+fn IntegrationSecretsForm(integration: String) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
+    let names = Resource::new(
+        {
+            let integration = integration.clone();
+            move || integration.clone()
+        },
+        get_integration_secret_names,
+    );
+
+    let save = move |integration: String, field: SecretField, value: RwSignal<String>| {
+        spawn_local(async move {
+            let result =
+                set_integration_secret(integration, field.name.to_string(), value.get_untracked())
+                    .await;
+            let message = match result {
+                Ok(()) => format!("Saved {}", field.label),
+                Err(e) => format!("{} error: {e}", field.label),
+            };
+            toast.set(Some(Toast(message)));
+            value.set(String::new());
+            names.refetch();
+        });
+    };
+
+    let fields = secret_fields(&integration)
+        .iter()
+        .map(|&field| {
+            let value = RwSignal::new(String::new());
+            let integration = integration.clone();
+            let is_set = move || {
+                names
+                    .get()
+                    .and_then(Result::ok)
+                    .is_some_and(|names| names.iter().any(|name| name == field.name))
+            };
+            view! {
+                <label class="flex justify-between items-center gap-x-4 py-1">
+                    <span class="text-gray-500">{field.label}</span>
+                    <input
+                        type=if field.sensitive { "password" } else { "text" }
+                        autocomplete="off"
+                        placeholder=move || if is_set() { "Saved" } else { "Not set" }
+                        class="w-40 rounded-md border-0 py-1 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 sm:text-sm"
+                        prop:value=value
+                        on:input=move |ev| value.set(event_target_value(&ev))
+                    />
+                </label>
+                <div class="flex justify-end gap-2">
+                    <button
+                        class="text-indigo-600"
+                        on:click={
+                            let integration = integration.clone();
+                            move |_| {
+                                // Clearing is explicit, an empty input doesn't
+                                // remove what's stored
+                                if !value.get_untracked().is_empty() {
+                                    save(integration.clone(), field, value);
+                                }
+                            }
+                        }
+                    >
+                        "Save"
+                    </button>
+                    <button
+                        class="text-red-600"
+                        on:click={
+                            let integration = integration.clone();
+                            move |_| {
+                                value.set(String::new());
+                                save(integration.clone(), field, value);
+                            }
+                        }
+                    >
+                        "Clear"
+                    </button>
+                </div>
+            }
+        })
+        .collect::<Vec<_>>();
+
+    view! {
+        <div class="px-6 pb-4 text-sm leading-6">
+            <h3 class="font-medium text-gray-900">"Credentials"</h3>
+            <Suspense fallback=|| ()>{fields}</Suspense>
+        </div>
+    }
+}
 
+#[component]
+pub fn IntegrationsPage() -> impl IntoView {
     let toggle_action = Action::new(|(id, enabled, name): &(i64, bool, String)| {
         let id = *id;
         let enabled = *enabled;
@@ -17,6 +243,32 @@ pub fn IntegrationsPage() -> impl IntoView {
         async move { toggle_integration(id, enabled, name).await }
     });
 
+    let integrations = Resource::new(
+        move || toggle_action.version().get(),
+        |_| get_integrations(),
+    );
+    let statuses = Resource::new(
+        move || toggle_action.version().get(),
+        |_| get_integration_statuses(),
+    );
+
+    let toast = use_context::<ToastContext>().unwrap();
+    let poll_now = move |name: String, restart: bool| {
+        spawn_local(async move {
+            let result = if restart {
+                restart_integration(name.clone()).await
+            } else {
+                refresh_integration(name.clone()).await
+            };
+            let message = match result {
+                Ok(count) => format!("{name} found or updated {count} devices"),
+                Err(e) => format!("{name} error: {e}"),
+            };
+            toast.set(Some(Toast(message)));
+            statuses.refetch();
+        });
+    };
+
     view! {
         <Suspense fallback=|| {
             view! { <p>Loading</p> }
@@ -30,6 +282,13 @@ pub fn IntegrationsPage() -> impl IntoView {
                                 let integration_views: Vec<_> = integrations
                                     .into_iter()
                                     .map(|data| {
+                                        let status = statuses
+                                            .get()
+                                            .and_then(Result::ok)
+                                            .and_then(|statuses| statuses.get(&data.name).cloned());
+                                        let (badge_class, badge_text) = health_badge(status.as_ref());
+                                        let has_job = status.is_some();
+                                        let status = status.unwrap_or_default();
                                         view! {
                                             <li class="bg-gray-100 overflow-hidden rounded-xl border border-gray-200">
                                                 <a href=format!("/integrations/{}", data.name.clone())>
@@ -42,7 +301,10 @@ pub fn IntegrationsPage() -> impl IntoView {
                                                         <div class="text-sm font-medium leading-6 text-gray-900">
                                                             {data.name.clone()}
                                                         </div>
-                                                        <div class="relative ml-auto">
+                                                        <span class=format!(
+                                                            "ml-auto rounded-md px-2 py-1 text-xs font-medium {badge_class}",
+                                                        )>{badge_text}</span>
+                                                        <div class="relative">
                                                             <Checkbox
                                                                 value=data.enabled
                                                                 on_click=None
@@ -61,13 +323,88 @@ pub fn IntegrationsPage() -> impl IntoView {
                                                     </div>
                                                     <dl class="-my-3 divide-y divide-gray-100 px-6 py-4 text-sm leading-6">
                                                         <div class="flex justify-between gap-x-4 py-3">
-                                                            <dt class="text-gray-500">"Last authenticated"</dt>
+                                                            <dt class="text-gray-500">"Last run"</dt>
                                                             <dd class="text-gray-700">
-                                                                <time datetime="2024-1-2">"January 2, 2024"</time>
+                                                                {format_time(status.last_success)}
+                                                            </dd>
+                                                        </div>
+                                                        <div class="flex justify-between gap-x-4 py-3">
+                                                            <dt class="text-gray-500">"Next run"</dt>
+                                                            <dd class="text-gray-700">
+                                                                {if status.running {
+                                                                    format_time(status.next_run)
+                                                                } else {
+                                                                    "Not scheduled".to_string()
+                                                                }}
+                                                            </dd>
+                                                        </div>
+                                                        <div class="flex justify-between gap-x-4 py-3">
+                                                            <dt class="text-gray-500">"Last error"</dt>
+                                                            <dd class="text-gray-700 text-right">
+                                                                {match status.last_error {
+                                                                    Some(error) => {
+                                                                        format!("{} {error}", format_time(status.last_error_at))
+                                                                    }
+                                                                    None => "None".to_string(),
+                                                                }}
                                                             </dd>
                                                         </div>
                                                     </dl>
+                                                    {has_job
+                                                        .then(|| {
+                                                            // The loop refuses both while the integration is disabled
+                                                            let can_poll = data.enabled;
+                                                            let title = (!can_poll)
+                                                                .then_some("Enable the integration first");
+                                                            view! {
+                                                                <div class="flex gap-2 px-6 pb-4 text-sm">
+                                                                    <button
+                                                                        class="bg-indigo-600 text-white px-3 py-1 rounded disabled:opacity-50"
+                                                                        disabled=!can_poll
+                                                                        title=title
+                                                                        on:click={
+                                                                            let name = data.name.clone();
+                                                                            move |ev| {
+                                                                                ev.prevent_default();
+                                                                                poll_now(name.clone(), false);
+                                                                            }
+                                                                        }
+                                                                    >
+                                                                        "Refresh now"
+                                                                    </button>
+                                                                    <button
+                                                                        class="bg-white text-gray-900 px-3 py-1 rounded ring-1 ring-gray-300 disabled:opacity-50"
+                                                                        disabled=!can_poll
+                                                                        title=title
+                                                                        on:click={
+                                                                            let name = data.name.clone();
+                                                                            move |ev| {
+                                                                                ev.prevent_default();
+                                                                                poll_now(name.clone(), true);
+                                                                            }
+                                                                        }
+                                                                    >
+                                                                        "Restart"
+                                                                    </button>
+                                                                </div>
+                                                            }
+                                                        })}
                                                 </a>
+                                                {(!secret_fields(&data.name).is_empty())
+                                                    .then(|| {
+                                                        view! { <IntegrationSecretsForm integration=data.name.clone() /> }
+                                                    })}
+                                                {has_job
+                                                    .then(|| {
+                                                        view! {
+                                                            <IntegrationSettingsForm
+                                                                id=data.id
+                                                                name=data.name.clone()
+                                                                settings=data.settings.clone()
+                                                                on_saved=Callback::new(move |_| statuses.refetch())
+                                                            />
+                                                        }
+                                                    })}
                                             </li>
                                         }
                                     })
@@ -1,72 +1,152 @@
 use {
-    super::pages::dashboard_page::DashboardValues,
+    crate::{
+        components::layout::{ToastContext, toast_on_error},
+        integrations::iron_nest::types::{Device, DeviceCommand, DeviceType},
+        server::{devices::handle_device_command, roku::get_roku_apps},
+    },
     leptos::{prelude::*, task::spawn_local},
 };
 
-#[server(LaunchRokuApp)]
-pub async fn launch_roku_app(app_id: String) -> Result<(), ServerFnError> {
-    use crate::integrations::roku::roku_launch_app;
-    roku_launch_app("10.0.0.217", &app_id).await;
-    Ok(())
-}
-
+/// Launchable channels of the selected Roku, from the catalog the Roku job
+/// keeps.
 #[component]
-pub fn RokuTvRemote(
-    dashboard_values: Resource<Result<DashboardValues, ServerFnError>>,
-) -> impl IntoView {
+pub fn RokuTvRemote(devices: Resource<Result<Vec<Device>, ServerFnError>>) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
+    let selected = RwSignal::new(None::<i64>);
+    let rokus = move || {
+        devices
+            .get()
+            .and_then(Result::ok)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|device| device.device_type == DeviceType::RokuTv)
+            .collect::<Vec<_>>()
+    };
+    let roku_id = Memo::new(move |_| {
+        selected
+            .get()
+            .or_else(|| rokus().first().map(|device| device.id))
+    });
+    let apps = Resource::new(
+        move || roku_id.get(),
+        |roku_id| async move {
+            match roku_id {
+                Some(roku_id) => get_roku_apps(roku_id).await,
+                None => Ok(Vec::new()),
+            }
+        },
+    );
+
     view! {
         <div class="col-span-3 h-[264px] rounded-lg shadow-lg">
-            <Suspense fallback=|| {
-                view! { <div>"Loading"</div> }
-            }>
-                {move || {
-                    dashboard_values
-                        .get()
-                        .map(|data| {
-                            match data {
-                                Ok(data) => {
-                                    view! {
-                                        <div class="col-span-4 h-[264px] flex flex-col">
-                                            <div class="bg-white rounded-lg transition-all duration-500 dark:border-slate-500 p-2 xl:p-6 flex flex-col h-full overflow-hidden">
-                                                <div class="grid grid-cols-5 gap-1 rounded-lg h-full content-center">
-                                                    {data
-                                                        .roku_apps
+            <div class="col-span-4 h-[264px] flex flex-col">
+                <div class="bg-white rounded-lg transition-all duration-500 dark:border-slate-500 p-2 xl:p-6 flex flex-col h-full overflow-hidden">
+                    {move || {
+                        let rokus = rokus();
+                        (rokus.len() > 1)
+                            .then(|| {
+                                view! {
+                                    <select
+                                        class="mb-2 rounded-md border-0 py-1 text-sm text-gray-900 ring-1 ring-inset ring-gray-300"
+                                        on:change=move |ev| selected.set(event_target_value(&ev).parse().ok())
+                                    >
+                                        {rokus
+                                            .into_iter()
+                                            .map(|roku| {
+                                                let id = roku.id;
+                                                view! {
+                                                    <option
+                                                        value=id.to_string()
+                                                        selected=move || roku_id.get() == Some(id)
+                                                    >
+                                                        {roku.label().to_string()}
+                                                    </option>
+                                                }
+                                            })
+                                            .collect::<Vec<_>>()}
+                                    </select>
+                                }
+                            })
+                    }}
+                    <Suspense fallback=|| {
+                        view! { <div>"Loading"</div> }
+                    }>
+                        {move || {
+                            apps.get()
+                                .map(|data| {
+                                    match data {
+                                        Ok(apps) if apps.is_empty() => {
+                                            view! {
+                                                <p class="text-sm text-gray-500">"No Roku channels found yet"</p>
+                                            }
+                                                .into_any()
+                                        }
+                                        Ok(apps) => {
+                                            view! {
+                                                <div class="grid grid-cols-5 gap-1 rounded-lg h-full content-center overflow-y-auto">
+                                                    {apps
                                                         .into_iter()
                                                         .map(|app| {
+                                                            let device_id = app.device_id;
+                                                            let app_id = app.app_id.clone();
+                                                            let icon_url = app.icon_url();
+                                                            let title = app.name.clone();
                                                             view! {
                                                                 <div
                                                                     class="bg-white border-slate-500 border rounded-lg flex items-center justify-center cursor-pointer shadow-sm w-full h-full"
+                                                                    title=title
                                                                     on:click=move |_| {
-                                                                        let id = app.id.to_string();
+                                                                        let app_id = app_id.clone();
                                                                         spawn_local(async move {
-                                                                            launch_roku_app(id).await.unwrap();
+                                                                            toast_on_error(
+                                                                                toast,
+                                                                                handle_device_command(
+                                                                                        device_id,
+                                                                                        DeviceCommand::LaunchApp {
+                                                                                            app_id,
+                                                                                        },
+                                                                                    )
+                                                                                    .await,
+                                                                            );
                                                                         });
                                                                     }
                                                                 >
-
-                                                                    <img
-                                                                        class="rounded-lg max-h-full max-w-full object-contain"
-                                                                        src=format!("data:image/png;base64, {}", app.icon)
-                                                                    />
+                                                                    {match icon_url {
+                                                                        Some(icon_url) => {
+                                                                            view! {
+                                                                                <img
+                                                                                    class="rounded-lg max-h-full max-w-full object-contain"
+                                                                                    src=icon_url
+                                                                                    alt=app.name
+                                                                                />
+                                                                            }
+                                                                                .into_any()
+                                                                        }
+                                                                        None => {
+                                                                            view! {
+                                                                                <span class="text-xs text-center">{app.name}</span>
+                                                                            }
+                                                                                .into_any()
+                                                                        }
+                                                                    }}
                                                                 </div>
                                                             }
                                                         })
                                                         .collect::<Vec<_>>()}
                                                 </div>
-                                            </div>
-                                        </div>
+                                            }
+                                                .into_any()
+                                        }
+                                        Err(e) => {
+                                            view! { <div>{format!("RokuTvRemote error: {e}")}</div> }
+                                                .into_any()
+                                        }
                                     }
-                                        .into_any()
-                                }
-                                Err(e) => {
-                                    view! { <div>{format!("RokuTvRemote error: {e}")}</div> }
-                                        .into_any()
-                                }
-                            }
-                        })
-                }}
-
-            </Suspense>
+                                })
+                        }}
+                    </Suspense>
+                </div>
+            </div>
         </div>
     }
 }
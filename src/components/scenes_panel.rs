@@ -0,0 +1,152 @@
+use {
+    crate::{
+        components::layout::{Toast, ToastContext},
+        integrations::iron_nest::types::Device,
+        server::scenes::{activate_scene, delete_scene, get_scenes, save_scene},
+    },
+    leptos::{prelude::*, task::spawn_local},
+    std::collections::HashSet,
+};
+
+/// Lists saved scenes with a button to run each one, and captures a new scene
+/// from the current state of the checked devices.
+#[component]
+pub fn ScenesPanel(devices: Resource<Result<Vec<Device>, ServerFnError>>) -> impl IntoView {
+    let toast = use_context::<ToastContext>().unwrap();
+    let scenes = Resource::new(|| (), |_| get_scenes());
+    let (name, set_name) = signal(String::new());
+    let selected = RwSignal::new(HashSet::<i64>::new());
+
+    let run_scene = move |id: i64, scene_name: String| {
+        spawn_local(async move {
+            let message = match activate_scene(id).await {
+                Ok(result) if result.failed.is_empty() => format!("Activated {scene_name}"),
+                Ok(result) => format!("{} devices in {scene_name} failed", result.failed.len()),
+                Err(e) => format!("Scene error: {e}"),
+            };
+            toast.set(Some(Toast(message)));
+        });
+    };
+
+    let remove_scene = move |id: i64| {
+        spawn_local(async move {
+            if let Err(e) = delete_scene(id).await {
+                toast.set(Some(Toast(format!("Delete scene error: {e}"))));
+            }
+            scenes.refetch();
+        });
+    };
+
+    let capture_scene = move |_| {
+        let scene_name = name.get_untracked();
+        let device_ids = selected.get_untracked().into_iter().collect::<Vec<_>>();
+        spawn_local(async move {
+            match save_scene(scene_name, device_ids).await {
+                Ok(scene) => {
+                    toast.set(Some(Toast(format!("Saved {}", scene.name))));
+                    set_name.set(String::new());
+                    selected.set(HashSet::new());
+                    scenes.refetch();
+                }
+                Err(e) => toast.set(Some(Toast(format!("Save scene error: {e}")))),
+            }
+        });
+    };
+
+    view! {
+        <div class="col-span-3 h-[264px] panel bg-white p-2 rounded-md shadow-lg overflow-y-auto">
+            <h2 class="text-lg text-black">"Scenes"</h2>
+            <Suspense fallback=|| {
+                view! { <p>"Loading scenes..."</p> }
+            }>
+                {move || {
+                    scenes
+                        .get()
+                        .map(|data| match data {
+                            Ok(scenes) => {
+                                view! {
+                                    <ul class="space-y-1">
+                                        {scenes
+                                            .into_iter()
+                                            .map(|scene| {
+                                                let scene_name = scene.name.clone();
+                                                view! {
+                                                    <li class="flex items-center gap-2 text-sm">
+                                                        <button
+                                                            class="bg-indigo-600 text-white px-2 py-1 rounded"
+                                                            on:click=move |_| run_scene(scene.id, scene_name.clone())
+                                                        >
+                                                            {scene.name}
+                                                        </button>
+                                                        <span class="text-gray-500">
+                                                            {format!("{} devices", scene.devices.len())}
+                                                        </span>
+                                                        <button
+                                                            class="text-red-600"
+                                                            on:click=move |_| remove_scene(scene.id)
+                                                        >
+                                                            "Delete"
+                                                        </button>
+                                                    </li>
+                                                }
+                                            })
+                                            .collect::<Vec<_>>()}
+                                    </ul>
+                                }
+                                    .into_any()
+                            }
+                            Err(e) => view! { <p>{format!("Scenes error: {e}")}</p> }.into_any(),
+                        })
+                }}
+            </Suspense>
+            <hr class="my-2" />
+            <div class="flex items-end gap-2">
+                <input
+                    type="text"
+                    placeholder="Scene name"
+                    class="rounded-md border-0 py-1.5 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 sm:text-sm"
+                    prop:value=name
+                    on:input=move |ev| set_name.set(event_target_value(&ev))
+                />
+                <button class="bg-indigo-600 text-white px-4 py-2 rounded" on:click=capture_scene>
+                    "Save current state"
+                </button>
+            </div>
+            <Suspense fallback=|| ()>
+                {move || {
+                    devices
+                        .get()
+                        .and_then(Result::ok)
+                        .map(|devices| {
+                            devices
+                                .into_iter()
+                                .map(|device| {
+                                    let device_id = device.id;
+                                    view! {
+                                        <label class="flex items-center gap-2 text-sm">
+                                            <input
+                                                type="checkbox"
+                                                prop:checked=move || selected.with(|ids| ids.contains(&device_id))
+                                                on:change=move |ev| {
+                                                    let checked = event_target_checked(&ev);
+                                                    selected
+                                                        .update(|ids| {
+                                                            if checked {
+                                                                ids.insert(device_id);
+                                                            } else {
+                                                                ids.remove(&device_id);
+                                                            }
+                                                        });
+                                                }
+                                            />
+                                            {device.label().to_string()}
+                                        </label>
+                                    }
+                                })
+                                .collect::<Vec<_>>()
+                        })
+                }}
+            </Suspense>
+        </div>
+    }
+}
@@ -1,4 +1,17 @@
-use axum::{extract::Path, http::StatusCode};
+use {
+    crate::{
+        components::mish::ipld_blob_page::get_ipld_blob_query, integrations::iron_nest::AppState,
+    },
+    axum::{
+        extract::{Path, State},
+        http::{
+            HeaderMap, StatusCode,
+            header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH},
+        },
+        response::{IntoResponse, Response},
+    },
+    cid::Cid,
+};
 
 pub async fn roku_keypress_handler(
     Path((device_id, key)): Path<(i64, String)>,
@@ -17,3 +30,77 @@ pub async fn roku_keypress_handler(
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
     }
 }
+
+/// Icons are looked up by the CID of their content, so a response never goes
+/// stale.
+const ICON_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+/// Serves a Roku channel icon stored in `ipld_blobs` by the Roku job.
+pub async fn roku_icon_handler(
+    State(state): State<AppState>,
+    Path(cid): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let cid = cid.parse::<Cid>().map_err(|_| StatusCode::BAD_REQUEST)?;
+    let etag = format!("\"{cid}\"");
+    if headers
+        .get(IF_NONE_MATCH)
+        .is_some_and(|if_none_match| if_none_match == etag.as_str())
+    {
+        return Ok(StatusCode::NOT_MODIFIED.into_response());
+    }
+
+    let icon = get_ipld_blob_query(&state.pool, &cid)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok((
+        [
+            (CONTENT_TYPE, image_content_type(&icon).to_string()),
+            (CACHE_CONTROL, ICON_CACHE_CONTROL.to_string()),
+            (ETAG, etag),
+        ],
+        icon,
+    )
+        .into_response())
+}
+
+/// Sniffs the image format, since Rokus serve icons as PNG, JPEG or GIF
+/// depending on the channel.
+fn image_content_type(image: &[u8]) -> &'static str {
+    match image {
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [0xFF, 0xD8, 0xFF, ..] => "image/jpeg",
+        [b'G', b'I', b'F', b'8', ..] => "image/gif",
+        [
+            b'R',
+            b'I',
+            b'F',
+            b'F',
+            _,
+            _,
+            _,
+            _,
+            b'W',
+            b'E',
+            b'B',
+            b'P',
+            ..,
+        ] => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_content_type() {
+        assert_eq!(image_content_type(b"\x89PNG\r\n\x1a\n"), "image/png");
+        assert_eq!(image_content_type(b"\xFF\xD8\xFF\xE0"), "image/jpeg");
+        assert_eq!(image_content_type(b"GIF89a"), "image/gif");
+        assert_eq!(image_content_type(b"RIFF\0\0\0\0WEBPVP8 "), "image/webp");
+        assert_eq!(image_content_type(b""), "application/octet-stream");
+    }
+}
@@ -1,5 +1,5 @@
 use {
-    crate::integrations::efuy::types::{ApiResponse, CountryDomainResponse},
+    crate::integrations::efuy::types::{ApiResponse, ApiStatus, CountryDomainResponse},
     aes::{
         Aes256,
         cipher::{BlockEncrypt, KeyInit, generic_array::typenum::U16},
@@ -8,12 +8,12 @@ use {
     chrono::Utc,
     elliptic_curve::{generic_array::GenericArray, sec1::ToEncodedPoint},
     hex::decode,
-    http::{HeaderMap, HeaderValue},
+    http::{HeaderMap, HeaderValue, header::InvalidHeaderValue},
     p256::{PublicKey, ecdh::EphemeralSecret},
     rand_core::OsRng,
     reqwest::Client,
     serde_json::json,
-    std::{env, iter},
+    std::iter,
 };
 
 static API_URL: &str = "https://extend.eufylife.com";
@@ -22,21 +22,36 @@ static SERVER_PUBLIC_KEY: &str = "04c5c00c4f8d1197cc7c3167c52bf7acb054d722f0ef08
 static SN: &str = "75814221ee75";
 static OS_TYPE: &str = "android";
 
-pub async fn get_country_url(client: &Client) -> String {
+#[derive(Debug, thiserror::Error)]
+pub enum EufyError {
+    #[error("Request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Invalid response: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Invalid auth token: {0}")]
+    InvalidToken(#[from] InvalidHeaderValue),
+
+    #[error("Login failed with code {code}: {msg}")]
+    Login { code: i32, msg: String },
+
+    #[error("Encryption failed: {0}")]
+    Encryption(String),
+}
+
+pub async fn get_country_url(client: &Client) -> Result<String, EufyError> {
     let country_domain_str = client
         .get(format!("{API_URL}/domain/US"))
         .send()
-        .await
-        .unwrap()
+        .await?
         .text()
-        .await
-        .unwrap();
+        .await?;
 
-    let country_domain_str: CountryDomainResponse =
-        serde_json::from_str(&country_domain_str).unwrap();
+    let country_domain_str: CountryDomainResponse = serde_json::from_str(&country_domain_str)?;
     let country_url = format!("https://{}", country_domain_str.data.domain);
     println!("Country URL: {country_url}");
-    country_url
+    Ok(country_url)
 }
 
 pub fn get_headers() -> HeaderMap {
@@ -57,25 +72,9 @@ pub fn get_headers() -> HeaderMap {
     headers
 }
 
-pub async fn eufy_login() -> ApiResponse {
-    let username = env::var("EUFY_USERNAME").expect("EUFY_USERNAME not found in environment");
-    let password = env::var("EUFY_PASSWORD").expect("EUFY_PASSWORD not found in environment");
-
+pub async fn eufy_login(username: &str, password: &str) -> Result<ApiResponse, EufyError> {
     let client = reqwest::Client::new();
-
-    let country_domain_str = client
-        .get(format!("{API_URL}/domain/US"))
-        .send()
-        .await
-        .unwrap()
-        .text()
-        .await
-        .unwrap();
-
-    let country_domain_str: CountryDomainResponse =
-        serde_json::from_str(&country_domain_str).unwrap();
-    let country_url = format!("https://{}", country_domain_str.data.domain);
-    println!("Country URL: {country_url}");
+    let country_url = get_country_url(&client).await?;
 
     let secret = EphemeralSecret::random(&mut OsRng);
     let public_key = PublicKey::from(&secret);
@@ -85,10 +84,10 @@ pub async fn eufy_login() -> ApiResponse {
     let public_key_hex = hex::encode(public_key_bytes);
 
     // Decode the server's public key from hex
-    let server_public_key_bytes =
-        decode(SERVER_PUBLIC_KEY).expect("Invalid hex in server public key");
-    let server_public_key =
-        PublicKey::from_sec1_bytes(&server_public_key_bytes).expect("Invalid server public key");
+    let server_public_key_bytes = decode(SERVER_PUBLIC_KEY)
+        .map_err(|e| EufyError::Encryption(format!("Invalid server public key: {e}")))?;
+    let server_public_key = PublicKey::from_sec1_bytes(&server_public_key_bytes)
+        .map_err(|e| EufyError::Encryption(format!("Invalid server public key: {e}")))?;
 
     // Compute the shared secret
     let shared_secret = secret.diffie_hellman(&server_public_key);
@@ -99,7 +98,8 @@ pub async fn eufy_login() -> ApiResponse {
     // Convert GenericArray reference to a slice
     let key_slice: &[u8] = shared_secret_bytes.as_slice();
 
-    let encrypted_password = encrypt_api_data(&password, key_slice).expect("Encryption failed");
+    let encrypted_password =
+        encrypt_api_data(password, key_slice).map_err(|e| EufyError::Encryption(e.to_string()))?;
     let request_body = &json!({
         "ab": "US",
         "client_secret_info": {
@@ -119,25 +119,27 @@ pub async fn eufy_login() -> ApiResponse {
         .json(&request_body)
         .headers(headers)
         .send()
-        .await
-        .unwrap()
+        .await?
         .text()
-        .await
-        .unwrap();
-    println!("Auth res: {auth_res}");
-
-    let auth = serde_json::from_str::<ApiResponse>(&auth_res).unwrap();
-    println!("Got Eufy auth_token: {:?}", auth.data.auth_token);
-    auth
+        .await?;
+
+    // A wrong password comes back as a non-zero code without any data
+    let status = serde_json::from_str::<ApiStatus>(&auth_res)?;
+    if status.code != 0 {
+        return Err(EufyError::Login {
+            code: status.code,
+            msg: status.msg,
+        });
+    }
+    Ok(serde_json::from_str::<ApiResponse>(&auth_res)?)
 }
 
-pub async fn get_devices(auth_token: String) {
+pub async fn get_devices(auth_token: String) -> Result<(), EufyError> {
     let client = reqwest::Client::new();
-    let country_url = get_country_url(&client).await;
+    let country_url = get_country_url(&client).await?;
 
     let mut headers = get_headers();
-    println!("auth_token: {auth_token}");
-    headers.insert("X-Auth-Token", HeaderValue::from_str(&auth_token).unwrap());
+    headers.insert("X-Auth-Token", HeaderValue::from_str(&auth_token)?);
 
     let res = client
         .post(format!("{country_url}/v2/house/device_list"))
@@ -152,11 +154,11 @@ pub async fn get_devices(auth_token: String) {
         }))
         .headers(headers)
         .send()
-        .await
-        .unwrap();
+        .await?;
     println!("res: {res:?}");
-    let device_res = res.text().await.unwrap();
+    let device_res = res.text().await?;
     println!("device_res: {:?}", device_res);
+    Ok(())
 }
 
 fn encrypt_api_data(data: &str, key: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
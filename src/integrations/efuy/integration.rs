@@ -0,0 +1,81 @@
+use {
+    super::{eufy_login, get_devices},
+    crate::integrations::iron_nest::{
+        secrets::SecretStore,
+        supervisor::{Every, HealthFuture, Integration, IntegrationFuture, PollFuture},
+        types::{AuthState, IntegrationSettings},
+    },
+    futures::FutureExt,
+    std::time::Duration,
+};
+
+/// Logs in when there's no refresh token and every five hours by default, and
+/// fetches the devices every poll.
+pub struct EufyIntegration {
+    secrets: SecretStore,
+    auth: Every,
+}
+
+impl EufyIntegration {
+    pub fn new(secrets: SecretStore) -> Self {
+        Self {
+            secrets,
+            auth: Every::new(Duration::from_secs(5 * 60 * 60)),
+        }
+    }
+}
+
+impl Integration for EufyIntegration {
+    fn name(&self) -> &'static str {
+        "eufy"
+    }
+
+    fn configure(&self, settings: &IntegrationSettings) {
+        self.auth
+            .set_period(settings.auth_interval().unwrap_or(settings.poll_interval()));
+    }
+
+    fn start(&self) -> IntegrationFuture<'_> {
+        async move {
+            self.auth.reset();
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn poll(&self) -> PollFuture<'_> {
+        async move {
+            // Checked first so the period restarts even when there's no token
+            if self.auth.due()
+                || self
+                    .secrets
+                    .get_auth(self.name())
+                    .await?
+                    .refresh_token
+                    .is_empty()
+            {
+                let username = self.secrets.require(self.name(), "username").await?;
+                let password = self.secrets.require(self.name(), "password").await?;
+                let res = eufy_login(&username, &password).await?;
+                let state = AuthState {
+                    refresh_token: res.data.auth_token.to_owned(),
+                    hardware_id: res.data.user_id,
+                    auth_token: res.data.auth_token,
+                };
+                self.secrets.set_auth(self.name(), &state).await?;
+            }
+
+            let eufy_auth = self.secrets.get_auth(self.name()).await?;
+            if !eufy_auth.auth_token.is_empty() {
+                get_devices(eufy_auth.auth_token).await?;
+            }
+            // Eufy devices aren't stored yet
+            Ok(0)
+        }
+        .boxed()
+    }
+
+    fn health(&self) -> HealthFuture<'_> {
+        async move { self.secrets.auth_health(self.name()).await }.boxed()
+    }
+}
@@ -3,4 +3,6 @@ pub mod types;
 cfg_if::cfg_if! { if #[cfg(feature = "ssr")] {
   mod client;
   pub use client::*;
+  mod integration;
+  pub use integration::*;
 }}
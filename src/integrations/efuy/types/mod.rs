@@ -12,6 +12,13 @@ pub struct Data {
     pub domain: String,
 }
 
+/// The `code` and `msg` every response has, whether or not it has `data`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiStatus {
+    pub code: i32,
+    pub msg: String,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ApiResponse {
     pub code: i32,
@@ -0,0 +1,49 @@
+use {
+    super::{events::Event, shutdown::Shutdown},
+    log::{error, warn},
+    sqlx::{PgPool, types::Json},
+    tokio::sync::broadcast::{Receiver, error::RecvError},
+};
+
+/// Writes every event received on `events`, a subscription to the event bus,
+/// to `event_log` until shutdown. Mish state changes are left out, scripts
+/// publish those far more often than anything else and `mish_states` already
+/// holds the result.
+pub async fn run_audit_log(pool: PgPool, mut events: Receiver<Event>, shutdown: Shutdown) {
+    let shutdown_requested = shutdown.requested();
+    tokio::pin!(shutdown_requested);
+
+    loop {
+        let event = tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => event,
+                Err(RecvError::Lagged(missed)) => {
+                    warn!("Audit log missed {missed} events");
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            },
+            _ = &mut shutdown_requested => break,
+        };
+        if matches!(event, Event::MishStateChanged(_)) {
+            continue;
+        }
+        if let Err(e) = insert_event(&pool, &event).await {
+            error!("Failed to record {}: {e}", event.name());
+        }
+    }
+}
+
+async fn insert_event(pool: &PgPool, event: &Event) -> Result<(), sqlx::Error> {
+    let query = "
+        INSERT INTO event_log (event_type, device_id, payload)
+        VALUES ($1, $2, $3)
+    ";
+    sqlx::query(query)
+        .bind(event.name())
+        .bind(event.device_id())
+        .bind(Json(event))
+        .execute(pool)
+        .await?;
+    Ok(())
+}
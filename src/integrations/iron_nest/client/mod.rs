@@ -1,91 +1,215 @@
 use {
     super::{
         cron::CronClient,
-        mish::MishStateModification,
+        driver::{DriverError, DriverRegistry},
+        events::{Event, EventBus},
+        secrets::SecretStore,
         shared::get_default_integrations,
-        types::{AuthState, ControlMessage, Device, DeviceType, Integration},
+        supervisor::{self, Supervisor},
+        types::{
+            Device, DeviceCommand, DeviceGroup, DeviceState, DeviceStateHistory, DeviceType,
+            EnergyPeriod, EnergyReading, EnergyUsage, GroupCommandResult, Integration, Room, Scene,
+            SceneDevice,
+        },
     },
     crate::{
         integrations::{
-            efuy,
-            ring::{
-                client::RingRestClient,
-                get_ring_camera,
-                types::{DevicesRes, RingCamera},
-            },
+            efuy::EufyIntegration,
+            ring::{RingIntegration, client::RingRestClient, types::RingCamera},
             roku::{
-                roku_discover, roku_get_device_info, roku_launch_app, roku_search,
-                roku_send_keypress,
+                RokuIntegration, roku_get_apps, roku_get_channel_icon, roku_search,
+                types::{NowPlaying, RokuApp, RokuNowPlaying},
             },
-            stoplight::toggle_stoplight,
             tplink::{
-                discover_devices, tplink_set_dimmer_brightness, tplink_set_light_brightness,
-                tplink_turn_light_on_off, tplink_turn_plug_off, tplink_turn_plug_on,
-                types::DeviceData,
-            },
-            tuya::{
-                discover_tuya_devices, get_devices, get_refresh_token, types::TuyaDeviceResResult,
+                TplinkIntegration, discover_devices, tplink_kasa_get_emeter_realtime,
+                types::{DeviceData, TPLinkSmartPowerStripRes},
             },
+            tuya::{TuyaIntegration, types::TuyaDeviceResResult},
         },
-        server::tplink::handle_smart_light_toggle,
+        ipld_codecs,
     },
-    chrono::Utc,
+    chrono::{DateTime, Utc},
+    cid::Cid,
+    futures::future::join_all,
     leptos::prelude::*,
-    log::{error, info},
+    log::{error, info, warn},
+    multihash_codetable::{Code, MultihashDigest},
+    serde::Deserialize,
     serde_json::{Value, json},
-    sqlx::PgPool,
-    std::{collections::HashMap, net::Ipv4Addr, sync::Arc},
-    tokio::sync::{
-        RwLock,
-        mpsc::{self, Receiver, Sender},
+    sqlx::{PgPool, types::Json},
+    std::{
+        collections::{HashMap, HashSet},
+        net::Ipv4Addr,
+        sync::Arc,
+        time::Duration,
     },
-    tokio_cron_scheduler::{Job, JobScheduler},
     url::Url,
 };
 
+/// Normalizes the MAC formats reported by integrations (`50:C7:BF:00:11:22`,
+/// `50c7bf001122`, `50-C7-BF-00-11-22`) to upper case colon separated.
+pub fn normalize_mac(mac: &str) -> Option<String> {
+    let hex = mac
+        .chars()
+        .filter(|c| !matches!(c, ':' | '-' | '.'))
+        .collect::<String>()
+        .to_uppercase();
+    if hex.len() != 12 || !hex.chars().all(|c| c.is_ascii_hexdigit()) || hex == "000000000000" {
+        return None;
+    }
+    Some(
+        hex.as_bytes()
+            .chunks(2)
+            .map(|pair| std::str::from_utf8(pair).unwrap())
+            .collect::<Vec<_>>()
+            .join(":"),
+    )
+}
+
+/// Upserts discovered devices. Devices reporting a MAC address are matched on
+/// `(mac_address, child_id)` so they keep their row, and with it their id, when
+/// DHCP hands them a new IP. A row discovered before MACs were collected is
+/// adopted by its IP the first time the device reports one. Devices without a
+/// MAC fall back to matching on `(ip, child_id)`.
+///
+/// Publishes `DeviceDiscovered` for rows that didn't exist yet,
+/// `DeviceCameOnline` for devices that were offline and `DeviceStateChanged`
+/// for devices whose power state differs from the stored one.
 pub async fn insert_devices_into_db(
     pool: &PgPool,
+    event_bus: &EventBus,
     devices: &Vec<Device>,
 ) -> Result<(), sqlx::Error> {
+    let query = "
+        SELECT id, power_state, online
+        FROM device
+    ";
+    let stored = sqlx::query_as::<_, (i64, i32, bool)>(query)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|(id, power_state, online)| (id, (power_state, online)))
+        .collect::<HashMap<_, _>>();
+
     for device in devices {
-        println!("insert_devices_into_db device {:?}", device);
-        let query = "
-            INSERT INTO device (
-                name,
-                device_type,
-                battery_percentage,
-                ip,
-                power_state,
-                last_seen,
-                child_id
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7)
-            ON CONFLICT ON CONSTRAINT unique_ip_child_id DO UPDATE
-            SET name=$1,
-                device_type=$2,
-                battery_percentage=$3,
-                ip=$4,
-                power_state=$5,
-                last_seen=$6,
-                child_id=$7
-        ";
-        sqlx::query(query)
-            .bind(&device.name)
-            .bind(&device.device_type)
-            .bind(device.battery_percentage)
-            .bind(&device.ip)
-            .bind(device.power_state)
-            .bind(device.last_seen)
-            .bind(&device.child_id)
-            .execute(pool)
-            .await?;
+        let id: i64 = if let Some(mac_address) = &device.mac_address {
+            let adopt_query = "
+                UPDATE device
+                SET mac_address = $1
+                WHERE mac_address IS NULL
+                    AND ip = $2
+                    AND coalesced_child_id = COALESCE($3, '')
+                    AND NOT EXISTS (
+                        SELECT 1 FROM device
+                        WHERE mac_address = $1 AND coalesced_child_id = COALESCE($3, '')
+                    )
+            ";
+            sqlx::query(adopt_query)
+                .bind(mac_address)
+                .bind(&device.ip)
+                .bind(&device.child_id)
+                .execute(pool)
+                .await?;
+
+            let query = "
+                INSERT INTO device (
+                    name,
+                    device_type,
+                    battery_percentage,
+                    ip,
+                    power_state,
+                    last_seen,
+                    child_id,
+                    mac_address
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                ON CONFLICT ON CONSTRAINT unique_mac_child_id DO UPDATE
+                SET name=$1,
+                    device_type=$2,
+                    battery_percentage=$3,
+                    ip=$4,
+                    power_state=$5,
+                    last_seen=$6,
+                    online=TRUE
+                RETURNING id
+            ";
+            sqlx::query_scalar(query)
+                .bind(&device.name)
+                .bind(device.device_type)
+                .bind(device.battery_percentage)
+                .bind(&device.ip)
+                .bind(device.power_state)
+                .bind(device.last_seen)
+                .bind(&device.child_id)
+                .bind(mac_address)
+                .fetch_one(pool)
+                .await?
+        } else {
+            let query = "
+                INSERT INTO device (
+                    name,
+                    device_type,
+                    battery_percentage,
+                    ip,
+                    power_state,
+                    last_seen,
+                    child_id
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT (ip, coalesced_child_id) WHERE mac_address IS NULL DO UPDATE
+                SET name=$1,
+                    device_type=$2,
+                    battery_percentage=$3,
+                    ip=$4,
+                    power_state=$5,
+                    last_seen=$6,
+                    child_id=$7,
+                    online=TRUE
+                RETURNING id
+            ";
+            sqlx::query_scalar(query)
+                .bind(&device.name)
+                .bind(device.device_type)
+                .bind(device.battery_percentage)
+                .bind(&device.ip)
+                .bind(device.power_state)
+                .bind(device.last_seen)
+                .bind(&device.child_id)
+                .fetch_one(pool)
+                .await?
+        };
+
+        let Some(&(power_state, online)) = stored.get(&id) else {
+            event_bus.publish(Event::DeviceDiscovered {
+                device: Device {
+                    id,
+                    ..device.clone()
+                },
+            });
+            continue;
+        };
+        if !online {
+            event_bus.publish(Event::DeviceCameOnline { device_id: id });
+        }
+        if power_state != device.power_state {
+            event_bus.publish(Event::DeviceStateChanged {
+                device_id: id,
+                state: DeviceState {
+                    on: device.power_state != 0,
+                    ..Default::default()
+                },
+            });
+        }
     }
 
     Ok(())
 }
 
-pub async fn insert_initial_devices_into_db(pool: &PgPool) -> Result<(), sqlx::Error> {
+pub async fn insert_initial_devices_into_db(
+    pool: &PgPool,
+    event_bus: &EventBus,
+) -> Result<(), sqlx::Error> {
     insert_devices_into_db(
         pool,
+        event_bus,
         &vec![Device {
             name: "Living Room Stoplight".to_owned(),
             device_type: DeviceType::Stoplight,
@@ -96,6 +220,11 @@ pub async fn insert_initial_devices_into_db(pool: &PgPool) -> Result<(), sqlx::E
             last_seen: Utc::now(),
             mac_address: None,
             child_id: None,
+            room_id: None,
+            tags: Vec::new(),
+            display_name: None,
+            icon: None,
+            online: true,
         }],
     )
     .await
@@ -110,14 +239,18 @@ pub async fn insert_integrations_into_db(pool: &PgPool) -> Result<(), sqlx::Erro
             INSERT INTO integration (
                 name,
                 enabled,
-                image
-            ) VALUES ($1, $2, $3)
+                image,
+                offline_after_secs,
+                settings
+            ) VALUES ($1, $2, $3, $4, $5)
             ON CONFLICT (name) DO NOTHING;
         ";
         sqlx::query(query)
             .bind(&integration.name)
             .bind(integration.enabled)
             .bind(integration.image)
+            .bind(integration.offline_after_secs)
+            .bind(Json(integration.settings))
             .execute(pool)
             .await?;
     }
@@ -133,115 +266,941 @@ pub fn extract_ip(url_str: &str) -> Result<String, url::ParseError> {
     }
 }
 
-pub async fn schedule_task(
-    function_name: Arc<String>,
-    function_args: Arc<Value>,
-    schedule: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let sched = JobScheduler::new().await?;
-    sched
-        .add(
-            Job::new_async(schedule, move |_uuid, mut _l| {
-                let function_name_clone = function_name.clone();
-                let function_args_clone = function_args.clone();
-
-                Box::pin(async move {
-                    let fn_name = (*function_name_clone).clone();
-                    let fn_args = (*function_args_clone).clone();
-
-                    println!("Calling {}", fn_name);
-                    execute_function(fn_name, fn_args).await;
-                })
-            })
-            .unwrap(),
-        )
+pub async fn get_device_by_id(pool: &PgPool, id: i64) -> Result<Device, DriverError> {
+    let query = "
+        SELECT id, name, device_type, ip, power_state, battery_percentage, last_seen, mac_address, child_id, room_id, tags, display_name, icon, online
+        FROM device
+        WHERE id = $1
+    ";
+    sqlx::query_as::<_, Device>(query)
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| DriverError::DeviceNotFound(format!("id {id}")))
+}
+
+pub async fn get_device_by_ip(
+    pool: &PgPool,
+    ip: &str,
+    child_id: Option<&str>,
+) -> Result<Device, DriverError> {
+    let query = "
+        SELECT id, name, device_type, ip, power_state, battery_percentage, last_seen, mac_address, child_id, room_id, tags, display_name, icon, online
+        FROM device
+        WHERE ip = $1 AND coalesced_child_id = COALESCE($2, '')
+        ORDER BY last_seen DESC
+        LIMIT 1
+    ";
+    sqlx::query_as::<_, Device>(query)
+        .bind(ip)
+        .bind(child_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| DriverError::DeviceNotFound(format!("ip {ip}")))
+}
+
+pub async fn get_devices_by_type(
+    pool: &PgPool,
+    device_type: DeviceType,
+) -> Result<Vec<Device>, sqlx::Error> {
+    let query = "
+        SELECT id, name, device_type, ip, power_state, battery_percentage, last_seen, mac_address, child_id, room_id, tags, display_name, icon, online
+        FROM device
+        WHERE device_type = $1
+        ORDER BY id
+    ";
+    sqlx::query_as::<_, Device>(query)
+        .bind(device_type)
+        .fetch_all(pool)
         .await
-        .unwrap();
-    sched.start().await?;
+}
+
+/// Moves a Tuya device stored by older versions, keyed on its position in the
+/// cloud's device list or on its MAC alone, to its Tuya device id. Rows keyed
+/// on the position must also match the name, since the position changes with
+/// the list.
+pub async fn rekey_tuya_device(
+    pool: &PgPool,
+    tuya_id: &str,
+    mac_address: Option<&str>,
+    index: usize,
+    name: &str,
+) -> Result<(), sqlx::Error> {
+    let query = "
+        UPDATE device
+        SET child_id = $1
+        WHERE device_type = $2
+            AND (
+                (mac_address = $3 AND child_id IS NULL)
+                OR (mac_address IS NULL AND child_id = $4 AND name = $5)
+            )
+            AND NOT EXISTS (
+                SELECT 1 FROM device WHERE device_type = $2 AND child_id = $1
+            )
+    ";
+    sqlx::query(query)
+        .bind(tuya_id)
+        .bind(DeviceType::TuyaLight)
+        .bind(mac_address)
+        .bind(index.to_string())
+        .bind(name)
+        .execute(pool)
+        .await?;
     Ok(())
 }
 
-pub async fn execute_function(function_name: String, function_args: serde_json::Value) -> Value {
-    match function_name.as_str() {
-        "roku_send_keypress" => {
-            let key = function_args["key"].as_str().unwrap();
-            let ip = function_args["ip"].as_str().unwrap();
-            roku_send_keypress(ip, key).await
+/// Every outlet of the power strip at `ip`, in the strip's order.
+pub async fn get_power_strip_outlets(pool: &PgPool, ip: &str) -> Result<Vec<Device>, sqlx::Error> {
+    let query = "
+        SELECT id, name, device_type, ip, power_state, battery_percentage, last_seen, mac_address, child_id, room_id, tags, display_name, icon, online
+        FROM device
+        WHERE ip = $1 AND device_type = $2 AND child_id IS NOT NULL
+        ORDER BY child_id
+    ";
+    sqlx::query_as::<_, Device>(query)
+        .bind(ip)
+        .bind(DeviceType::KasaPowerStrip)
+        .fetch_all(pool)
+        .await
+}
+
+/// Stores the state and name each outlet reports in `strip`'s sysinfo on the
+/// outlet's own row, after a command that switched several at once.
+pub async fn update_power_strip_outlets(
+    pool: &PgPool,
+    event_bus: &EventBus,
+    ip: &str,
+    strip: &TPLinkSmartPowerStripRes,
+) -> Result<(), sqlx::Error> {
+    let query = "
+        UPDATE device
+        SET power_state = $1, name = $2, last_seen = NOW()
+        WHERE ip = $3 AND device_type = $4 AND child_id = $5
+        RETURNING id
+    ";
+    for outlet in &strip.children {
+        let device_id = sqlx::query_scalar::<_, i64>(query)
+            .bind(outlet.state)
+            .bind(&outlet.alias)
+            .bind(ip)
+            .bind(DeviceType::KasaPowerStrip)
+            .bind(strip.outlet_child_id(outlet))
+            .fetch_optional(pool)
+            .await?;
+        if let Some(device_id) = device_id {
+            event_bus.publish(Event::DeviceStateChanged {
+                device_id,
+                state: DeviceState {
+                    on: outlet.state != 0,
+                    ..Default::default()
+                },
+            });
         }
-        "tplink_turn_plug_on" => {
-            let ip = function_args["ip"].as_str().unwrap();
-            tplink_turn_plug_on(ip).await;
-            json!({
-                "message": "success"
-            })
+    }
+    Ok(())
+}
+
+/// Stores what the Roku `device_id` is showing and marks it as seen,
+/// publishing `NowPlayingChanged` when more than the playback position
+/// changed.
+pub async fn update_now_playing(
+    pool: &PgPool,
+    event_bus: &EventBus,
+    device_id: i64,
+    now_playing: &NowPlaying,
+) -> Result<(), sqlx::Error> {
+    let query = "
+        UPDATE device
+        SET now_playing = $1, last_seen = NOW()
+        FROM (SELECT now_playing FROM device WHERE id = $2 FOR UPDATE) AS previous
+        WHERE device.id = $2
+        RETURNING previous.now_playing
+    ";
+    let previous = sqlx::query_scalar::<_, Option<Json<NowPlaying>>>(query)
+        .bind(Json(now_playing))
+        .bind(device_id)
+        .fetch_optional(pool)
+        .await?;
+    let Some(previous) = previous else {
+        return Ok(());
+    };
+    if previous.is_none_or(|Json(previous)| now_playing.changed_from(&previous)) {
+        event_bus.publish(Event::NowPlayingChanged {
+            device_id,
+            now_playing: now_playing.clone(),
+        });
+    }
+    Ok(())
+}
+
+/// The last polled `NowPlaying` of every Roku that has one.
+pub async fn get_roku_now_playing(pool: &PgPool) -> Result<Vec<RokuNowPlaying>, sqlx::Error> {
+    let query = "
+        SELECT id AS device_id, COALESCE(display_name, name) AS name, now_playing
+        FROM device
+        WHERE device_type = $1 AND now_playing IS NOT NULL
+        ORDER BY id
+    ";
+    sqlx::query_as::<_, RokuNowPlaying>(query)
+        .bind(DeviceType::RokuTv)
+        .fetch_all(pool)
+        .await
+}
+
+/// Stores `content` as a raw block in `ipld_blobs`, returning its CID.
+pub async fn insert_ipld_blob(pool: &PgPool, content: &[u8]) -> Result<Cid, sqlx::Error> {
+    let cid = Cid::new_v1(ipld_codecs::RAW, Code::Sha2_256.digest(content));
+    let query = "
+        INSERT INTO ipld_blobs (cid, content)
+        VALUES ($1, $2)
+        ON CONFLICT (cid) DO NOTHING
+    ";
+    sqlx::query(query)
+        .bind(cid.to_bytes())
+        .bind(content)
+        .execute(pool)
+        .await?;
+    Ok(cid)
+}
+
+/// Replaces the channel catalog of the Roku `device` with what it reports.
+/// Icons are only downloaded for new channels and channel updates, and
+/// channels sharing an icon share its blob.
+pub async fn refresh_roku_apps(pool: &PgPool, device: &Device) -> anyhow::Result<usize> {
+    let apps = roku_get_apps(&device.ip).await?.apps;
+    let stored = sqlx::query_as::<_, (String, String, Option<Vec<u8>>)>(
+        "SELECT app_id, version, icon_cid FROM roku_app WHERE device_id = $1",
+    )
+    .bind(device.id)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|(app_id, version, icon_cid)| (app_id, (version, icon_cid)))
+    .collect::<HashMap<_, _>>();
+
+    let mut icon_cids = Vec::with_capacity(apps.len());
+    for app in &apps {
+        let icon_cid = match stored.get(&app.id) {
+            Some((version, Some(icon_cid))) if *version == app.version => Some(icon_cid.clone()),
+            _ => match roku_get_channel_icon(&device.ip, &app.id).await {
+                Ok(icon) => Some(insert_ipld_blob(pool, &icon).await?.to_bytes()),
+                Err(e) => {
+                    warn!(
+                        "Failed to get the {} icon from {}: {e}",
+                        app.name, device.ip
+                    );
+                    None
+                }
+            },
+        };
+        icon_cids.push(icon_cid);
+    }
+
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM roku_app WHERE device_id = $1 AND NOT app_id = ANY($2)")
+        .bind(device.id)
+        .bind(apps.iter().map(|app| app.id.clone()).collect::<Vec<_>>())
+        .execute(&mut *tx)
+        .await?;
+    let query = "
+        INSERT INTO roku_app (device_id, app_id, name, app_type, version, icon_cid, position)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (device_id, app_id) DO UPDATE
+        SET name = EXCLUDED.name,
+            app_type = EXCLUDED.app_type,
+            version = EXCLUDED.version,
+            icon_cid = EXCLUDED.icon_cid,
+            position = EXCLUDED.position
+    ";
+    for (position, (app, icon_cid)) in apps.iter().zip(icon_cids).enumerate() {
+        sqlx::query(query)
+            .bind(device.id)
+            .bind(&app.id)
+            .bind(&app.name)
+            .bind(&app.app_type)
+            .bind(&app.version)
+            .bind(icon_cid)
+            .bind(position as i32)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(apps.len())
+}
+
+/// The channels of the Roku `device_id`, in the order the Roku lists them.
+pub async fn get_roku_apps(pool: &PgPool, device_id: i64) -> Result<Vec<RokuApp>, sqlx::Error> {
+    let query = "
+        SELECT app_id, name, app_type, version, icon_cid
+        FROM roku_app
+        WHERE device_id = $1
+        ORDER BY position
+    ";
+    let rows = sqlx::query_as::<_, (String, String, String, String, Option<Vec<u8>>)>(query)
+        .bind(device_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(app_id, name, app_type, version, icon_cid)| RokuApp {
+            device_id,
+            app_id,
+            name,
+            app_type,
+            version,
+            icon_cid: icon_cid
+                .and_then(|icon_cid| Cid::try_from(icon_cid).ok())
+                .map(|icon_cid| icon_cid.to_string()),
+        })
+        .collect())
+}
+
+/// How often a device is re-read, within its driver's `read_back_window`,
+/// when it doesn't yet report the power state it was just switched to, e.g. a
+/// Roku TV that's still waking up.
+const READ_BACK_DELAY: Duration = Duration::from_millis(500);
+
+/// Dispatches `command` through the driver registry and then reads the state
+/// back from the device, storing what the device reports rather than what was
+/// asked for. Only devices the driver can't query have the power state of a
+/// successful `SetPower` stored as-is. The `device` triggers take care of
+/// recording it in `device_state_history`, and the new state is published as
+/// `DeviceStateChanged`.
+pub async fn execute_device_command(
+    pool: &PgPool,
+    driver_registry: &DriverRegistry,
+    event_bus: &EventBus,
+    device: &Device,
+    command: DeviceCommand,
+) -> Result<(), DriverError> {
+    let expected_power = match &command {
+        DeviceCommand::SetPower { on } => Some(*on),
+        DeviceCommand::FadeTo { state, .. } => Some(state.on),
+        _ => None,
+    };
+    driver_registry.execute(device, command).await?;
+
+    let read_back_deadline =
+        tokio::time::Instant::now() + driver_registry.read_back_window(device.device_type);
+    let state = loop {
+        let state = driver_registry.read_state(device).await.map_err(|e| {
+            DriverError::Device(format!(
+                "Command was sent to {} but its state couldn't be read back: {e}",
+                device.label()
+            ))
+        })?;
+        match (&state, expected_power) {
+            (Some(state), Some(on))
+                if state.on != on
+                    && tokio::time::Instant::now() + READ_BACK_DELAY <= read_back_deadline =>
+            {
+                tokio::time::sleep(READ_BACK_DELAY).await;
+            }
+            _ => break state,
         }
-        "tplink_turn_plug_off" => {
-            let ip = function_args["ip"].as_str().unwrap();
-            tplink_turn_plug_off(ip).await;
-            json!({
-                "message": "success"
-            })
+    };
+
+    // A successful read back also means the device was just seen
+    if let Some(on) = state.as_ref().map(|state| state.on).or(expected_power) {
+        let query = "
+            UPDATE device
+            SET power_state = $1, last_seen = CASE WHEN $2 THEN NOW() ELSE last_seen END
+            WHERE id = $3
+        ";
+        sqlx::query(query)
+            .bind(i32::from(on))
+            .bind(state.is_some())
+            .bind(device.id)
+            .execute(pool)
+            .await?;
+        event_bus.publish(Event::DeviceStateChanged {
+            device_id: device.id,
+            state: state.clone().unwrap_or(DeviceState {
+                on,
+                ..Default::default()
+            }),
+        });
+    }
+
+    match (state, expected_power) {
+        (Some(state), Some(on)) if state.on != on => Err(DriverError::Device(format!(
+            "{} is still {} after being turned {}",
+            device.label(),
+            if state.on { "on" } else { "off" },
+            if on { "on" } else { "off" },
+        ))),
+        _ => Ok(()),
+    }
+}
+
+pub async fn get_device_state_history(
+    pool: &PgPool,
+    device_id: i64,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<DeviceStateHistory>, sqlx::Error> {
+    let query = "
+        SELECT id, device_id, power_state, battery_percentage, recorded_at
+        FROM device_state_history
+        WHERE device_id = $1 AND recorded_at >= $2 AND recorded_at < $3
+        ORDER BY recorded_at
+    ";
+    sqlx::query_as::<_, DeviceStateHistory>(query)
+        .bind(device_id)
+        .bind(start)
+        .bind(end)
+        .fetch_all(pool)
+        .await
+}
+
+/// Returns the last state recorded before `at`, so a timeline starting at
+/// `at` knows what state the device was already in. A state recorded exactly
+/// at `at` is left to `get_device_state_history`.
+pub async fn get_device_state_at(
+    pool: &PgPool,
+    device_id: i64,
+    at: DateTime<Utc>,
+) -> Result<Option<DeviceStateHistory>, sqlx::Error> {
+    let query = "
+        SELECT id, device_id, power_state, battery_percentage, recorded_at
+        FROM device_state_history
+        WHERE device_id = $1 AND recorded_at < $2
+        ORDER BY recorded_at DESC
+        LIMIT 1
+    ";
+    sqlx::query_as::<_, DeviceStateHistory>(query)
+        .bind(device_id)
+        .bind(at)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn get_devices_in_group(
+    pool: &PgPool,
+    group: &DeviceGroup,
+) -> Result<Vec<Device>, sqlx::Error> {
+    let filter = match group {
+        DeviceGroup::Room(_) => "lower(room.name) = lower($1)",
+        DeviceGroup::Zone(_) => "lower(room.zone) = lower($1)",
+        DeviceGroup::Tag(_) => "lower($1) = ANY(device.tags)",
+    };
+    let name = match group {
+        DeviceGroup::Room(name) | DeviceGroup::Zone(name) | DeviceGroup::Tag(name) => name,
+    };
+    let query = format!(
+        "
+        SELECT device.id, device.name, device.device_type, device.ip, device.power_state,
+            device.battery_percentage, device.last_seen, device.mac_address, device.child_id,
+            device.room_id, device.tags, device.display_name, device.icon, device.online
+        FROM device
+        LEFT JOIN room ON room.id = device.room_id
+        WHERE {filter}
+        ORDER BY device.id
+    "
+    );
+    sqlx::query_as::<_, Device>(&query)
+        .bind(name)
+        .fetch_all(pool)
+        .await
+}
+
+/// Sends `command` to every device in `group` that supports it. The devices
+/// are commanded concurrently and a failure on one doesn't stop the others.
+pub async fn execute_group_command(
+    pool: &PgPool,
+    driver_registry: &DriverRegistry,
+    event_bus: &EventBus,
+    group: &DeviceGroup,
+    command: DeviceCommand,
+) -> Result<GroupCommandResult, DriverError> {
+    let devices = get_devices_in_group(pool, group).await?;
+    if devices.is_empty() {
+        return Err(DriverError::DeviceNotFound(group.to_string()));
+    }
+
+    let capability = command.capability();
+    let (supported, skipped): (Vec<_>, Vec<_>) = devices
+        .into_iter()
+        .partition(|device| driver_registry.supports(device.device_type, capability));
+
+    let results = join_all(supported.iter().map(|device| {
+        let command = command.clone();
+        async move {
+            (
+                device.id,
+                execute_device_command(pool, driver_registry, event_bus, device, command).await,
+            )
         }
-        "tplink_turn_light_on_off" => {
-            let ip = function_args["ip"].as_str().unwrap();
-            let state: u8 = function_args["state"].as_str().unwrap().parse().unwrap();
-            tplink_turn_light_on_off(ip, state).await;
-            json!({
-                "message": "success"
-            })
+    }))
+    .await;
+
+    let mut result = GroupCommandResult {
+        skipped: skipped.iter().map(|device| device.id).collect(),
+        ..Default::default()
+    };
+    for (device_id, res) in results {
+        match res {
+            Ok(()) => result.succeeded.push(device_id),
+            Err(e) => {
+                error!("Group command for {group} failed on device {device_id}: {e}");
+                result.failed.push((device_id, e.to_string()));
+            }
         }
-        "tplink_set_light_brightness" => {
-            let ip = function_args["ip"].as_str().unwrap();
-            let brightness: u8 = function_args["brightness"]
-                .as_u64()
-                .unwrap()
-                .try_into()
-                .unwrap();
-            tplink_set_light_brightness(ip, brightness).await;
-            json!({
-                "message": "success"
-            })
+    }
+    Ok(result)
+}
+
+pub async fn get_rooms(pool: &PgPool) -> Result<Vec<Room>, sqlx::Error> {
+    let query = "
+        SELECT id, name, zone
+        FROM room
+        ORDER BY zone, name
+    ";
+    sqlx::query_as::<_, Room>(query).fetch_all(pool).await
+}
+
+pub async fn insert_room(
+    pool: &PgPool,
+    name: &str,
+    zone: Option<&str>,
+) -> Result<Room, sqlx::Error> {
+    let query = "
+        INSERT INTO room (name, zone)
+        VALUES ($1, $2)
+        RETURNING id, name, zone
+    ";
+    sqlx::query_as::<_, Room>(query)
+        .bind(name)
+        .bind(zone)
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn delete_room(pool: &PgPool, id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM room WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|_| ())
+}
+
+pub async fn set_device_room(
+    pool: &PgPool,
+    device_id: i64,
+    room_id: Option<i64>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE device SET room_id = $1 WHERE id = $2")
+        .bind(room_id)
+        .bind(device_id)
+        .execute(pool)
+        .await
+        .map(|_| ())
+}
+
+/// Sets the user owned display name and icon. Empty values clear them so the
+/// device falls back to its discovered name and type icon.
+pub async fn set_device_display_name(
+    pool: &PgPool,
+    device_id: i64,
+    display_name: Option<String>,
+    icon: Option<String>,
+) -> Result<(), sqlx::Error> {
+    let display_name = display_name.filter(|name| !name.trim().is_empty());
+    let icon = icon.filter(|icon| !icon.trim().is_empty());
+    sqlx::query("UPDATE device SET display_name = $1, icon = $2 WHERE id = $3")
+        .bind(display_name.as_deref().map(str::trim))
+        .bind(icon.as_deref().map(str::trim))
+        .bind(device_id)
+        .execute(pool)
+        .await
+        .map(|_| ())
+}
+
+/// Tags are matched case-insensitively, so they're stored trimmed and
+/// lowercased with duplicates removed.
+pub async fn set_device_tags(
+    pool: &PgPool,
+    device_id: i64,
+    tags: Vec<String>,
+) -> Result<Vec<String>, sqlx::Error> {
+    let mut tags = tags
+        .iter()
+        .map(|tag| tag.trim().to_lowercase())
+        .filter(|tag| !tag.is_empty())
+        .collect::<Vec<_>>();
+    tags.sort();
+    tags.dedup();
+    sqlx::query("UPDATE device SET tags = $1 WHERE id = $2")
+        .bind(&tags)
+        .bind(device_id)
+        .execute(pool)
+        .await?;
+    Ok(tags)
+}
+
+async fn get_scene_devices(pool: &PgPool, scene_id: i64) -> Result<Vec<SceneDevice>, sqlx::Error> {
+    let query = "
+        SELECT device_id, state
+        FROM scene_device
+        WHERE scene_id = $1
+        ORDER BY device_id
+    ";
+    sqlx::query_as::<_, SceneDevice>(query)
+        .bind(scene_id)
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn get_scenes(pool: &PgPool) -> Result<Vec<Scene>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (i64, String)>("SELECT id, name FROM scene ORDER BY name")
+        .fetch_all(pool)
+        .await?;
+    let mut scenes = Vec::with_capacity(rows.len());
+    for (id, name) in rows {
+        let devices = get_scene_devices(pool, id).await?;
+        scenes.push(Scene { id, name, devices });
+    }
+    Ok(scenes)
+}
+
+pub async fn get_scene_by_id(pool: &PgPool, id: i64) -> Result<Scene, DriverError> {
+    let (id, name) = sqlx::query_as::<_, (i64, String)>("SELECT id, name FROM scene WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| DriverError::SceneNotFound(format!("id {id}")))?;
+    let devices = get_scene_devices(pool, id).await?;
+    Ok(Scene { id, name, devices })
+}
+
+/// Scene names are matched case-insensitively so the assistant and scripts
+/// don't have to get the capitalization right.
+pub async fn get_scene_by_name(pool: &PgPool, name: &str) -> Result<Scene, DriverError> {
+    let (id, name) = sqlx::query_as::<_, (i64, String)>(
+        "SELECT id, name FROM scene WHERE lower(name) = lower($1)",
+    )
+    .bind(name.trim())
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| DriverError::SceneNotFound(name.to_string()))?;
+    let devices = get_scene_devices(pool, id).await?;
+    Ok(Scene { id, name, devices })
+}
+
+/// Captures the current state of `device_ids` as the scene `name`, replacing
+/// the scene if one with that name already exists. Nothing is saved unless
+/// every device could be read.
+pub async fn save_scene(
+    pool: &PgPool,
+    driver_registry: &DriverRegistry,
+    name: &str,
+    device_ids: &[i64],
+) -> Result<Scene, DriverError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(DriverError::InvalidArgs(
+            "scene name is required".to_string(),
+        ));
+    }
+    if device_ids.is_empty() {
+        return Err(DriverError::InvalidArgs(
+            "a scene needs at least one device".to_string(),
+        ));
+    }
+
+    let devices = join_all(device_ids.iter().map(|&device_id| async move {
+        let device = get_device_by_id(pool, device_id).await?;
+        let state = driver_registry.get_state(&device).await?;
+        Ok::<_, DriverError>(SceneDevice { device_id, state })
+    }))
+    .await
+    .into_iter()
+    .collect::<Result<Vec<_>, _>>()?;
+
+    let mut tx = pool.begin().await?;
+    let query = "
+        INSERT INTO scene (name)
+        VALUES ($1)
+        ON CONFLICT (name) DO UPDATE SET updated_at = NOW()
+        RETURNING id
+    ";
+    let id: i64 = sqlx::query_scalar(query)
+        .bind(name)
+        .fetch_one(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM scene_device WHERE scene_id = $1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    for device in &devices {
+        sqlx::query("INSERT INTO scene_device (scene_id, device_id, state) VALUES ($1, $2, $3)")
+            .bind(id)
+            .bind(device.device_id)
+            .bind(Json(&device.state))
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    Ok(Scene {
+        id,
+        name: name.to_string(),
+        devices,
+    })
+}
+
+pub async fn delete_scene(pool: &PgPool, id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM scene WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|_| ())
+}
+
+/// Restores every device in `scene` concurrently. Parts of a state the device
+/// no longer supports are left out, and a device with nothing left to restore
+/// is skipped.
+pub async fn activate_scene(
+    pool: &PgPool,
+    driver_registry: &DriverRegistry,
+    event_bus: &EventBus,
+    scene: &Scene,
+) -> Result<GroupCommandResult, DriverError> {
+    async fn restore(
+        pool: &PgPool,
+        driver_registry: &DriverRegistry,
+        event_bus: &EventBus,
+        device_id: i64,
+        state: &DeviceState,
+    ) -> Result<bool, DriverError> {
+        let device = get_device_by_id(pool, device_id).await?;
+        let commands = state
+            .commands()
+            .into_iter()
+            .filter(|command| driver_registry.supports(device.device_type, command.capability()))
+            .collect::<Vec<_>>();
+        if commands.is_empty() {
+            return Ok(false);
         }
-        "tplink_set_dimmer_brightness" => {
-            let ip = function_args["ip"].as_str().unwrap();
-            let brightness: u8 = function_args["brightness"]
-                .as_u64()
-                .unwrap()
-                .try_into()
-                .unwrap();
-            tplink_set_dimmer_brightness(ip, &brightness).await;
-            json!({
-                "message": "success"
-            })
+        for command in commands {
+            execute_device_command(pool, driver_registry, event_bus, &device, command).await?;
         }
-        "handle_smart_light_toggle" => {
-            handle_smart_light_toggle(
-                function_args["state"].as_bool().unwrap(),
-                function_args["ip"].as_str().unwrap().to_string(),
+        Ok(true)
+    }
+
+    let results = join_all(scene.devices.iter().map(|scene_device| async move {
+        (
+            scene_device.device_id,
+            restore(
+                pool,
+                driver_registry,
+                event_bus,
+                scene_device.device_id,
+                &scene_device.state,
             )
-            .await
-            .unwrap();
-            json!({
-                "message": "success"
-            })
+            .await,
+        )
+    }))
+    .await;
+
+    let mut result = GroupCommandResult::default();
+    for (device_id, res) in results {
+        match res {
+            Ok(true) => result.succeeded.push(device_id),
+            Ok(false) => result.skipped.push(device_id),
+            Err(e) => {
+                error!("Scene {} failed on device {device_id}: {e}", scene.name);
+                result.failed.push((device_id, e.to_string()));
+            }
         }
-        "roku_search" => {
-            let query = function_args["query"].as_str().unwrap();
-            let ip = function_args["ip"].as_str().unwrap();
-            roku_search(ip, query).await
+    }
+    Ok(result)
+}
+
+/// Resolves the device an action targets. Actions reference devices by
+/// `device_id`; `ip` is still accepted for actions and scripts written before
+/// devices had a stable identity.
+async fn device_arg(pool: &PgPool, args: &Value) -> Result<Device, DriverError> {
+    if let Some(device_id) = args["device_id"].as_i64() {
+        return get_device_by_id(pool, device_id).await;
+    }
+    let ip = str_arg(args, "ip")?;
+    warn!("Device referenced by ip {ip}, use device_id instead");
+    get_device_by_ip(pool, ip, args["child_id"].as_str()).await
+}
+
+fn str_arg<'a>(args: &'a Value, name: &str) -> Result<&'a str, DriverError> {
+    args[name]
+        .as_str()
+        .ok_or_else(|| DriverError::InvalidArgs(format!("expected string argument `{name}`")))
+}
+
+fn bool_arg(args: &Value, name: &str) -> Result<bool, DriverError> {
+    args[name]
+        .as_bool()
+        .ok_or_else(|| DriverError::InvalidArgs(format!("expected bool argument `{name}`")))
+}
+
+fn u8_arg(args: &Value, name: &str) -> Result<u8, DriverError> {
+    let value = match &args[name] {
+        Value::Number(n) => n.as_u64(),
+        // Older actions stored numbers as strings
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    };
+    value
+        .and_then(|value| u8::try_from(value).ok())
+        .ok_or_else(|| DriverError::InvalidArgs(format!("expected 0-255 argument `{name}`")))
+}
+
+#[derive(Deserialize)]
+struct DeviceCommandArgs {
+    device_id: i64,
+    #[serde(flatten)]
+    command: DeviceCommand,
+}
+
+#[derive(Deserialize)]
+struct GroupCommandArgs {
+    room: Option<String>,
+    zone: Option<String>,
+    tag: Option<String>,
+    #[serde(flatten)]
+    command: DeviceCommand,
+}
+
+impl GroupCommandArgs {
+    fn group(&mut self) -> Result<DeviceGroup, DriverError> {
+        match (self.room.take(), self.zone.take(), self.tag.take()) {
+            (Some(room), None, None) => Ok(DeviceGroup::Room(room)),
+            (None, Some(zone), None) => Ok(DeviceGroup::Zone(zone)),
+            (None, None, Some(tag)) => Ok(DeviceGroup::Tag(tag)),
+            _ => Err(DriverError::InvalidArgs(
+                "expected exactly one of `room`, `zone` or `tag`".to_string(),
+            )),
         }
-        "roku_launch_app" => {
-            let app_id = function_args["app_id"].as_str().unwrap();
-            let ip = function_args["ip"].as_str().unwrap();
-            roku_launch_app(ip, app_id).await
+    }
+}
+
+/// Runs a named function from an action, the assistant or a script. Device
+/// functions are resolved to a `Device` and dispatched through the driver
+/// registry; `device_command` is the generic form taking a `device_id` and a
+/// flattened `DeviceCommand` and `group_command` takes one of `room`, `zone` or
+/// `tag` instead of the id. `activate_scene` takes a `scene_id` or `name`. The
+/// rest are kept for existing actions. Every call is published as
+/// `ActionExecuted`.
+pub async fn execute_function(
+    pool: &PgPool,
+    driver_registry: &DriverRegistry,
+    event_bus: &EventBus,
+    function_name: String,
+    function_args: Value,
+) -> Result<Value, DriverError> {
+    let result = run_function(
+        pool,
+        driver_registry,
+        event_bus,
+        &function_name,
+        &function_args,
+    )
+    .await;
+    event_bus.publish(Event::ActionExecuted {
+        function_name,
+        function_args,
+        error: result.as_ref().err().map(ToString::to_string),
+    });
+    result
+}
+
+async fn run_function(
+    pool: &PgPool,
+    driver_registry: &DriverRegistry,
+    event_bus: &EventBus,
+    function_name: &str,
+    args: &Value,
+) -> Result<Value, DriverError> {
+    let (device, command) = match function_name {
+        "device_command" => {
+            let DeviceCommandArgs { device_id, command } = serde_json::from_value(args.clone())
+                .map_err(|e| DriverError::InvalidArgs(e.to_string()))?;
+            (get_device_by_id(pool, device_id).await?, command)
         }
-        "stoplight_toggle" => {
-            let color = function_args["color"].as_str().unwrap();
-            let result = toggle_stoplight(color).await.is_ok();
-            json!({"success": result})
+        "group_command" => {
+            let mut args: GroupCommandArgs = serde_json::from_value(args.clone())
+                .map_err(|e| DriverError::InvalidArgs(e.to_string()))?;
+            let group = args.group()?;
+            let result =
+                execute_group_command(pool, driver_registry, event_bus, &group, args.command)
+                    .await?;
+            return Ok(json!(result));
         }
-        &_ => todo!(),
-    }
+        "activate_scene" => {
+            let scene = match args["scene_id"].as_i64() {
+                Some(scene_id) => get_scene_by_id(pool, scene_id).await?,
+                None => get_scene_by_name(pool, str_arg(args, "name")?).await?,
+            };
+            let result = activate_scene(pool, driver_registry, event_bus, &scene).await?;
+            return Ok(json!(result));
+        }
+        "roku_send_keypress" => (
+            device_arg(pool, args).await?,
+            DeviceCommand::Keypress {
+                key: str_arg(args, "key")?.to_string(),
+            },
+        ),
+        "roku_launch_app" => (
+            device_arg(pool, args).await?,
+            DeviceCommand::LaunchApp {
+                app_id: str_arg(args, "app_id")?.to_string(),
+            },
+        ),
+        "roku_search" => {
+            let device = device_arg(pool, args).await?;
+            return roku_search(&device.ip, str_arg(args, "query")?)
+                .await
+                .map_err(|e| DriverError::Device(format!("{}: {e}", device.label())));
+        }
+        "tplink_turn_plug_on" | "tplink_turn_plug_off" => (
+            device_arg(pool, args).await?,
+            DeviceCommand::SetPower {
+                on: function_name == "tplink_turn_plug_on",
+            },
+        ),
+        "tplink_turn_light_on_off" => (
+            device_arg(pool, args).await?,
+            DeviceCommand::SetPower {
+                on: u8_arg(args, "state")? != 0,
+            },
+        ),
+        "handle_smart_light_toggle" => (
+            device_arg(pool, args).await?,
+            DeviceCommand::SetPower {
+                on: bool_arg(args, "state")?,
+            },
+        ),
+        "tplink_set_light_brightness" | "tplink_set_dimmer_brightness" => (
+            device_arg(pool, args).await?,
+            DeviceCommand::SetBrightness {
+                brightness: u8_arg(args, "brightness")?,
+            },
+        ),
+        "stoplight_toggle" => (
+            get_devices_by_type(pool, DeviceType::Stoplight)
+                .await?
+                .into_iter()
+                .next()
+                .ok_or_else(|| DriverError::DeviceNotFound("stoplight".to_string()))?,
+            DeviceCommand::SetColor {
+                color: str_arg(args, "color")?.to_string(),
+            },
+        ),
+        _ => return Err(DriverError::UnknownFunction(function_name.to_string())),
+    };
+
+    execute_device_command(pool, driver_registry, event_bus, &device, command).await?;
+    Ok(json!({
+        "message": "success"
+    }))
 }
 
 pub async fn insert_tuya_device_keys(
@@ -311,491 +1270,296 @@ pub async fn insert_cameras_into_db(
     Ok(())
 }
 
-pub async fn insert_auth(pool: &PgPool, name: &str, state: AuthState) {
-    let dt = Utc::now();
-    let query = "
-        INSERT INTO auth (name, auth_token, refresh_token, hardware_id, last_login) 
-        VALUES ($1, $2, $3, $4, $5)
-        ON CONFLICT(name) DO UPDATE SET
-            auth_token = EXCLUDED.auth_token,
-            refresh_token = EXCLUDED.refresh_token,
-            hardware_id = EXCLUDED.hardware_id,
-            last_login = EXCLUDED.last_login;
-    ";
-
-    sqlx::query(query)
-        .bind(name)
-        .bind(&state.auth_token)
-        .bind(&state.refresh_token)
-        .bind(&state.hardware_id)
-        .bind(dt)
-        .execute(pool)
-        .await
-        .unwrap();
-}
-
-pub async fn get_auth_from_db(pool: &PgPool, name: &str) -> AuthState {
-    let query = "
-        SELECT hardware_id, auth_token, refresh_token 
-        FROM auth
-        WHERE name=$1
-    ";
-
-    let auth_query = sqlx::query_as::<_, AuthState>(query)
-        .bind(name)
-        .fetch_one(pool)
-        .await;
-
-    match auth_query {
-        Ok(state) => state,
-        Err(err) => {
-            error!("{err}");
-            AuthState {
-                hardware_id: "".to_string(),
-                auth_token: "".to_string(),
-                refresh_token: "".to_string(),
-            }
-        }
-    }
-}
-
 #[derive(Debug, Clone)]
 pub struct AppState {
     pub leptos_options: LeptosOptions,
     pub ring_rest_client: Arc<RingRestClient>,
     pub pool: PgPool,
     pub cron_client: CronClient,
-    pub control_senders: Arc<RwLock<HashMap<String, Sender<ControlMessage>>>>,
-    pub mish_state_modification_bus_sender:
-        tokio::sync::mpsc::UnboundedSender<MishStateModification>,
+    pub secrets: SecretStore,
+    pub supervisor: Supervisor,
+    pub driver_registry: DriverRegistry,
+    pub event_bus: EventBus,
 }
 
-pub fn match_control_message(msg: ControlMessage, running: &mut bool) -> bool {
-    match msg {
-        ControlMessage::Start => {
-            println!("Starting discovery job");
-            *running = true;
-            true
-        }
-        ControlMessage::Stop => {
-            println!("Stopping discovery job");
-            *running = false;
-            true
-        }
-        ControlMessage::Shutdown => {
-            println!("Shutting down discovery job");
-            false
-        }
-    }
-}
-
-pub fn tuya_job(
+/// Discovers TP-Link devices by broadcast and by probing `scan_hosts`, and
+/// upserts them.
+pub async fn refresh_tplink_devices(
     shared_pool: PgPool,
-    mut control_rx: Receiver<ControlMessage>,
-    initial_enabled: bool,
-) {
-    tokio::task::spawn(async move {
-        println!("Running Tuya discovery job");
-        let mut auth_interval = tokio::time::interval(chrono::Duration::hours(1).to_std().unwrap());
-        let mut discovery_interval =
-            tokio::time::interval(chrono::Duration::hours(1).to_std().unwrap());
-        let mut running = initial_enabled;
+    event_bus: &EventBus,
+    scan_hosts: &[Ipv4Addr],
+) -> anyhow::Result<usize> {
+    let tp_link_devices = discover_devices(scan_hosts)
+        .await
+        .map_err(|e| anyhow::anyhow!("Discovery failed: {e}"))?;
+    let mut devices: Vec<Device> = Vec::new();
 
-        loop {
-            tokio::select! {
-                _ = auth_interval.tick(), if running => {
-                    let tuya_auth = get_auth_from_db(&shared_pool, "tuya").await;
-                    if !tuya_auth.refresh_token.is_empty() {
-                        println!("Found a refresh_token, refreshing auth_token");
-                        let res = get_refresh_token().await.unwrap();
-                        insert_auth(
-                            &shared_pool,
-                            "tuya",
-                            AuthState {
-                                refresh_token: res.result.refresh_token,
-                                hardware_id: res.result.uid,
-                                auth_token: res.result.access_token,
-                            },
-                        )
-                        .await;
-                    } else {
-                        println!("No refresh_token found, getting a new one");
-                        let res = get_refresh_token().await.unwrap();
-                        insert_auth(
-                            &shared_pool,
-                            "tuya",
-                            AuthState {
-                                refresh_token: res.result.refresh_token,
-                                hardware_id: res.result.uid,
-                                auth_token: res.result.access_token,
-                            },
-                        )
-                        .await;
-                    }
-                },
-                _ = discovery_interval.tick(), if running => {
-                    let tuya_auth = get_auth_from_db(&shared_pool, "tuya").await;
-                    if !tuya_auth.auth_token.is_empty() {
-                        // @TODO refactor user_id to come from db
-                        let res = get_devices("az17063780590351Cr1b", &tuya_auth.auth_token)
-                            .await
-                            .unwrap();
-                        println!("{:?}", res);
-                        let devices: Vec<Device> = res
-                            .result
-                            .iter().enumerate()
-                            .map(|(index, device)| {
-                                let ip = Ipv4Addr::new(0, 0, 0, 0).to_string();
-                                Device {
-                                    id: 0,
-                                    name: device.name.clone(),
-                                    device_type: DeviceType::TuyaLight,
-                                    ip,
-                                    power_state: 0,
-                                    battery_percentage: 0,
-                                    last_seen: Utc::now(),
-                                    mac_address: None,
-                                    child_id: Some(index.to_string()),
-                                }
-                            })
-                            .collect();
-
-                        println!("bhap: {:?}", devices);
-                        insert_devices_into_db(&shared_pool, &devices)
-                            .await
-                            .unwrap();
-
-                        // task for local network discovery
-                        tokio::task::spawn(async {
-                            match tokio::time::timeout(
-                                std::time::Duration::from_secs(10),
-                                discover_tuya_devices(),
-                            ).await {
-                                Ok(Ok(_)) => println!("Local Tuya discovery completed."),
-                                Ok(Err(e)) => println!("Error during local Tuya discovery: {}", e),
-                                Err(_) => println!("Local Tuya discovery timed out."),
-                            }
-                        });
-                    }
-                },
-                Some(msg) = control_rx.recv() => {
-                    println!("Received control message: {:?}", msg);
-                    if !match_control_message(msg, &mut running) {
-                        break;
-                    }
-                },
-                else => {
-                    println!("Control channel closed");
-                    break;
+    for device_data in tp_link_devices {
+        match device_data {
+            DeviceData::SmartPlug(data) => {
+                if let Some(ip) = data.ip {
+                    devices.push(Device {
+                        id: 0,
+                        name: data.alias,
+                        device_type: DeviceType::KasaPlug,
+                        ip: ip.to_string(),
+                        power_state: data.relay_state,
+                        battery_percentage: 0,
+                        last_seen: Utc::now(),
+                        mac_address: normalize_mac(&data.mac),
+                        child_id: None,
+                        room_id: None,
+                        tags: Vec::new(),
+                        display_name: None,
+                        icon: None,
+                        online: true,
+                    });
                 }
             }
-        }
-    });
-}
-
-pub fn eufy_job(
-    shared_pool: PgPool,
-    mut control_rx: Receiver<ControlMessage>,
-    initial_enabled: bool,
-) {
-    tokio::task::spawn(async move {
-        println!("Running Eufy discovery job");
-        let mut auth_interval = tokio::time::interval(chrono::Duration::hours(5).to_std().unwrap());
-        let mut discovery_interval =
-            tokio::time::interval(chrono::Duration::hours(1).to_std().unwrap());
-        let mut running = initial_enabled;
-
-        loop {
-            tokio::select! {
-                _ = auth_interval.tick(), if running => {
-                    let eufy_auth = get_auth_from_db(&shared_pool, "eufy").await;
-                    if !eufy_auth.refresh_token.is_empty() {
-                        println!("Found a refresh_token, refreshing auth_token");
-                        // Add your refresh logic here
-                    } else {
-                        println!("No refresh_token found, getting a new one");
-                        let res = efuy::eufy_login().await;
-                        insert_auth(
-                            &shared_pool,
-                            "eufy",
-                            AuthState {
-                                refresh_token: res.data.auth_token.to_owned(),
-                                hardware_id: res.data.user_id,
-                                auth_token: res.data.auth_token,
-                            },
-                        )
-                        .await;
-                    }
-                },
-                _ = discovery_interval.tick(), if running => {
-                    let eufy_auth = get_auth_from_db(&shared_pool, "eufy").await;
-                    if !eufy_auth.auth_token.is_empty() {
-                        efuy::get_devices(eufy_auth.auth_token).await;
-                    }
-                },
-                Some(msg) = control_rx.recv() => {
-                    println!("Received control message: {:?}", msg);
-                    if !match_control_message(msg, &mut running) {
-                        break;
-                    }
-                },
-                else => {
-                    println!("Control channel closed");
-                    break;
+            DeviceData::SmartLight(data) => {
+                if let Some(ip) = data.ip {
+                    devices.push(Device {
+                        id: 0,
+                        name: data.alias,
+                        device_type: DeviceType::KasaLight,
+                        ip: ip.to_string(),
+                        power_state: data.light_state.on_off,
+                        battery_percentage: 0,
+                        last_seen: Utc::now(),
+                        mac_address: data.mic_mac.as_deref().and_then(normalize_mac),
+                        child_id: None,
+                        room_id: None,
+                        tags: Vec::new(),
+                        display_name: None,
+                        icon: None,
+                        online: true,
+                    });
                 }
             }
-        }
-    });
-}
-
-pub fn ring_job(
-    shared_pool: PgPool,
-    ring_rest_client: Arc<RingRestClient>,
-    mut control_rx: Receiver<ControlMessage>,
-    initial_enabled: bool,
-) {
-    tokio::task::spawn(async move {
-        println!("Running Ring discovery job");
-        let mut auth_interval = tokio::time::interval(chrono::Duration::hours(5).to_std().unwrap());
-        let mut discovery_interval =
-            tokio::time::interval(chrono::Duration::minutes(5).to_std().unwrap());
-        let mut running = initial_enabled;
-
-        loop {
-            tokio::select! {
-                _ = auth_interval.tick(), if running => {
-                    info!("Refreshing Ring auth token");
-                    ring_rest_client.refresh_auth_token().await;
-                },
-                _ = discovery_interval.tick(), if running => {
-                    info!("Refreshing Ring Device Data");
-                    let ring_devices = match ring_rest_client.get_devices().await {
-                        Ok(data) => data,
-                        Err(_) => DevicesRes {
-                            doorbots: Vec::new(),
-                            authorized_doorbots: Vec::new(),
-                        },
-                    };
-
-                    let doorbots = ring_devices
-                        .doorbots
-                        .into_iter()
-                        .chain(ring_devices.authorized_doorbots)
-                        .collect::<Vec<_>>();
-
-                    let mut cameras = Vec::with_capacity(20);
-                    for doorbot in doorbots.iter() {
-                        cameras.push(get_ring_camera(&ring_rest_client, doorbot).await)
-                    }
-
-                    let mut devices = Vec::with_capacity(20);
-                    for camera in cameras.iter() {
+            DeviceData::SmartDimmer(data) => {
+                if let Some(ip) = data.ip {
+                    devices.push(Device {
+                        id: 0,
+                        name: data.alias,
+                        device_type: DeviceType::KasaDimmer,
+                        ip: ip.to_string(),
+                        power_state: data.relay_state,
+                        battery_percentage: 0,
+                        last_seen: Utc::now(),
+                        mac_address: normalize_mac(&data.mac),
+                        child_id: None,
+                        room_id: None,
+                        tags: Vec::new(),
+                        display_name: None,
+                        icon: None,
+                        online: true,
+                    });
+                }
+            }
+            DeviceData::SmartPowerStrip(data) => {
+                if let Some(ip) = data.ip {
+                    for outlet in &data.children {
                         devices.push(Device {
                             id: 0,
-                            name: camera.description.to_string(),
-                            ip: camera.id.to_string(),
-                            device_type: DeviceType::RingDoorbell,
-                            power_state: 1,
-                            battery_percentage: camera.health,
+                            name: outlet.alias.clone(),
+                            device_type: DeviceType::KasaPowerStrip,
+                            ip: ip.to_string(),
+                            power_state: outlet.state,
+                            battery_percentage: 0,
                             last_seen: Utc::now(),
-                            mac_address: None,
-                            child_id: None,
+                            mac_address: normalize_mac(&data.mac),
+                            child_id: Some(data.outlet_child_id(outlet)),
+                            room_id: None,
+                            tags: Vec::new(),
+                            display_name: None,
+                            icon: None,
+                            online: true,
                         });
                     }
-                    match insert_cameras_into_db(&shared_pool, &cameras).await {
-                        Ok(_) => print!("success"),
-                        Err(err) => error!("{err}"),
-                    }
-                    match insert_devices_into_db(&shared_pool, &devices).await {
-                        Ok(_) => print!("success"),
-                        Err(err) => error!("{err}"),
-                    }
-                },
-                Some(msg) = control_rx.recv() => {
-                    println!("Received control message: {:?}", msg);
-                    if !match_control_message(msg, &mut running) {
-                        break;
-                    }
-                },
-                else => {
-                    println!("Control channel closed");
-                    break;
                 }
             }
         }
-    });
+    }
+    insert_devices_into_db(&shared_pool, event_bus, &devices).await?;
+    Ok(devices.len())
 }
 
-pub fn roku_discovery_job(
-    shared_pool: PgPool,
-    mut control_rx: Receiver<ControlMessage>,
-    initial_enabled: bool,
-) {
-    tokio::task::spawn(async move {
-        println!("Running Roku discovery job");
-        let mut interval = tokio::time::interval(chrono::Duration::hours(1).to_std().unwrap());
-        let mut running = initial_enabled;
+/// Polls the emeter of every Kasa plug and power strip outlet and stores the
+/// readings. Devices that turn out not to have an emeter are added to
+/// `without_emeter` and skipped from then on.
+pub async fn poll_tplink_energy(pool: &PgPool, without_emeter: &mut HashSet<i64>) {
+    let mut devices = Vec::new();
+    for device_type in [DeviceType::KasaPlug, DeviceType::KasaPowerStrip] {
+        match get_devices_by_type(pool, device_type).await {
+            Ok(found) => devices.extend(found),
+            Err(e) => error!("Failed to load {device_type} devices: {e}"),
+        }
+    }
+    devices.retain(|device| !without_emeter.contains(&device.id));
 
-        loop {
-            tokio::select! {
-                _ = interval.tick(), if running => {
-                    let roku_devices = roku_discover().await;
-                    let mut devices: Vec<Device> = Vec::new();
-
-                    for device in roku_devices.iter() {
-                        let ip = extract_ip(&device.location).unwrap();
-                        let device_info = roku_get_device_info(&ip).await;
-                        let power_state = if device_info.power_mode == "PowerOn" {
-                            1
-                        } else {
-                            0
-                        };
-                        devices.push(Device {
-                            id: 0,
-                            name: device_info.user_device_name,
-                            device_type: DeviceType::RokuTv,
-                            ip,
-                            power_state,
-                            battery_percentage: 0,
-                            last_seen: Utc::now(),
-                            mac_address: None,
-                            child_id: None,
-                        });
-                    }
+    let results = join_all(devices.iter().map(|device| async move {
+        let res = tplink_kasa_get_emeter_realtime(&device.ip, device.child_id.as_deref()).await;
+        (device, res)
+    }))
+    .await;
 
-                    match insert_devices_into_db(&shared_pool, &devices).await {
-                        Ok(_) => {}
-                        Err(e) => {
-                            print!("{e}");
-                        }
-                    };
-                    tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
-                },
-                Some(msg) = control_rx.recv() => {
-                    println!("Received control message: {msg:?}");
-                    if !match_control_message(msg, &mut running) {
-                        break;
-                    }
-                }
+    for (device, res) in results {
+        let realtime = match res {
+            Ok(realtime) => realtime,
+            Err(e) if e.is_unsupported() => {
+                info!("{} has no emeter, not polling it again", device.label());
+                without_emeter.insert(device.id);
+                continue;
             }
+            Err(e) => {
+                error!("Failed to read emeter of {}: {e}", device.label());
+                continue;
+            }
+        };
+        let reading = EnergyReading {
+            device_id: device.id,
+            recorded_at: Utc::now(),
+            voltage: realtime.voltage(),
+            current: realtime.current(),
+            power: realtime.power(),
+            total_kwh: realtime.total_kwh(),
+        };
+        if let Err(e) = insert_energy_reading(pool, &reading).await {
+            error!("Failed to store emeter reading of {}: {e}", device.label());
         }
-    });
+    }
 }
 
-pub async fn refresh_tplink_devices(shared_pool: PgPool) {
-    match discover_devices().await {
-        Ok(tp_link_devices) => {
-            let mut devices: Vec<Device> = Vec::new();
-
-            for device_data in tp_link_devices {
-                match device_data {
-                    DeviceData::SmartPlug(data) => {
-                        if let Some(ip) = data.ip {
-                            devices.push(Device {
-                                id: 0,
-                                name: data.alias,
-                                device_type: DeviceType::KasaPlug,
-                                ip: ip.to_string(),
-                                power_state: data.relay_state,
-                                battery_percentage: 0,
-                                last_seen: Utc::now(),
-                                mac_address: None,
-                                child_id: None,
-                            });
-                        }
-                    }
-                    DeviceData::SmartLight(data) => {
-                        if let Some(ip) = data.ip {
-                            devices.push(Device {
-                                id: 0,
-                                name: data.alias,
-                                device_type: DeviceType::KasaLight,
-                                ip: ip.to_string(),
-                                power_state: data.light_state.on_off,
-                                battery_percentage: 0,
-                                last_seen: Utc::now(),
-                                mac_address: None,
-                                child_id: None,
-                            });
-                        }
-                    }
-                    DeviceData::SmartDimmer(data) => {
-                        if let Some(ip) = data.ip {
-                            devices.push(Device {
-                                id: 0,
-                                name: data.alias,
-                                device_type: DeviceType::KasaDimmer,
-                                ip: ip.to_string(),
-                                power_state: data.relay_state,
-                                battery_percentage: 0,
-                                last_seen: Utc::now(),
-                                mac_address: None,
-                                child_id: None,
-                            });
-                        }
-                    }
-                    DeviceData::SmartPowerStrip(data) => {
-                        if let Some(ip) = data.ip {
-                            for outlet in data.children {
-                                devices.push(Device {
-                                    id: 0,
-                                    name: outlet.alias,
-                                    device_type: DeviceType::KasaPowerStrip,
-                                    ip: ip.to_string(),
-                                    power_state: outlet.state,
-                                    battery_percentage: 0,
-                                    last_seen: Utc::now(),
-                                    mac_address: None,
-                                    child_id: Some(format!("{}{}", data.device_id, outlet.id)),
-                                });
-                            }
-                        }
-                    }
-                }
-            }
-            insert_devices_into_db(&shared_pool, &devices)
-                .await
-                .unwrap();
-        }
-        Err(e) => {
-            eprintln!("Error discovering devices: {e}");
+pub async fn insert_energy_reading(
+    pool: &PgPool,
+    reading: &EnergyReading,
+) -> Result<(), sqlx::Error> {
+    let query = "
+        INSERT INTO energy_reading (device_id, recorded_at, voltage, current, power, total_kwh)
+        VALUES ($1, $2, $3, $4, $5, $6)
+    ";
+    sqlx::query(query)
+        .bind(reading.device_id)
+        .bind(reading.recorded_at)
+        .bind(reading.voltage)
+        .bind(reading.current)
+        .bind(reading.power)
+        .bind(reading.total_kwh)
+        .execute(pool)
+        .await
+        .map(|_| ())
+}
+
+/// The most recent reading of every device with an emeter.
+pub async fn get_latest_energy_readings(pool: &PgPool) -> Result<Vec<EnergyReading>, sqlx::Error> {
+    let query = "
+        SELECT DISTINCT ON (device_id) device_id, recorded_at, voltage, current, power, total_kwh
+        FROM energy_reading
+        ORDER BY device_id, recorded_at DESC
+    ";
+    sqlx::query_as::<_, EnergyReading>(query)
+        .fetch_all(pool)
+        .await
+}
+
+/// Energy used per device and day or month since `start`. Usage is the sum of
+/// the increases of each device's lifetime counter, so a counter that resets
+/// doesn't produce negative usage.
+pub async fn get_energy_usage(
+    pool: &PgPool,
+    period: EnergyPeriod,
+    start: DateTime<Utc>,
+) -> Result<Vec<EnergyUsage>, sqlx::Error> {
+    let query = "
+        SELECT
+            device_id,
+            date_trunc($1, recorded_at) AS period_start,
+            COALESCE(SUM(GREATEST(delta, 0)), 0) AS kwh,
+            AVG(power) AS average_power
+        FROM (
+            SELECT
+                device_id,
+                recorded_at,
+                power,
+                total_kwh - LAG(total_kwh) OVER (PARTITION BY device_id ORDER BY recorded_at) AS delta
+            FROM energy_reading
+            WHERE recorded_at >= $2
+        ) AS readings
+        GROUP BY device_id, period_start
+        ORDER BY period_start, device_id
+    ";
+    sqlx::query_as::<_, EnergyUsage>(query)
+        .bind(period.as_str())
+        .bind(start)
+        .fetch_all(pool)
+        .await
+}
+
+/// Whether a device last seen at `last_seen` still counts as online at `now`.
+/// Devices of integrations without a threshold never go offline.
+pub fn is_device_online(
+    last_seen: DateTime<Utc>,
+    offline_after_secs: Option<i32>,
+    now: DateTime<Utc>,
+) -> bool {
+    offline_after_secs.is_none_or(|secs| now - last_seen <= chrono::Duration::seconds(secs.into()))
+}
+
+/// Re-derives `online` for every device from `last_seen` and its
+/// integration's threshold, publishing `DeviceWentOffline` and
+/// `DeviceCameOnline` for the devices that changed.
+pub async fn update_device_availability(
+    pool: &PgPool,
+    event_bus: &EventBus,
+) -> Result<(), sqlx::Error> {
+    let thresholds = get_integrations(pool)
+        .await?
+        .into_iter()
+        .map(|integration| (integration.name, integration.offline_after_secs))
+        .collect::<HashMap<_, _>>();
+
+    let query = "
+        SELECT id, device_type, last_seen, online
+        FROM device
+    ";
+    let devices = sqlx::query_as::<_, (i64, DeviceType, DateTime<Utc>, bool)>(query)
+        .fetch_all(pool)
+        .await?;
+
+    let now = Utc::now();
+    for (device_id, device_type, last_seen, online) in devices {
+        let offline_after_secs = thresholds.get(device_type.integration()).copied().flatten();
+        let now_online = is_device_online(last_seen, offline_after_secs, now);
+        if now_online == online {
+            continue;
         }
+
+        let query = "
+            UPDATE device
+            SET online = $1
+            WHERE id = $2
+        ";
+        sqlx::query(query)
+            .bind(now_online)
+            .bind(device_id)
+            .execute(pool)
+            .await?;
+        event_bus.publish(if now_online {
+            Event::DeviceCameOnline { device_id }
+        } else {
+            Event::DeviceWentOffline { device_id }
+        });
     }
+    Ok(())
 }
 
-pub fn tplink_discovery_job(
-    shared_pool: PgPool,
-    mut control_rx: Receiver<ControlMessage>,
-    initial_enabled: bool,
-) {
-    let shared_pool = shared_pool.clone();
+pub fn availability_job(shared_pool: PgPool, event_bus: EventBus) {
     tokio::task::spawn(async move {
-        println!("Running TPlink discovery job");
-        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(300));
-        let mut running = initial_enabled;
-
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
         loop {
-            tokio::select! {
-                _ = interval.tick(), if running => {
-                    refresh_tplink_devices(shared_pool.clone()).await;
-                },
-                Some(msg) = control_rx.recv() => {
-                    println!("Received control message: {msg:?}");
-                    match msg {
-                        ControlMessage::Start => {
-                            println!("Starting discovery job");
-                            running = true;
-                        }
-                        ControlMessage::Stop => {
-                            println!("Stopping discovery job");
-                            running = false;
-                        }
-                        ControlMessage::Shutdown => {
-                            println!("Shutting down discovery job");
-                            break;
-                        }
-                    }
-                }
+            interval.tick().await;
+            if let Err(e) = update_device_availability(&shared_pool, &event_bus).await {
+                error!("Failed to update device availability: {e}");
             }
         }
     });
@@ -803,7 +1567,7 @@ pub fn tplink_discovery_job(
 
 pub async fn get_integrations(shared_pool: &PgPool) -> Result<Vec<Integration>, sqlx::Error> {
     let query = "
-        SELECT id, name, enabled, image 
+        SELECT id, name, enabled, image, offline_after_secs, settings
         FROM integration
     ";
     sqlx::query_as(query).fetch_all(shared_pool).await
@@ -812,54 +1576,66 @@ pub async fn get_integrations(shared_pool: &PgPool) -> Result<Vec<Integration>,
 pub async fn run_devices_tasks(
     ring_rest_client: Arc<RingRestClient>,
     shared_pool: &PgPool,
-    control_senders: Arc<RwLock<HashMap<String, Sender<ControlMessage>>>>,
+    supervisor: &Supervisor,
+    event_bus: EventBus,
+    secrets: SecretStore,
 ) -> Result<(), sqlx::Error> {
     insert_integrations_into_db(shared_pool).await?;
-    insert_initial_devices_into_db(shared_pool).await?;
-    let integrations = get_integrations(shared_pool).await.unwrap();
+    insert_initial_devices_into_db(shared_pool, &event_bus).await?;
+    let integrations = get_integrations(shared_pool).await?;
+    availability_job(shared_pool.clone(), event_bus.clone());
 
     for integration in integrations {
-        match integration.name.as_str() {
-            "tplink" => {
-                let (tx, rx) = mpsc::channel(10);
-                tplink_discovery_job(shared_pool.clone(), rx, integration.enabled);
-                let mut senders = control_senders.write().await;
-                senders.insert("tplink".to_string(), tx);
-            }
-            "roku" => {
-                let (tx, rx) = mpsc::channel(10);
-                roku_discovery_job(shared_pool.clone(), rx, integration.enabled);
-                let mut senders = control_senders.write().await;
-                senders.insert("roku".to_string(), tx);
-            }
-            "ring" => {
-                let (tx, rx) = mpsc::channel(10);
-                ring_job(
-                    shared_pool.clone(),
-                    ring_rest_client.clone(),
-                    rx,
-                    integration.enabled,
-                );
-                let mut senders = control_senders.write().await;
-                senders.insert("ring".to_string(), tx);
-            }
-            "tuya" => {
-                let (tx, rx) = mpsc::channel(10);
-                tuya_job(shared_pool.clone(), rx, integration.enabled);
-                let mut senders = control_senders.write().await;
-                senders.insert("tuya".to_string(), tx);
-            }
-            "eufy" => {
-                let (tx, rx) = mpsc::channel(10);
-                eufy_job(shared_pool.clone(), rx, integration.enabled);
-                let mut senders = control_senders.write().await;
-                senders.insert("eufy".to_string(), tx);
-            }
-            _ => {}
-        }
+        let job: Arc<dyn supervisor::Integration> = match integration.name.as_str() {
+            "tplink" => Arc::new(TplinkIntegration::new(
+                shared_pool.clone(),
+                event_bus.clone(),
+                secrets.clone(),
+            )),
+            "roku" => Arc::new(RokuIntegration::new(shared_pool.clone(), event_bus.clone())),
+            "ring" => Arc::new(RingIntegration::new(
+                shared_pool.clone(),
+                event_bus.clone(),
+                ring_rest_client.clone(),
+            )),
+            "tuya" => Arc::new(TuyaIntegration::new(
+                shared_pool.clone(),
+                event_bus.clone(),
+                secrets.clone(),
+            )),
+            "eufy" => Arc::new(EufyIntegration::new(secrets.clone())),
+            _ => continue,
+        };
+        supervisor
+            .spawn(job, integration.enabled, integration.settings)
+            .await;
     }
     Ok(())
 }
 
-// integrations register their own supported common capabilities
-// 'color', 'brightness', 'on_off'
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_mac() {
+        let expected = Some("50:C7:BF:0A:1B:2C".to_string());
+        assert_eq!(normalize_mac("50:C7:BF:0A:1B:2C"), expected);
+        assert_eq!(normalize_mac("50c7bf0a1b2c"), expected);
+        assert_eq!(normalize_mac("50-c7-bf-0a-1b-2c"), expected);
+        assert_eq!(normalize_mac(""), None);
+        assert_eq!(normalize_mac("00:00:00:00:00:00"), None);
+        assert_eq!(normalize_mac("50:C7:BF:0A:1B"), None);
+        assert_eq!(normalize_mac("50:C7:BF:0A:1B:ZZ"), None);
+    }
+
+    #[test]
+    fn test_is_device_online() {
+        let now = Utc::now();
+        let ten_minutes_ago = now - chrono::Duration::minutes(10);
+        assert!(is_device_online(ten_minutes_ago, Some(900), now));
+        assert!(!is_device_online(ten_minutes_ago, Some(300), now));
+        assert!(is_device_online(ten_minutes_ago, None, now));
+        assert!(is_device_online(now - chrono::Duration::days(7), None, now));
+    }
+}
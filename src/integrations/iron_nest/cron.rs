@@ -1,5 +1,10 @@
 use {
-    crate::{integrations::iron_nest::execute_function, server::actions::get_actions_query},
+    crate::{
+        integrations::iron_nest::{
+            driver::DriverRegistry, events::EventBus, execute_function, shutdown::Shutdown,
+        },
+        server::actions::get_actions_query,
+    },
     core::fmt,
     sqlx::PgPool,
     std::{
@@ -13,6 +18,9 @@ use {
 #[derive(Clone)]
 pub struct CronClient {
     job_scheduler: Arc<RwLock<JobScheduler>>,
+    driver_registry: DriverRegistry,
+    event_bus: EventBus,
+    shutdown: Shutdown,
 }
 
 impl Debug for CronClient {
@@ -22,32 +30,67 @@ impl Debug for CronClient {
 }
 
 impl CronClient {
-    pub async fn new() -> Self {
+    pub async fn new(
+        driver_registry: DriverRegistry,
+        event_bus: EventBus,
+        shutdown: Shutdown,
+    ) -> Self {
         Self {
             job_scheduler: Arc::new(RwLock::new(JobScheduler::new().await.unwrap())),
+            driver_registry,
+            event_bus,
+            shutdown,
         }
     }
 
+    /// Stops scheduling actions. Ones already running are tracked by
+    /// `Shutdown`.
+    pub async fn shutdown(&self) -> Result<(), anyhow::Error> {
+        self.job_scheduler.write().await.shutdown().await?;
+        Ok(())
+    }
+
     pub async fn schedule_tasks(&self, pool: &PgPool) -> Result<(), anyhow::Error> {
         let actions = get_actions_query(pool).await?;
 
         let mut job_scheduler = self.job_scheduler.write().await;
         job_scheduler.shutdown().await?;
+        if self.shutdown.is_requested() {
+            return Ok(());
+        }
 
         *job_scheduler = JobScheduler::new().await?;
 
         for action in actions {
             println!("scheduling action: {}", action.fields.cron);
+            let pool = pool.clone();
+            let driver_registry = self.driver_registry.clone();
+            let event_bus = self.event_bus.clone();
+            let shutdown = self.shutdown.clone();
             job_scheduler
                 .add(Job::new_async(
                     action.fields.cron.as_ref(),
                     move |_uuid, mut _l| {
+                        let pool = pool.clone();
+                        let driver_registry = driver_registry.clone();
+                        let event_bus = event_bus.clone();
                         let function_name = action.fields.function_name.clone();
                         let function_args = action.fields.function_args.clone();
-                        Box::pin(async move {
+                        shutdown.spawn(format!("action {function_name}"), async move {
                             println!("Calling {function_name}({function_args})");
-                            execute_function(function_name, function_args).await;
-                        })
+                            if let Err(e) = execute_function(
+                                &pool,
+                                &driver_registry,
+                                &event_bus,
+                                function_name.clone(),
+                                function_args,
+                            )
+                            .await
+                            {
+                                log::error!("Action {function_name} failed: {e}");
+                            }
+                        });
+                        Box::pin(async {})
                     },
                 )?)
                 .await?;
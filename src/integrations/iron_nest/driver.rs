@@ -0,0 +1,236 @@
+use {
+    super::types::{Capability, Device, DeviceCommand, DeviceState, DeviceType},
+    crate::integrations::{
+        ring::RingDoorbellDriver,
+        roku::RokuTvDriver,
+        stoplight::StoplightDriver,
+        tplink::{KasaDimmerDriver, KasaLightDriver, KasaPlugDriver, KasaPowerStripDriver},
+        tuya::TuyaLightDriver,
+    },
+    futures::future::{self, BoxFuture, FutureExt},
+    std::{collections::HashMap, fmt, sync::Arc, time::Duration},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum DriverError {
+    #[error("{device_type} does not support {capability:?}")]
+    Unsupported {
+        device_type: DeviceType,
+        capability: Capability,
+    },
+
+    #[error("No driver registered for {0}")]
+    NoDriver(DeviceType),
+
+    #[error("Device not found: {0}")]
+    DeviceNotFound(String),
+
+    #[error("Scene not found: {0}")]
+    SceneNotFound(String),
+
+    #[error("Unknown function: {0}")]
+    UnknownFunction(String),
+
+    #[error("Invalid arguments: {0}")]
+    InvalidArgs(String),
+
+    #[error("Device error: {0}")]
+    Device(String),
+
+    #[error("Database error: {0}")]
+    Sql(#[from] sqlx::Error),
+}
+
+pub type DriverFuture<'a> = BoxFuture<'a, Result<(), DriverError>>;
+pub type StateFuture<'a> = BoxFuture<'a, Result<Option<DeviceState>, DriverError>>;
+
+fn unsupported(device: &Device, capability: Capability) -> DriverFuture<'static> {
+    future::ready(Err(DriverError::Unsupported {
+        device_type: device.device_type,
+        capability,
+    }))
+    .boxed()
+}
+
+/// Implemented by every integration that can control devices. Each driver
+/// declares the capabilities it supports and only overrides those methods,
+/// the rest fall back to returning `DriverError::Unsupported`.
+pub trait DeviceDriver: Send + Sync {
+    fn capabilities(&self) -> &'static [Capability];
+
+    fn set_power<'a>(&'a self, device: &'a Device, _on: bool) -> DriverFuture<'a> {
+        unsupported(device, Capability::OnOff)
+    }
+
+    fn set_brightness<'a>(&'a self, device: &'a Device, _brightness: u8) -> DriverFuture<'a> {
+        unsupported(device, Capability::Brightness)
+    }
+
+    fn set_color<'a>(&'a self, device: &'a Device, _color: &'a str) -> DriverFuture<'a> {
+        unsupported(device, Capability::Color)
+    }
+
+    fn set_hsv<'a>(
+        &'a self,
+        device: &'a Device,
+        _hue: u16,
+        _saturation: u8,
+        _brightness: u8,
+    ) -> DriverFuture<'a> {
+        unsupported(device, Capability::Color)
+    }
+
+    fn set_color_temp<'a>(&'a self, device: &'a Device, _color_temp: u16) -> DriverFuture<'a> {
+        unsupported(device, Capability::ColorTemp)
+    }
+
+    fn apply_preset<'a>(&'a self, device: &'a Device, _index: u8) -> DriverFuture<'a> {
+        unsupported(device, Capability::Presets)
+    }
+
+    fn fade_to<'a>(
+        &'a self,
+        device: &'a Device,
+        _state: &'a DeviceState,
+        _transition_ms: u32,
+    ) -> DriverFuture<'a> {
+        unsupported(device, Capability::Transition)
+    }
+
+    fn keypress<'a>(&'a self, device: &'a Device, _key: &'a str) -> DriverFuture<'a> {
+        unsupported(device, Capability::Keypress)
+    }
+
+    fn launch_app<'a>(&'a self, device: &'a Device, _app_id: &'a str) -> DriverFuture<'a> {
+        unsupported(device, Capability::LaunchApp)
+    }
+
+    /// Reads the device's current state back from the device, or `None` if
+    /// the driver has no way of querying it.
+    fn get_state<'a>(&'a self, _device: &'a Device) -> StateFuture<'a> {
+        future::ready(Ok(None)).boxed()
+    }
+
+    /// How long a device may take to report the power state it was just
+    /// switched to before the state it does report is taken as final.
+    fn read_back_window(&self) -> Duration {
+        Duration::from_millis(1500)
+    }
+}
+
+#[derive(Clone)]
+pub struct DriverRegistry {
+    drivers: HashMap<DeviceType, Arc<dyn DeviceDriver>>,
+}
+
+impl fmt::Debug for DriverRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DriverRegistry")
+            .field("device_types", &self.drivers.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Default for DriverRegistry {
+    fn default() -> Self {
+        let mut registry = Self {
+            drivers: HashMap::new(),
+        };
+        registry.register(DeviceType::KasaPlug, KasaPlugDriver);
+        registry.register(DeviceType::KasaLight, KasaLightDriver);
+        registry.register(DeviceType::KasaDimmer, KasaDimmerDriver);
+        registry.register(DeviceType::KasaPowerStrip, KasaPowerStripDriver);
+        registry.register(DeviceType::TuyaLight, TuyaLightDriver);
+        registry.register(DeviceType::TuyaGrowLight, TuyaLightDriver);
+        registry.register(DeviceType::RingDoorbell, RingDoorbellDriver);
+        registry.register(DeviceType::RokuTv, RokuTvDriver);
+        registry.register(DeviceType::Stoplight, StoplightDriver);
+        registry
+    }
+}
+
+impl DriverRegistry {
+    pub fn register(&mut self, device_type: DeviceType, driver: impl DeviceDriver + 'static) {
+        self.drivers.insert(device_type, Arc::new(driver));
+    }
+
+    pub fn get(&self, device_type: DeviceType) -> Option<&Arc<dyn DeviceDriver>> {
+        self.drivers.get(&device_type)
+    }
+
+    pub fn capabilities(&self, device_type: DeviceType) -> &'static [Capability] {
+        self.get(device_type)
+            .map(|driver| driver.capabilities())
+            .unwrap_or_default()
+    }
+
+    pub fn supports(&self, device_type: DeviceType, capability: Capability) -> bool {
+        self.capabilities(device_type).contains(&capability)
+    }
+
+    pub async fn execute(
+        &self,
+        device: &Device,
+        command: DeviceCommand,
+    ) -> Result<(), DriverError> {
+        let driver = self
+            .get(device.device_type)
+            .ok_or(DriverError::NoDriver(device.device_type))?;
+
+        let capability = command.capability();
+        if !driver.capabilities().contains(&capability) {
+            return Err(DriverError::Unsupported {
+                device_type: device.device_type,
+                capability,
+            });
+        }
+
+        match command {
+            DeviceCommand::SetPower { on } => driver.set_power(device, on).await,
+            DeviceCommand::SetBrightness { brightness } => {
+                driver.set_brightness(device, brightness).await
+            }
+            DeviceCommand::SetColor { color } => driver.set_color(device, &color).await,
+            DeviceCommand::SetHsv {
+                hue,
+                saturation,
+                brightness,
+            } => driver.set_hsv(device, hue, saturation, brightness).await,
+            DeviceCommand::SetColorTemp { color_temp } => {
+                driver.set_color_temp(device, color_temp).await
+            }
+            DeviceCommand::ApplyPreset { index } => driver.apply_preset(device, index).await,
+            DeviceCommand::FadeTo {
+                state,
+                transition_ms,
+            } => driver.fade_to(device, &state, transition_ms).await,
+            DeviceCommand::Keypress { key } => driver.keypress(device, &key).await,
+            DeviceCommand::LaunchApp { app_id } => driver.launch_app(device, &app_id).await,
+        }
+    }
+
+    pub async fn read_state(&self, device: &Device) -> Result<Option<DeviceState>, DriverError> {
+        self.get(device.device_type)
+            .ok_or(DriverError::NoDriver(device.device_type))?
+            .get_state(device)
+            .await
+    }
+
+    pub fn read_back_window(&self, device_type: DeviceType) -> Duration {
+        self.get(device_type)
+            .map(|driver| driver.read_back_window())
+            .unwrap_or_default()
+    }
+
+    /// Like `read_state`, but falls back to the last power state stored for
+    /// devices the driver can't query.
+    pub async fn get_state(&self, device: &Device) -> Result<DeviceState, DriverError> {
+        Ok(self
+            .read_state(device)
+            .await?
+            .unwrap_or_else(|| DeviceState {
+                on: device.power_state != 0,
+                ..Default::default()
+            }))
+    }
+}
@@ -0,0 +1,216 @@
+use {
+    super::{
+        mish::MishStateModification,
+        types::{Device, DeviceState},
+    },
+    crate::integrations::roku::types::NowPlaying,
+    serde::Serialize,
+    serde_json::Value,
+    std::{
+        fmt::Display,
+        sync::{Arc, Mutex},
+    },
+    tokio::sync::{broadcast, mpsc},
+};
+
+/// How many events a subscriber can fall behind before it starts missing
+/// them.
+const EVENT_BUS_CAPACITY: usize = 1024;
+
+/// Serializes to the variant's fields, which is what the audit log stores.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum Event {
+    /// A discovery job stored a device that wasn't in the database before.
+    DeviceDiscovered {
+        device: Device,
+    },
+    /// A command changed a device's state, or a discovery job found it in a
+    /// different power state than the one stored.
+    DeviceStateChanged {
+        device_id: i64,
+        state: DeviceState,
+    },
+    /// A device hasn't been seen for longer than its integration's
+    /// `offline_after_secs`.
+    DeviceWentOffline {
+        device_id: i64,
+    },
+    /// An offline device was seen again.
+    DeviceCameOnline {
+        device_id: i64,
+    },
+    /// A Roku switched apps, started, paused or stopped playback, or started
+    /// something else. Playback position alone doesn't publish this.
+    NowPlayingChanged {
+        device_id: i64,
+        now_playing: NowPlaying,
+    },
+    /// A named function ran from an action, the assistant or a script.
+    /// `error` is set when it failed.
+    ActionExecuted {
+        function_name: String,
+        function_args: Value,
+        error: Option<String>,
+    },
+    IntegrationError {
+        integration: String,
+        error: String,
+    },
+    #[serde(skip)]
+    MishStateChanged(MishStateModification),
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::DeviceDiscovered { .. } => "DeviceDiscovered",
+            Event::DeviceStateChanged { .. } => "DeviceStateChanged",
+            Event::DeviceWentOffline { .. } => "DeviceWentOffline",
+            Event::DeviceCameOnline { .. } => "DeviceCameOnline",
+            Event::NowPlayingChanged { .. } => "NowPlayingChanged",
+            Event::ActionExecuted { .. } => "ActionExecuted",
+            Event::IntegrationError { .. } => "IntegrationError",
+            Event::MishStateChanged(_) => "MishStateChanged",
+        }
+    }
+
+    /// The device the event is about, if any.
+    pub fn device_id(&self) -> Option<i64> {
+        match self {
+            Event::DeviceDiscovered { device } => Some(device.id),
+            Event::DeviceStateChanged { device_id, .. }
+            | Event::DeviceWentOffline { device_id }
+            | Event::DeviceCameOnline { device_id }
+            | Event::NowPlayingChanged { device_id, .. } => Some(*device_id),
+            Event::ActionExecuted { .. }
+            | Event::IntegrationError { .. }
+            | Event::MishStateChanged(_) => None,
+        }
+    }
+}
+
+/// Broadcasts events from jobs, commands and scripts to any number of
+/// subscribers. Every subscriber gets its own copy of each event published
+/// after it subscribed.
+///
+/// A subscriber that falls behind misses events, so mish state changes are
+/// also queued on a separate unbounded channel for the script runner, which
+/// must see every one of them.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+    mish_sender: mpsc::UnboundedSender<MishStateModification>,
+    mish_receiver: Arc<Mutex<Option<mpsc::UnboundedReceiver<MishStateModification>>>>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUS_CAPACITY);
+        let (mish_sender, mish_receiver) = mpsc::unbounded_channel();
+        Self {
+            sender,
+            mish_sender,
+            mish_receiver: Arc::new(Mutex::new(Some(mish_receiver))),
+        }
+    }
+}
+
+impl EventBus {
+    pub fn publish(&self, event: Event) {
+        log::debug!("Publishing {event:?}");
+        if let Event::MishStateChanged(modification) = &event {
+            // Only fails once the script runner has stopped
+            let _ = self.mish_sender.send(modification.clone());
+        }
+        // Having nobody subscribed isn't an error
+        let _ = self.sender.send(event);
+    }
+
+    /// Every mish state change published from now on, without loss. There's
+    /// only one script runner, so this returns `None` when called again.
+    pub fn take_mish_state_changes(
+        &self,
+    ) -> Option<mpsc::UnboundedReceiver<MishStateModification>> {
+        self.mish_receiver.lock().unwrap().take()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+
+    /// Logs an error from a background job and publishes it as an
+    /// `IntegrationError`.
+    pub fn integration_error(&self, integration: &str, error: impl Display) {
+        log::error!("{integration}: {error}");
+        self.publish(Event::IntegrationError {
+            integration: integration.to_string(),
+            error: error.to_string(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscribers_each_receive_every_event() {
+        let event_bus = EventBus::default();
+        let mut automations = event_bus.subscribe();
+        let mut audit_log = event_bus.subscribe();
+
+        event_bus.publish(Event::DeviceStateChanged {
+            device_id: 7,
+            state: DeviceState {
+                on: true,
+                ..Default::default()
+            },
+        });
+        event_bus.publish(Event::DeviceWentOffline { device_id: 8 });
+
+        for events in [&mut automations, &mut audit_log] {
+            match events.try_recv() {
+                Ok(Event::DeviceStateChanged { device_id, state }) => {
+                    assert_eq!(device_id, 7);
+                    assert!(state.on);
+                }
+                other => panic!("Unexpected {other:?}"),
+            }
+            assert!(matches!(
+                events.try_recv(),
+                Ok(Event::DeviceWentOffline { device_id: 8 })
+            ));
+            assert!(matches!(
+                events.try_recv(),
+                Err(broadcast::error::TryRecvError::Empty)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_mish_state_changes_are_lossless() {
+        let event_bus = EventBus::default();
+        let mut events = event_bus.subscribe();
+        let mut mish_state_changes = event_bus.take_mish_state_changes().unwrap();
+        assert!(event_bus.take_mish_state_changes().is_none());
+
+        let published = EVENT_BUS_CAPACITY + 10;
+        for i in 0..published {
+            event_bus.publish(Event::MishStateChanged(MishStateModification::Delete {
+                name: i.to_string(),
+            }));
+        }
+
+        assert!(matches!(
+            events.try_recv(),
+            Err(broadcast::error::TryRecvError::Lagged(_))
+        ));
+        for i in 0..published {
+            match mish_state_changes.try_recv() {
+                Ok(MishStateModification::Delete { name }) => assert_eq!(name, i.to_string()),
+                other => panic!("Unexpected {other:?}"),
+            }
+        }
+    }
+}
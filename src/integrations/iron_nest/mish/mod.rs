@@ -3,7 +3,9 @@ use {
         components::mish::{
             ipld_blob_page::get_ipld_blob_query, mish_state_page::get_mish_state_query,
         },
-        integrations::tplink::{tplink_turn_plug_off, tplink_turn_plug_on},
+        integrations::iron_nest::{
+            driver::DriverRegistry, events::EventBus, execute_function, shutdown::Shutdown,
+        },
         mish_api::{UpdateMishStateBody, update_mish_state},
     },
     cid::Cid,
@@ -11,15 +13,13 @@ use {
     rhai::Dynamic,
     serde::{Deserialize, Serialize},
     serde_ipld_dagjson::codec::DagJsonCodec,
+    serde_json::json,
     std::{
         collections::HashMap,
         str::FromStr,
         time::{SystemTime, UNIX_EPOCH},
     },
-    tokio::{
-        sync::mpsc::{UnboundedReceiver, UnboundedSender},
-        time::{Duration, Instant},
-    },
+    tokio::time::{Duration, Instant},
     tokio_cron_scheduler::{Job, JobScheduler},
 };
 
@@ -34,18 +34,17 @@ pub enum MishStateModification {
     },
 }
 
-pub fn create_mish_state_modification_bus() -> (
-    UnboundedSender<MishStateModification>,
-    UnboundedReceiver<MishStateModification>,
-) {
-    tokio::sync::mpsc::unbounded_channel()
-}
-
 pub async fn register_native_queries(
     pool: &sqlx::PgPool,
-    mut mish_state_modification_bus_receiver: UnboundedReceiver<MishStateModification>,
-    mish_state_modification_bus_sender: UnboundedSender<MishStateModification>,
+    driver_registry: &DriverRegistry,
+    event_bus: EventBus,
+    shutdown: Shutdown,
 ) {
+    let mut mish_state_changes = event_bus
+        .take_mish_state_changes()
+        .expect("mish state changes are already being consumed");
+    let shutdown_requested = shutdown.requested();
+    tokio::pin!(shutdown_requested);
     let mut lookup = HashMap::new();
     let mut job_scheduler = JobScheduler::new().await.unwrap();
 
@@ -53,7 +52,9 @@ pub async fn register_native_queries(
     if let Some(state) = state {
         do_install(
             pool,
-            mish_state_modification_bus_sender.clone(),
+            driver_registry,
+            event_bus.clone(),
+            &shutdown,
             &mut lookup,
             &mut job_scheduler,
             state.state.clone(),
@@ -61,14 +62,23 @@ pub async fn register_native_queries(
         .await;
     }
 
-    while let Some(mish_state_modification) = mish_state_modification_bus_receiver.recv().await {
+    loop {
+        let mish_state_modification = tokio::select! {
+            mish_state_modification = mish_state_changes.recv() => match mish_state_modification {
+                Some(mish_state_modification) => mish_state_modification,
+                None => break,
+            },
+            _ = &mut shutdown_requested => break,
+        };
         log::info!("Mish state modification: {:?}", mish_state_modification);
         match mish_state_modification {
             MishStateModification::CreateOrUpdate { name, state } => match name.as_str() {
                 "run" => {
                     do_install(
                         pool,
-                        mish_state_modification_bus_sender.clone(),
+                        driver_registry,
+                        event_bus.clone(),
+                        &shutdown,
                         &mut lookup,
                         &mut job_scheduler,
                         state,
@@ -99,7 +109,10 @@ pub async fn register_native_queries(
                                 };
                                 run_mish_state_at_most_once_rhai(
                                     pool.clone(),
-                                    mish_state_modification_bus_sender.clone(),
+                                    driver_registry.clone(),
+                                    event_bus.clone(),
+                                    shutdown.clone(),
+                                    name.to_owned(),
                                     rhai,
                                     scope,
                                 )
@@ -116,6 +129,11 @@ pub async fn register_native_queries(
             MishStateModification::Delete { name: _ } => {}
         }
     }
+
+    // Scripts already running are tracked by `Shutdown`
+    if let Err(e) = job_scheduler.shutdown().await {
+        log::error!("Failed to shut down the mish scheduler: {e}");
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -135,7 +153,9 @@ enum InstallItem {
 
 async fn do_install(
     pool: &sqlx::PgPool,
-    mish_state_modification_bus_sender: UnboundedSender<MishStateModification>,
+    driver_registry: &DriverRegistry,
+    event_bus: EventBus,
+    shutdown: &Shutdown,
     lookup: &mut HashMap<String, InstallItem>,
     job_scheduler: &mut JobScheduler,
     state: serde_json::Value,
@@ -180,7 +200,10 @@ async fn do_install(
                                 };
                                 run_mish_state_at_most_once_rhai(
                                     pool.clone(),
-                                    mish_state_modification_bus_sender.clone(),
+                                    driver_registry.clone(),
+                                    event_bus.clone(),
+                                    shutdown.clone(),
+                                    name.clone(),
                                     rhai.clone(),
                                     scope,
                                 )
@@ -190,21 +213,27 @@ async fn do_install(
                     }
                     InstallItem::CronAtMostOnceRhai { cron_string, rhai } => {
                         let pool = pool.clone();
-                        let mish_state_modification_bus_sender =
-                            mish_state_modification_bus_sender.clone();
+                        let driver_registry = driver_registry.clone();
+                        let event_bus = event_bus.clone();
+                        let shutdown = shutdown.clone();
                         let rhai = rhai.clone();
                         job_scheduler
                             .add(
                                 Job::new_async(cron_string.as_ref(), move |_uuid, mut _l| {
                                     let pool = pool.clone();
-                                    let mish_state_modification_bus_sender =
-                                        mish_state_modification_bus_sender.clone();
+                                    let driver_registry = driver_registry.clone();
+                                    let event_bus = event_bus.clone();
+                                    let shutdown = shutdown.clone();
+                                    let name = name.clone();
                                     let rhai = rhai.clone();
                                     Box::pin(async move {
                                         let scope = rhai::Scope::new();
                                         run_mish_state_at_most_once_rhai(
                                             pool,
-                                            mish_state_modification_bus_sender,
+                                            driver_registry,
+                                            event_bus,
+                                            shutdown,
+                                            name,
                                             rhai,
                                             scope,
                                         )
@@ -227,7 +256,10 @@ async fn do_install(
 
 async fn run_mish_state_at_most_once_rhai(
     pool: sqlx::PgPool,
-    mish_state_modification_bus_sender: UnboundedSender<MishStateModification>,
+    driver_registry: DriverRegistry,
+    event_bus: EventBus,
+    shutdown: Shutdown,
+    script_name: String,
     rhai: serde_json::Value,
     scope: rhai::Scope<'static>,
 ) {
@@ -261,17 +293,168 @@ async fn run_mish_state_at_most_once_rhai(
             return;
         }
     };
-    tokio::task::spawn_blocking(move || {
+    let device_command = {
+        let pool = pool.clone();
+        let event_bus = event_bus.clone();
+        let shutdown = shutdown.clone();
+        let script_name = script_name.clone();
+        move |function_name: &'static str, function_args: serde_json::Value| {
+            let pool = pool.clone();
+            let driver_registry = driver_registry.clone();
+            let event_bus = event_bus.clone();
+            let label = format!("{function_name} from {script_name}");
+            shutdown.spawn(label, async move {
+                if let Err(e) = execute_function(
+                    &pool,
+                    &driver_registry,
+                    &event_bus,
+                    function_name.to_owned(),
+                    function_args,
+                )
+                .await
+                {
+                    log::error!("Failed to run {function_name} from script: {e}");
+                }
+            });
+        }
+    };
+    let spawner = shutdown.clone();
+    spawner.spawn_blocking(format!("script {script_name}"), move || {
         let start = Instant::now();
         let mut scope = scope;
-        let result = rhai::Engine::new()
-            .on_progress(move |_| {
-                if start.elapsed() > Duration::from_secs(10) {
-                    // Return a dummy token just to force-terminate the script
-                    Some(Dynamic::UNIT)
-                } else {
-                    // Continue
-                    None
+        let mut engine = rhai::Engine::new();
+        {
+            let device_command = device_command.clone();
+            engine.register_fn("tplink_turn_plug_on", move |ip: String| {
+                device_command("tplink_turn_plug_on", json!({ "ip": ip }));
+            });
+        }
+        {
+            let device_command = device_command.clone();
+            engine.register_fn("tplink_turn_plug_off", move |ip: String| {
+                device_command("tplink_turn_plug_off", json!({ "ip": ip }));
+            });
+        }
+        {
+            let device_command = device_command.clone();
+            engine.register_fn("tplink_turn_plug_on", move |device_id: i64| {
+                device_command("tplink_turn_plug_on", json!({ "device_id": device_id }));
+            });
+        }
+        {
+            let device_command = device_command.clone();
+            engine.register_fn("tplink_turn_plug_off", move |device_id: i64| {
+                device_command("tplink_turn_plug_off", json!({ "device_id": device_id }));
+            });
+        }
+        {
+            let device_command = device_command.clone();
+            engine.register_fn("set_power", move |device_id: i64, on: bool| {
+                device_command(
+                    "device_command",
+                    json!({ "device_id": device_id, "command": "set_power", "on": on }),
+                );
+            });
+        }
+        {
+            let device_command = device_command.clone();
+            engine.register_fn("set_brightness", move |device_id: i64, brightness: i64| {
+                device_command(
+                    "device_command",
+                    json!({
+                        "device_id": device_id,
+                        "command": "set_brightness",
+                        "brightness": brightness,
+                    }),
+                );
+            });
+        }
+        {
+            let device_command = device_command.clone();
+            engine.register_fn("set_color", move |device_id: i64, color: String| {
+                device_command(
+                    "device_command",
+                    json!({ "device_id": device_id, "command": "set_color", "color": color }),
+                );
+            });
+        }
+        {
+            let device_command = device_command.clone();
+            engine.register_fn("keypress", move |device_id: i64, key: String| {
+                device_command(
+                    "device_command",
+                    json!({ "device_id": device_id, "command": "keypress", "key": key }),
+                );
+            });
+        }
+        {
+            let device_command = device_command.clone();
+            engine.register_fn("launch_app", move |device_id: i64, app_id: String| {
+                device_command(
+                    "device_command",
+                    json!({ "device_id": device_id, "command": "launch_app", "app_id": app_id }),
+                );
+            });
+        }
+        {
+            let device_command = device_command.clone();
+            engine.register_fn("activate_scene", move |name: String| {
+                device_command("activate_scene", json!({ "name": name }));
+            });
+        }
+        for group in ["room", "zone", "tag"] {
+            {
+                let device_command = device_command.clone();
+                engine.register_fn(
+                    format!("{group}_set_power"),
+                    move |name: String, on: bool| {
+                        device_command(
+                            "group_command",
+                            json!({ group: name, "command": "set_power", "on": on }),
+                        );
+                    },
+                );
+            }
+            {
+                let device_command = device_command.clone();
+                engine.register_fn(
+                    format!("{group}_set_brightness"),
+                    move |name: String, brightness: i64| {
+                        device_command(
+                            "group_command",
+                            json!({
+                                group: name,
+                                "command": "set_brightness",
+                                "brightness": brightness,
+                            }),
+                        );
+                    },
+                );
+            }
+            {
+                let device_command = device_command.clone();
+                engine.register_fn(
+                    format!("{group}_set_color"),
+                    move |name: String, color: String| {
+                        device_command(
+                            "group_command",
+                            json!({ group: name, "command": "set_color", "color": color }),
+                        );
+                    },
+                );
+            }
+        }
+        let result = engine
+            .on_progress({
+                let shutdown = shutdown.clone();
+                move |_| {
+                    if start.elapsed() > Duration::from_secs(10) || shutdown.is_requested() {
+                        // Return a dummy token just to force-terminate the script
+                        Some(Dynamic::UNIT)
+                    } else {
+                        // Continue
+                        None
+                    }
                 }
             })
             .register_fn("unix_timestamp", || {
@@ -280,27 +463,17 @@ async fn run_mish_state_at_most_once_rhai(
                     .unwrap()
                     .as_secs() as i64 // conversion to i64 needed or else modulos in the script will fail
             })
-            .register_fn("tplink_turn_plug_on", |ip: String| {
-                tokio::task::spawn(async move {
-                    tplink_turn_plug_on(&ip).await;
-                });
-            })
-            .register_fn("tplink_turn_plug_off", |ip: String| {
-                tokio::task::spawn(async move {
-                    tplink_turn_plug_off(&ip).await;
-                });
-            })
             .register_fn(
                 "update_mish_state",
                 move |name: String, path: String, content: Dynamic| {
                     let pool = pool.clone();
-                    let mish_state_modification_bus_sender =
-                        mish_state_modification_bus_sender.clone();
+                    let event_bus = event_bus.clone();
                     let content = serde_json::to_value(&content).unwrap();
-                    tokio::task::spawn(async move {
+                    let label = format!("update_mish_state {name} from {script_name}");
+                    shutdown.spawn(label, async move {
                         if let Err(e) = update_mish_state(
                             &pool,
-                            &mish_state_modification_bus_sender,
+                            &event_bus,
                             UpdateMishStateBody {
                                 mish_state_name: name,
                                 path,
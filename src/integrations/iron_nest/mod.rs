@@ -2,8 +2,14 @@ pub mod shared;
 pub mod types;
 
 cfg_if::cfg_if! { if #[cfg(feature = "ssr")] {
+  pub mod audit_log;
   pub mod client;
   pub use client::*;
   pub mod cron;
+  pub mod driver;
+  pub mod events;
   pub mod mish;
+  pub mod secrets;
+  pub mod shutdown;
+  pub mod supervisor;
 }}
@@ -0,0 +1,358 @@
+use {
+    super::types::{AuthState, IntegrationHealth, secret_fields},
+    aes_gcm::{
+        Aes256Gcm, Key, Nonce,
+        aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    },
+    base64::{Engine, engine::general_purpose::STANDARD as base64},
+    sqlx::PgPool,
+    std::{
+        fmt,
+        fs::{self, OpenOptions},
+        io::Write,
+        path::Path,
+        sync::Arc,
+    },
+};
+
+/// Base64 encoded 32 byte key. Takes precedence over the key file.
+pub const MASTER_KEY_ENV: &str = "IRON_NEST_MASTER_KEY";
+/// Where the key is read from, and generated on first start, when
+/// `IRON_NEST_MASTER_KEY` isn't set.
+pub const MASTER_KEY_FILE_ENV: &str = "IRON_NEST_MASTER_KEY_FILE";
+const DEFAULT_MASTER_KEY_FILE: &str = "data/master.key";
+
+/// Environment variables older versions read credentials from, with the
+/// integration and secret they're imported as.
+const ENV_CREDENTIALS: &[(&str, &str, &str)] = &[
+    ("TUYA_CLIENT_ID", "tuya", "client_id"),
+    ("TUYA_API_KEY", "tuya", "api_key"),
+    ("EUFY_USERNAME", "eufy", "username"),
+    ("EUFY_PASSWORD", "eufy", "password"),
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum SecretError {
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("Master key error: {0}")]
+    MasterKey(String),
+
+    #[error("Failed to decrypt {0}, was it stored with a different master key?")]
+    Decrypt(String),
+
+    #[error("{integration} has no {name} set, add it on the integrations page")]
+    Missing { integration: String, name: String },
+}
+
+/// Integration credentials, encrypted with AES-256-GCM under the master key
+/// before they're written to `integration_secret`. Values never leave the
+/// server; the UI only learns which names are set.
+#[derive(Clone)]
+pub struct SecretStore {
+    pool: PgPool,
+    cipher: Arc<Aes256Gcm>,
+}
+
+impl fmt::Debug for SecretStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretStore").finish_non_exhaustive()
+    }
+}
+
+/// Binds a ciphertext to the secret it was written for, so rows can't be
+/// swapped in the database.
+fn associated_data(integration: &str, name: &str) -> String {
+    format!("{integration}/{name}")
+}
+
+fn encrypt(cipher: &Aes256Gcm, aad: &str, value: &str) -> (Vec<u8>, Vec<u8>) {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: value.as_bytes(),
+                aad: aad.as_bytes(),
+            },
+        )
+        .expect("AES-GCM encryption of an in-memory buffer can't fail");
+    (nonce.to_vec(), ciphertext)
+}
+
+fn decrypt(
+    cipher: &Aes256Gcm,
+    aad: &str,
+    nonce: &[u8],
+    ciphertext: &[u8],
+) -> Result<String, SecretError> {
+    if nonce.len() != 12 {
+        return Err(SecretError::Decrypt(aad.to_string()));
+    }
+    let plaintext = cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: aad.as_bytes(),
+            },
+        )
+        .map_err(|_| SecretError::Decrypt(aad.to_string()))?;
+    String::from_utf8(plaintext).map_err(|_| SecretError::Decrypt(aad.to_string()))
+}
+
+fn parse_key(encoded: &str) -> Result<Key<Aes256Gcm>, SecretError> {
+    let bytes = base64
+        .decode(encoded.trim())
+        .map_err(|e| SecretError::MasterKey(format!("not base64: {e}")))?;
+    if bytes.len() != 32 {
+        return Err(SecretError::MasterKey(format!(
+            "expected 32 bytes, got {}",
+            bytes.len()
+        )));
+    }
+    Ok(*Key::<Aes256Gcm>::from_slice(&bytes))
+}
+
+/// Reads the key file, or creates it readable only by the owner with a new
+/// random key.
+fn load_or_create_key_file(path: &Path) -> Result<Key<Aes256Gcm>, SecretError> {
+    let key_error = |e: std::io::Error| SecretError::MasterKey(format!("{}: {e}", path.display()));
+    if path.exists() {
+        return parse_key(&fs::read_to_string(path).map_err(key_error)?);
+    }
+
+    log::warn!("No master key found, generating {}", path.display());
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(key_error)?;
+    }
+    let key = Aes256Gcm::generate_key(OsRng);
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(base64.encode(key).as_bytes()))
+        .map_err(key_error)?;
+    Ok(key)
+}
+
+impl SecretStore {
+    pub fn new(pool: PgPool, key: &Key<Aes256Gcm>) -> Self {
+        Self {
+            pool,
+            cipher: Arc::new(Aes256Gcm::new(key)),
+        }
+    }
+
+    /// Uses the key from `IRON_NEST_MASTER_KEY` or the key file.
+    pub fn load(pool: PgPool) -> Result<Self, SecretError> {
+        let key = match std::env::var(MASTER_KEY_ENV) {
+            Ok(encoded) => parse_key(&encoded)?,
+            Err(_) => {
+                let path = std::env::var(MASTER_KEY_FILE_ENV)
+                    .unwrap_or_else(|_| DEFAULT_MASTER_KEY_FILE.to_string());
+                load_or_create_key_file(Path::new(&path))?
+            }
+        };
+        Ok(Self::new(pool, &key))
+    }
+
+    pub async fn get(&self, integration: &str, name: &str) -> Result<Option<String>, SecretError> {
+        let query = "
+            SELECT nonce, ciphertext
+            FROM integration_secret
+            WHERE integration = $1 AND name = $2
+        ";
+        let row = sqlx::query_as::<_, (Vec<u8>, Vec<u8>)>(query)
+            .bind(integration)
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.map(|(nonce, ciphertext)| {
+            decrypt(
+                &self.cipher,
+                &associated_data(integration, name),
+                &nonce,
+                &ciphertext,
+            )
+        })
+        .transpose()
+    }
+
+    /// Like `get`, for credentials an integration can't work without.
+    pub async fn require(&self, integration: &str, name: &str) -> Result<String, SecretError> {
+        self.get(integration, name)
+            .await?
+            .filter(|value| !value.is_empty())
+            .ok_or_else(|| SecretError::Missing {
+                integration: integration.to_string(),
+                name: name.to_string(),
+            })
+    }
+
+    pub async fn set(&self, integration: &str, name: &str, value: &str) -> Result<(), SecretError> {
+        let (nonce, ciphertext) = encrypt(&self.cipher, &associated_data(integration, name), value);
+        let query = "
+            INSERT INTO integration_secret (integration, name, nonce, ciphertext, updated_at)
+            VALUES ($1, $2, $3, $4, NOW())
+            ON CONFLICT (integration, name) DO UPDATE SET
+                nonce = EXCLUDED.nonce,
+                ciphertext = EXCLUDED.ciphertext,
+                updated_at = EXCLUDED.updated_at
+        ";
+        sqlx::query(query)
+            .bind(integration)
+            .bind(name)
+            .bind(nonce)
+            .bind(ciphertext)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete(&self, integration: &str, name: &str) -> Result<(), SecretError> {
+        sqlx::query("DELETE FROM integration_secret WHERE integration = $1 AND name = $2")
+            .bind(integration)
+            .bind(name)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Names of the secrets stored for `integration`, without their values.
+    pub async fn names(&self, integration: &str) -> Result<Vec<String>, SecretError> {
+        let query = "
+            SELECT name
+            FROM integration_secret
+            WHERE integration = $1
+            ORDER BY name
+        ";
+        Ok(sqlx::query_scalar(query)
+            .bind(integration)
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    /// The tokens an integration got from logging in. Empty when it hasn't.
+    pub async fn get_auth(&self, integration: &str) -> Result<AuthState, SecretError> {
+        Ok(AuthState {
+            auth_token: self
+                .get(integration, "auth_token")
+                .await?
+                .unwrap_or_default(),
+            refresh_token: self
+                .get(integration, "refresh_token")
+                .await?
+                .unwrap_or_default(),
+            hardware_id: self
+                .get(integration, "hardware_id")
+                .await?
+                .unwrap_or_default(),
+        })
+    }
+
+    pub async fn set_auth(&self, integration: &str, state: &AuthState) -> Result<(), SecretError> {
+        self.set(integration, "auth_token", &state.auth_token)
+            .await?;
+        self.set(integration, "refresh_token", &state.refresh_token)
+            .await?;
+        self.set(integration, "hardware_id", &state.hardware_id)
+            .await
+    }
+
+    /// Unhealthy when a credential from `secret_fields` is missing or the
+    /// integration has no auth token.
+    pub async fn auth_health(&self, integration: &str) -> IntegrationHealth {
+        for field in secret_fields(integration) {
+            match self.require(integration, field.name).await {
+                Ok(_) => {}
+                Err(SecretError::Missing { .. }) => {
+                    return IntegrationHealth::Unhealthy(format!("{} not set", field.label));
+                }
+                Err(e) => return IntegrationHealth::Unhealthy(e.to_string()),
+            }
+        }
+        match self.get(integration, "auth_token").await {
+            Ok(Some(token)) if !token.is_empty() => IntegrationHealth::Healthy,
+            Ok(_) => IntegrationHealth::Unhealthy("Not logged in".to_string()),
+            Err(e) => IntegrationHealth::Unhealthy(e.to_string()),
+        }
+    }
+
+    /// Moves tokens stored in plaintext in the `auth` table by older versions
+    /// into the store.
+    pub async fn import_plaintext_auth(&self) -> Result<(), SecretError> {
+        let query = "
+            SELECT name, COALESCE(hardware_id, ''), COALESCE(auth_token, ''), COALESCE(refresh_token, '')
+            FROM auth
+        ";
+        let rows = sqlx::query_as::<_, (String, String, String, String)>(query)
+            .fetch_all(&self.pool)
+            .await?;
+        for (name, hardware_id, auth_token, refresh_token) in rows {
+            log::info!("Encrypting stored {name} tokens");
+            let state = AuthState {
+                refresh_token,
+                hardware_id,
+                auth_token,
+            };
+            self.set_auth(&name, &state).await?;
+            sqlx::query("DELETE FROM auth WHERE name = $1")
+                .bind(&name)
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Imports credentials older versions read from the environment, unless
+    /// the store already has a value for them, e.g. one entered in the UI.
+    pub async fn import_env_credentials(&self) -> Result<(), SecretError> {
+        for (var, integration, name) in ENV_CREDENTIALS {
+            let Ok(value) = std::env::var(var) else {
+                continue;
+            };
+            if value.is_empty() || self.get(integration, name).await?.is_some() {
+                continue;
+            }
+            log::info!("Importing {var} as the {integration} {name}");
+            self.set(integration, name, &value).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let cipher = Aes256Gcm::new(&Aes256Gcm::generate_key(OsRng));
+        let (nonce, ciphertext) = encrypt(&cipher, "tuya/api_key", "hunter2");
+        assert_ne!(ciphertext, b"hunter2");
+        assert_eq!(
+            decrypt(&cipher, "tuya/api_key", &nonce, &ciphertext).unwrap(),
+            "hunter2"
+        );
+
+        // A row moved to another secret, or read with another key, fails
+        assert!(decrypt(&cipher, "eufy/password", &nonce, &ciphertext).is_err());
+        let other_cipher = Aes256Gcm::new(&Aes256Gcm::generate_key(OsRng));
+        assert!(decrypt(&other_cipher, "tuya/api_key", &nonce, &ciphertext).is_err());
+    }
+
+    #[test]
+    fn test_parse_key() {
+        let key = Aes256Gcm::generate_key(OsRng);
+        assert_eq!(parse_key(&base64.encode(key)).unwrap(), key);
+        assert!(parse_key("not base64!").is_err());
+        assert!(parse_key(&base64.encode([0u8; 16])).is_err());
+    }
+}
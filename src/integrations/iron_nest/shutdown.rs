@@ -0,0 +1,186 @@
+use {
+    std::{
+        collections::HashMap,
+        future::Future,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicU64, Ordering},
+        },
+    },
+    tokio::{
+        sync::watch,
+        time::{Instant, timeout_at},
+    },
+};
+
+/// Coordinates stopping the server. Background loops wait on `requested`
+/// and work that shouldn't be dropped halfway, like a command sent to a
+/// device or a running script, is registered with `spawn` or
+/// `spawn_blocking` so `drain` can wait for it.
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    requested: watch::Sender<bool>,
+    in_flight: Arc<Mutex<HashMap<u64, String>>>,
+    next_id: Arc<AtomicU64>,
+    finished: watch::Sender<()>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self {
+            requested: watch::channel(false).0,
+            in_flight: Arc::default(),
+            next_id: Arc::default(),
+            finished: watch::channel(()).0,
+        }
+    }
+}
+
+/// Removes a task from the in-flight set when it finishes or panics.
+struct InFlightGuard {
+    shutdown: Shutdown,
+    id: u64,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.shutdown.in_flight.lock().unwrap().remove(&self.id);
+        self.shutdown.finished.send_replace(());
+    }
+}
+
+impl Shutdown {
+    /// Tells everything waiting on `requested` to stop. Safe to call more
+    /// than once.
+    pub fn begin(&self) {
+        self.requested.send_replace(true);
+    }
+
+    pub fn is_requested(&self) -> bool {
+        *self.requested.borrow()
+    }
+
+    /// Resolves once `begin` has been called.
+    pub fn requested(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut requested = self.requested.subscribe();
+        async move {
+            // The sender lives in self, so this only errors if every clone
+            // was dropped, which also means nothing can begin a shutdown
+            let _ = requested.wait_for(|requested| *requested).await;
+        }
+    }
+
+    fn track(&self, label: String) -> InFlightGuard {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.in_flight.lock().unwrap().insert(id, label);
+        InFlightGuard {
+            shutdown: self.clone(),
+            id,
+        }
+    }
+
+    /// Like `tokio::task::spawn`, but `drain` waits for the task. `label`
+    /// is logged if it's still running when the drain times out.
+    pub fn spawn<F>(&self, label: impl Into<String>, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let guard = self.track(label.into());
+        tokio::task::spawn(async move {
+            future.await;
+            drop(guard);
+        });
+    }
+
+    /// Like `tokio::task::spawn_blocking`, but `drain` waits for the task.
+    pub fn spawn_blocking<F>(&self, label: impl Into<String>, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let guard = self.track(label.into());
+        tokio::task::spawn_blocking(move || {
+            f();
+            drop(guard);
+        });
+    }
+
+    /// Waits until no tracked tasks are running or `deadline` passes.
+    /// Returns the labels of the tasks that were still running.
+    pub async fn drain(&self, deadline: Instant) -> Vec<String> {
+        let mut finished = self.finished.subscribe();
+        let wait = async {
+            while !self.in_flight.lock().unwrap().is_empty() {
+                if finished.changed().await.is_err() {
+                    break;
+                }
+            }
+        };
+        let _ = timeout_at(deadline, wait).await;
+        let mut abandoned = self
+            .in_flight
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        abandoned.sort();
+        abandoned
+    }
+}
+
+/// Resolves on Ctrl-C, or SIGTERM from `docker stop`.
+pub async fn signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            log::error!("Failed to listen for Ctrl-C: {e}");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                log::error!("Failed to listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => log::info!("Received Ctrl-C"),
+        _ = terminate => log::info!("Received SIGTERM"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::time::Duration, tokio::sync::oneshot};
+
+    #[tokio::test]
+    async fn test_drain() {
+        let shutdown = Shutdown::default();
+        let (release, released) = oneshot::channel::<()>();
+        shutdown.spawn("quick", async {});
+        shutdown.spawn("stuck", async {
+            let _ = released.await;
+        });
+
+        let abandoned = shutdown
+            .drain(Instant::now() + Duration::from_millis(50))
+            .await;
+        assert_eq!(abandoned, vec!["stuck".to_string()]);
+
+        release.send(()).unwrap();
+        let abandoned = shutdown
+            .drain(Instant::now() + Duration::from_secs(5))
+            .await;
+        assert!(abandoned.is_empty());
+    }
+}
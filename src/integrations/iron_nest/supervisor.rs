@@ -0,0 +1,409 @@
+use {
+    super::{
+        events::EventBus,
+        types::{
+            ControlMessage, IntegrationHealth, IntegrationSettings, IntegrationStatus, PollReply,
+        },
+    },
+    chrono::Utc,
+    futures::future::{self, BoxFuture, FutureExt},
+    std::{collections::HashMap, sync::Arc, time::Duration},
+    tokio::{
+        sync::{
+            RwLock,
+            mpsc::{self, Receiver, Sender},
+            oneshot,
+        },
+        task::JoinHandle,
+        time::{Instant, timeout_at},
+    },
+};
+
+pub type IntegrationFuture<'a> = BoxFuture<'a, anyhow::Result<()>>;
+pub type PollFuture<'a> = BoxFuture<'a, anyhow::Result<usize>>;
+pub type HealthFuture<'a> = BoxFuture<'a, IntegrationHealth>;
+
+/// Implemented by every integration with a background job. The supervisor
+/// owns the loop: it calls `start` when the integration is enabled, `poll`
+/// every `poll_interval_secs` of its settings while it's running and `stop`
+/// when it's disabled. `health` is checked after every poll.
+pub trait Integration: Send + Sync {
+    /// Matches the `name` of the integration's row in the `integration` table.
+    fn name(&self) -> &'static str;
+
+    /// Called with the stored settings before the first poll and again
+    /// whenever they're edited.
+    fn configure(&self, _settings: &IntegrationSettings) {}
+
+    fn start(&self) -> IntegrationFuture<'_> {
+        future::ready(Ok(())).boxed()
+    }
+
+    fn stop(&self) -> IntegrationFuture<'_> {
+        future::ready(Ok(())).boxed()
+    }
+
+    /// Returns the number of devices found or updated.
+    fn poll(&self) -> PollFuture<'_>;
+
+    /// Polls because the user asked for it. Integrations that spread their
+    /// work over several polls should do all of it here.
+    fn refresh(&self) -> PollFuture<'_> {
+        self.poll()
+    }
+
+    fn health(&self) -> HealthFuture<'_> {
+        future::ready(IntegrationHealth::Healthy).boxed()
+    }
+}
+
+/// Tracks work an integration does less often than it polls, like refreshing
+/// an auth token.
+#[derive(Debug)]
+pub struct Every {
+    period: std::sync::Mutex<Duration>,
+    last: std::sync::Mutex<Option<Instant>>,
+}
+
+impl Every {
+    pub fn new(period: Duration) -> Self {
+        Self {
+            period: std::sync::Mutex::new(period),
+            last: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// Whether the work should run now. Returns true the first time and then
+    /// once every `period`.
+    pub fn due(&self) -> bool {
+        let mut last = self.last.lock().unwrap();
+        let now = Instant::now();
+        match *last {
+            Some(last) if now - last < *self.period.lock().unwrap() => false,
+            _ => {
+                *last = Some(now);
+                true
+            }
+        }
+    }
+
+    pub fn set_period(&self, period: Duration) {
+        *self.period.lock().unwrap() = period;
+    }
+
+    /// Makes the work due on the next check.
+    pub fn reset(&self) {
+        *self.last.lock().unwrap() = None;
+    }
+}
+
+/// Runs the poll loop of every integration and keeps track of how each one
+/// is doing. Loops are controlled through `send`.
+#[derive(Debug, Clone)]
+pub struct Supervisor {
+    control_senders: Arc<RwLock<HashMap<String, Sender<ControlMessage>>>>,
+    statuses: Arc<RwLock<HashMap<String, IntegrationStatus>>>,
+    loops: Arc<RwLock<HashMap<String, JoinHandle<()>>>>,
+    event_bus: EventBus,
+}
+
+impl Supervisor {
+    pub fn new(event_bus: EventBus) -> Self {
+        Self {
+            control_senders: Arc::default(),
+            statuses: Arc::default(),
+            loops: Arc::default(),
+            event_bus,
+        }
+    }
+
+    pub async fn spawn(
+        &self,
+        integration: Arc<dyn Integration>,
+        enabled: bool,
+        settings: IntegrationSettings,
+    ) {
+        let name = integration.name().to_string();
+        let (tx, rx) = mpsc::channel(10);
+        self.control_senders.write().await.insert(name.clone(), tx);
+        self.statuses
+            .write()
+            .await
+            .insert(name.clone(), IntegrationStatus::default());
+        let handle = tokio::task::spawn(self.clone().supervise(integration, rx, enabled, settings));
+        self.loops.write().await.insert(name, handle);
+    }
+
+    /// Sends `message` to the loop of the integration `name`. Returns false if
+    /// there is no such loop.
+    pub async fn send(&self, name: &str, message: ControlMessage) -> bool {
+        let sender = self.control_senders.read().await.get(name).cloned();
+        match sender {
+            Some(sender) => sender.send(message).await.is_ok(),
+            None => false,
+        }
+    }
+
+    /// Hands edited settings to the integration `name`. Returns false if there
+    /// is no such loop.
+    pub async fn configure(&self, name: &str, settings: IntegrationSettings) -> bool {
+        self.send(name, ControlMessage::Configure(settings)).await
+    }
+
+    /// Has the integration `name` poll right away and waits for the number of
+    /// devices it found or updated.
+    pub async fn refresh(&self, name: &str) -> Result<usize, String> {
+        self.request(name, ControlMessage::RefreshNow).await
+    }
+
+    /// Has the integration `name` stop, start and poll right away, and waits
+    /// for the number of devices it found or updated.
+    pub async fn restart(&self, name: &str) -> Result<usize, String> {
+        self.request(name, ControlMessage::Restart).await
+    }
+
+    async fn request(
+        &self,
+        name: &str,
+        message: impl FnOnce(PollReply) -> ControlMessage,
+    ) -> Result<usize, String> {
+        let (reply, response) = oneshot::channel();
+        if !self.send(name, message(reply)).await {
+            return Err(format!("{name} has no background job"));
+        }
+        response
+            .await
+            .map_err(|_| format!("{name} stopped before replying"))?
+    }
+
+    /// Sends `Shutdown` to every loop and waits for them to stop the
+    /// integration and exit. Loops in the middle of a poll finish it first.
+    /// Returns the names of the integrations still running at `deadline`,
+    /// whose loops are then aborted.
+    pub async fn shutdown(&self, deadline: Instant) -> Vec<String> {
+        let senders = std::mem::take(&mut *self.control_senders.write().await);
+        for sender in senders.values() {
+            // An error means the loop has already exited
+            let _ = timeout_at(deadline, sender.send(ControlMessage::Shutdown)).await;
+        }
+        drop(senders);
+
+        let loops = std::mem::take(&mut *self.loops.write().await);
+        let mut abandoned = Vec::new();
+        for (name, mut handle) in loops {
+            if timeout_at(deadline, &mut handle).await.is_err() {
+                handle.abort();
+                abandoned.push(name);
+            }
+        }
+        abandoned.sort();
+        abandoned
+    }
+
+    pub async fn statuses(&self) -> HashMap<String, IntegrationStatus> {
+        self.statuses.read().await.clone()
+    }
+
+    async fn update_status(&self, name: &str, update: impl FnOnce(&mut IntegrationStatus)) {
+        if let Some(status) = self.statuses.write().await.get_mut(name) {
+            update(status);
+        }
+    }
+
+    async fn record_error(&self, name: &str, error: &anyhow::Error) -> String {
+        let error = format!("{error:#}");
+        self.event_bus.integration_error(name, &error);
+        self.update_status(name, |status| {
+            status.last_error = Some(error.clone());
+            status.last_error_at = Some(Utc::now());
+        })
+        .await;
+        error
+    }
+
+    async fn run_poll(
+        &self,
+        integration: &dyn Integration,
+        refresh: bool,
+    ) -> Result<usize, String> {
+        let name = integration.name();
+        let result = if refresh {
+            integration.refresh().await
+        } else {
+            integration.poll().await
+        };
+        let health = integration.health().await;
+        match result {
+            Ok(count) => {
+                self.update_status(name, |status| {
+                    status.last_success = Some(Utc::now());
+                    status.health = health;
+                })
+                .await;
+                Ok(count)
+            }
+            Err(e) => {
+                self.update_status(name, |status| status.health = health)
+                    .await;
+                Err(self.record_error(name, &e).await)
+            }
+        }
+    }
+
+    async fn run_start(&self, integration: &dyn Integration) {
+        if let Err(e) = integration.start().await {
+            self.record_error(integration.name(), &e).await;
+        }
+    }
+
+    async fn run_stop(&self, integration: &dyn Integration) {
+        if let Err(e) = integration.stop().await {
+            self.record_error(integration.name(), &e).await;
+        }
+    }
+
+    async fn supervise(
+        self,
+        integration: Arc<dyn Integration>,
+        mut control_rx: Receiver<ControlMessage>,
+        enabled: bool,
+        mut settings: IntegrationSettings,
+    ) {
+        let name = integration.name();
+        log::info!("Supervising {name}");
+        integration.configure(&settings);
+        let mut running = enabled;
+        if running {
+            self.run_start(&*integration).await;
+        }
+        let mut next_run = Instant::now();
+
+        loop {
+            self.update_status(name, |status| {
+                status.running = running;
+                status.next_run = running.then(|| {
+                    let until_next_run = next_run.saturating_duration_since(Instant::now());
+                    Utc::now() + chrono::Duration::from_std(until_next_run).unwrap_or_default()
+                });
+            })
+            .await;
+
+            tokio::select! {
+                _ = tokio::time::sleep_until(next_run), if running => {
+                    // Failures are recorded in the status
+                    let _ = self.run_poll(&*integration, false).await;
+                    next_run = Instant::now() + settings.poll_interval();
+                },
+                message = control_rx.recv() => {
+                    log::info!("{name} received control message: {message:?}");
+                    match message {
+                        Some(ControlMessage::Start) => {
+                            if !running {
+                                self.run_start(&*integration).await;
+                                running = true;
+                                next_run = Instant::now();
+                            }
+                        }
+                        Some(ControlMessage::Stop) => {
+                            if running {
+                                self.run_stop(&*integration).await;
+                                running = false;
+                            }
+                        }
+                        Some(ControlMessage::RefreshNow(reply)) => {
+                            let result = if running {
+                                let result = self.run_poll(&*integration, true).await;
+                                next_run = Instant::now() + settings.poll_interval();
+                                result
+                            } else {
+                                Err(format!("{name} is disabled"))
+                            };
+                            let _ = reply.send(result);
+                        }
+                        Some(ControlMessage::Restart(reply)) => {
+                            let result = if running {
+                                self.run_stop(&*integration).await;
+                                self.run_start(&*integration).await;
+                                let result = self.run_poll(&*integration, true).await;
+                                next_run = Instant::now() + settings.poll_interval();
+                                result
+                            } else {
+                                Err(format!("{name} is disabled"))
+                            };
+                            let _ = reply.send(result);
+                        }
+                        Some(ControlMessage::Configure(new_settings)) => {
+                            integration.configure(&new_settings);
+                            // A shorter interval takes effect right away, a
+                            // longer one after the run already scheduled
+                            next_run = next_run.min(Instant::now() + new_settings.poll_interval());
+                            settings = new_settings;
+                        }
+                        Some(ControlMessage::Shutdown) | None => {
+                            if running {
+                                self.run_stop(&*integration).await;
+                            }
+                            self.update_status(name, |status| {
+                                status.running = false;
+                                status.next_run = None;
+                            })
+                            .await;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every() {
+        let every = Every::new(Duration::from_secs(3600));
+        assert!(every.due());
+        assert!(!every.due());
+        every.reset();
+        assert!(every.due());
+        assert!(Every::new(Duration::ZERO).due());
+    }
+
+    struct FakeIntegration;
+
+    impl Integration for FakeIntegration {
+        fn name(&self) -> &'static str {
+            "fake"
+        }
+
+        fn poll(&self) -> PollFuture<'_> {
+            future::ready(Ok(3)).boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_and_restart() {
+        let supervisor = Supervisor::new(EventBus::default());
+        supervisor
+            .spawn(
+                Arc::new(FakeIntegration),
+                true,
+                IntegrationSettings::default(),
+            )
+            .await;
+        assert_eq!(supervisor.refresh("fake").await, Ok(3));
+        assert_eq!(supervisor.restart("fake").await, Ok(3));
+        assert!(supervisor.statuses().await["fake"].last_success.is_some());
+
+        assert!(supervisor.send("fake", ControlMessage::Stop).await);
+        assert!(supervisor.refresh("fake").await.is_err());
+        assert!(supervisor.refresh("missing").await.is_err());
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        assert!(supervisor.shutdown(deadline).await.is_empty());
+        assert!(!supervisor.statuses().await["fake"].running);
+        assert!(!supervisor.send("fake", ControlMessage::Start).await);
+    }
+}
@@ -2,13 +2,13 @@ use {
     chrono::{DateTime, Utc},
     serde::{Deserialize, Serialize},
     serde_json::Value,
-    std::fmt,
+    std::{fmt, net::Ipv4Addr, time::Duration},
     uuid::Uuid,
 };
 
 pub mod config;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[cfg_attr(feature = "ssr", derive(sqlx::prelude::Type))]
 #[serde(rename_all = "kebab-case")]
 #[cfg_attr(
@@ -43,6 +43,88 @@ impl fmt::Display for DeviceType {
     }
 }
 
+impl DeviceType {
+    /// The name of the integration that discovers and controls this type.
+    pub fn integration(&self) -> &'static str {
+        match self {
+            Self::KasaPlug | Self::KasaLight | Self::KasaDimmer | Self::KasaPowerStrip => "tplink",
+            Self::TuyaLight | Self::TuyaGrowLight => "tuya",
+            Self::RingDoorbell => "ring",
+            Self::RokuTv => "roku",
+            Self::Stoplight => "stoplight",
+        }
+    }
+}
+
+/// Common operations a device driver can declare support for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    OnOff,
+    Brightness,
+    Color,
+    ColorTemp,
+    /// Applying the lighting presets saved on the device.
+    Presets,
+    /// Fading to a state over a transition period.
+    Transition,
+    Keypress,
+    LaunchApp,
+}
+
+/// A command that can be dispatched to any device through its driver.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum DeviceCommand {
+    SetPower {
+        on: bool,
+    },
+    SetBrightness {
+        brightness: u8,
+    },
+    SetColor {
+        color: String,
+    },
+    SetColorTemp {
+        color_temp: u16,
+    },
+    SetHsv {
+        hue: u16,
+        saturation: u8,
+        brightness: u8,
+    },
+    /// Applies the preset saved at `index` on the device.
+    ApplyPreset {
+        index: u8,
+    },
+    /// Changes to the fields set in `state` over `transition_ms`.
+    FadeTo {
+        state: DeviceState,
+        transition_ms: u32,
+    },
+    Keypress {
+        key: String,
+    },
+    LaunchApp {
+        app_id: String,
+    },
+}
+
+impl DeviceCommand {
+    pub fn capability(&self) -> Capability {
+        match self {
+            Self::SetPower { .. } => Capability::OnOff,
+            Self::SetBrightness { .. } => Capability::Brightness,
+            Self::SetColor { .. } | Self::SetHsv { .. } => Capability::Color,
+            Self::SetColorTemp { .. } => Capability::ColorTemp,
+            Self::ApplyPreset { .. } => Capability::Presets,
+            Self::FadeTo { .. } => Capability::Transition,
+            Self::Keypress { .. } => Capability::Keypress,
+            Self::LaunchApp { .. } => Capability::LaunchApp,
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 #[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
 pub struct Device {
@@ -55,10 +137,180 @@ pub struct Device {
     pub last_seen: DateTime<Utc>,
     pub mac_address: Option<String>,
     pub child_id: Option<String>,
+    pub room_id: Option<i64>,
+    pub tags: Vec<String>,
+    pub display_name: Option<String>,
+    pub icon: Option<String>,
+    /// False once the device hasn't been seen for longer than its
+    /// integration's `offline_after_secs`.
+    pub online: bool,
 }
 
+impl Device {
+    /// The user's display name if one was set, otherwise the name reported by
+    /// the device.
+    pub fn label(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// A room devices can be assigned to. Rooms sharing a `zone` (e.g. "basement")
+/// can be controlled together.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+pub struct Room {
+    pub id: i64,
+    pub name: String,
+    pub zone: Option<String>,
+}
+
+/// A set of devices targeted by a group command.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceGroup {
+    Room(String),
+    Zone(String),
+    Tag(String),
+}
+
+impl fmt::Display for DeviceGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Room(name) => write!(f, "room {name}"),
+            Self::Zone(name) => write!(f, "zone {name}"),
+            Self::Tag(name) => write!(f, "tag {name}"),
+        }
+    }
+}
+
+/// Outcome of a group command. Devices in the group that don't support the
+/// command are skipped rather than failed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GroupCommandResult {
+    pub succeeded: Vec<i64>,
+    pub failed: Vec<(i64, String)>,
+    pub skipped: Vec<i64>,
+}
+
+/// The controllable state of a device, as captured by a scene. Fields the
+/// device doesn't report are left empty and aren't restored.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeviceState {
+    pub on: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brightness: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hue: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub saturation: Option<u8>,
+    /// Kelvin, `None` or 0 while a bulb is in color mode.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_temp: Option<u16>,
+}
+
+impl DeviceState {
+    /// The commands that bring a device back to this state, power first.
+    /// Color is restored as HSV unless the device was in color temperature
+    /// mode.
+    pub fn commands(&self) -> Vec<DeviceCommand> {
+        let mut commands = vec![DeviceCommand::SetPower { on: self.on }];
+        if !self.on {
+            return commands;
+        }
+        match (
+            self.color_temp.filter(|&k| k > 0),
+            self.hue,
+            self.saturation,
+        ) {
+            (Some(color_temp), _, _) => {
+                commands.push(DeviceCommand::SetColorTemp { color_temp });
+                commands.extend(
+                    self.brightness
+                        .map(|brightness| DeviceCommand::SetBrightness { brightness }),
+                );
+            }
+            (None, Some(hue), Some(saturation)) => commands.push(DeviceCommand::SetHsv {
+                hue,
+                saturation,
+                brightness: self.brightness.unwrap_or(100),
+            }),
+            _ => commands.extend(
+                self.brightness
+                    .map(|brightness| DeviceCommand::SetBrightness { brightness }),
+            ),
+        }
+        commands
+    }
+}
+
+/// A named snapshot of device states that can be restored in one call.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Scene {
+    pub id: i64,
+    pub name: String,
+    pub devices: Vec<SceneDevice>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+pub struct SceneDevice {
+    pub device_id: i64,
+    #[cfg_attr(feature = "ssr", sqlx(json))]
+    pub state: DeviceState,
+}
+
+/// A row of `device_state_history`, recorded whenever a device's state changes.
 #[derive(Clone, Serialize, Deserialize, Debug)]
 #[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+pub struct DeviceStateHistory {
+    pub id: i64,
+    pub device_id: i64,
+    pub power_state: i32,
+    pub battery_percentage: Option<i64>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// A row of `energy_reading`, polled from devices with an emeter.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+pub struct EnergyReading {
+    pub device_id: i64,
+    pub recorded_at: DateTime<Utc>,
+    pub voltage: Option<f64>,
+    pub current: Option<f64>,
+    pub power: Option<f64>,
+    pub total_kwh: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnergyPeriod {
+    Day,
+    Month,
+}
+
+impl EnergyPeriod {
+    /// The `date_trunc` field for the period.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Month => "month",
+        }
+    }
+}
+
+/// Energy a device used during the day or month starting at `period_start`.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+pub struct EnergyUsage {
+    pub device_id: i64,
+    pub period_start: DateTime<Utc>,
+    pub kwh: f64,
+    pub average_power: Option<f64>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
 pub struct AuthState {
     pub refresh_token: String,
     pub hardware_id: String,
@@ -90,11 +342,326 @@ pub struct Integration {
     pub name: String,
     pub enabled: bool,
     pub image: String,
+    /// Devices not seen for this long are marked offline. `None` for
+    /// integrations whose devices aren't polled.
+    pub offline_after_secs: Option<i32>,
+    #[cfg_attr(feature = "ssr", sqlx(json))]
+    pub settings: IntegrationSettings,
+}
+
+/// How often an integration's background job does its work, stored in
+/// `integration.settings`. Changes are picked up by the running job.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct IntegrationSettings {
+    pub poll_interval_secs: u32,
+    /// For integrations that look for new devices less often than they poll.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discovery_interval_secs: Option<u32>,
+    /// For integrations whose credentials expire.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_interval_secs: Option<u32>,
+    /// Hosts and CIDR ranges probed by unicast during discovery, for devices
+    /// on subnets that broadcasts don't reach. See [`scan_target_hosts`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scan_targets: Vec<String>,
+}
+
+impl Default for IntegrationSettings {
+    fn default() -> Self {
+        Self {
+            poll_interval_secs: 60 * 60,
+            discovery_interval_secs: None,
+            auth_interval_secs: None,
+            scan_targets: Vec::new(),
+        }
+    }
+}
+
+impl IntegrationSettings {
+    /// Shortest interval accepted from the UI, so a typo can't flood the
+    /// network or a cloud API.
+    pub const MIN_INTERVAL_SECS: u32 = 10;
+
+    pub fn validate(&self) -> Result<(), String> {
+        let intervals = [
+            ("Poll", Some(self.poll_interval_secs)),
+            ("Discovery", self.discovery_interval_secs),
+            ("Auth", self.auth_interval_secs),
+        ];
+        for (name, secs) in intervals {
+            if let Some(secs) = secs
+                && secs < Self::MIN_INTERVAL_SECS
+            {
+                return Err(format!(
+                    "{name} interval must be at least {} seconds",
+                    Self::MIN_INTERVAL_SECS
+                ));
+            }
+        }
+        for target in &self.scan_targets {
+            scan_target_hosts(target)?;
+        }
+        Ok(())
+    }
+
+    /// Every address in `scan_targets`, in order and without repeats.
+    /// Targets that don't parse are skipped; `validate` rejects them on save.
+    pub fn scan_hosts(&self) -> Vec<Ipv4Addr> {
+        let mut hosts = Vec::new();
+        for target in &self.scan_targets {
+            for host in scan_target_hosts(target).unwrap_or_default() {
+                if !hosts.contains(&host) {
+                    hosts.push(host);
+                }
+            }
+        }
+        hosts
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_secs(self.poll_interval_secs.into())
+    }
+
+    pub fn discovery_interval(&self) -> Option<Duration> {
+        self.discovery_interval_secs
+            .map(|secs| Duration::from_secs(secs.into()))
+    }
+
+    pub fn auth_interval(&self) -> Option<Duration> {
+        self.auth_interval_secs
+            .map(|secs| Duration::from_secs(secs.into()))
+    }
+}
+
+/// Largest range accepted in `IntegrationSettings::scan_targets`, which is
+/// 1022 hosts.
+pub const MIN_SCAN_PREFIX_LEN: u8 = 22;
+
+/// Expands a scan target, either a single address like `10.0.20.5` or a range
+/// like `10.0.20.0/24`, into the hosts to probe. Ranges of /30 or larger skip
+/// their network and broadcast addresses.
+pub fn scan_target_hosts(target: &str) -> Result<Vec<Ipv4Addr>, String> {
+    let target = target.trim();
+    let Some((addr, prefix_len)) = target.split_once('/') else {
+        let host = target
+            .parse::<Ipv4Addr>()
+            .map_err(|_| format!("{target:?} is not an IPv4 address"))?;
+        return Ok(vec![host]);
+    };
+    let addr = addr
+        .parse::<Ipv4Addr>()
+        .map_err(|_| format!("{target:?} is not an IPv4 range"))?;
+    let prefix_len = prefix_len
+        .parse::<u8>()
+        .ok()
+        .filter(|len| *len <= 32)
+        .ok_or_else(|| format!("{target:?} has an invalid prefix length"))?;
+    if prefix_len < MIN_SCAN_PREFIX_LEN {
+        return Err(format!(
+            "{target:?} is too large, use /{MIN_SCAN_PREFIX_LEN} or smaller"
+        ));
+    }
+    let mask = u32::MAX
+        .checked_shl(32 - u32::from(prefix_len))
+        .unwrap_or(0);
+    let network = u32::from(addr) & mask;
+    let broadcast = network | !mask;
+    let hosts = if prefix_len >= 31 {
+        network..=broadcast
+    } else {
+        network + 1..=broadcast - 1
+    };
+    Ok(hosts.map(Ipv4Addr::from).collect())
+}
+
+/// A credential an integration needs, entered on the integrations page and
+/// stored encrypted under `name`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SecretField {
+    pub name: &'static str,
+    pub label: &'static str,
+    /// Entered in a password input.
+    pub sensitive: bool,
+}
+
+/// The credentials entered on the integrations page for `integration`.
+/// Integrations that log in through their own page, like Ring, have none.
+pub fn secret_fields(integration: &str) -> &'static [SecretField] {
+    match integration {
+        "tuya" => &[
+            SecretField {
+                name: "client_id",
+                label: "Client ID",
+                sensitive: false,
+            },
+            SecretField {
+                name: "api_key",
+                label: "Client secret",
+                sensitive: true,
+            },
+            SecretField {
+                name: "user_id",
+                label: "User ID",
+                sensitive: false,
+            },
+        ],
+        "eufy" => &[
+            SecretField {
+                name: "username",
+                label: "Email",
+                sensitive: false,
+            },
+            SecretField {
+                name: "password",
+                label: "Password",
+                sensitive: true,
+            },
+        ],
+        "tplink" => &[
+            SecretField {
+                name: "username",
+                label: "TP-Link account email",
+                sensitive: false,
+            },
+            SecretField {
+                name: "password",
+                label: "TP-Link account password",
+                sensitive: true,
+            },
+        ],
+        _ => &[],
+    }
+}
+
+/// The result of an integration's own health check, e.g. whether it still
+/// has valid credentials.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum IntegrationHealth {
+    #[default]
+    Unknown,
+    Healthy,
+    Unhealthy(String),
 }
 
+/// What the supervisor knows about an integration's poll loop.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct IntegrationStatus {
+    pub running: bool,
+    pub health: IntegrationHealth,
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<DateTime<Utc>>,
+    pub next_run: Option<DateTime<Utc>>,
+}
+
+/// Where an integration job sends the number of devices a poll found or
+/// updated, or why it failed.
+#[cfg(feature = "ssr")]
+pub type PollReply = tokio::sync::oneshot::Sender<Result<usize, String>>;
+
+#[cfg(feature = "ssr")]
 #[derive(Debug)]
 pub enum ControlMessage {
     Start,
     Stop,
+    /// Poll right away instead of waiting for the next scheduled run.
+    RefreshNow(PollReply),
+    /// Stop and start the integration, then poll right away.
+    Restart(PollReply),
+    /// The integration's settings were edited.
+    Configure(IntegrationSettings),
     Shutdown,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_state_commands() {
+        let off = DeviceState {
+            on: false,
+            brightness: Some(40),
+            ..Default::default()
+        };
+        assert_eq!(off.commands(), vec![DeviceCommand::SetPower { on: false }]);
+
+        let color = DeviceState {
+            on: true,
+            brightness: Some(15),
+            hue: Some(240),
+            saturation: Some(80),
+            color_temp: Some(0),
+        };
+        assert_eq!(
+            color.commands(),
+            vec![
+                DeviceCommand::SetPower { on: true },
+                DeviceCommand::SetHsv {
+                    hue: 240,
+                    saturation: 80,
+                    brightness: 15
+                },
+            ]
+        );
+
+        let warm = DeviceState {
+            color_temp: Some(2700),
+            ..color
+        };
+        assert_eq!(
+            warm.commands(),
+            vec![
+                DeviceCommand::SetPower { on: true },
+                DeviceCommand::SetColorTemp { color_temp: 2700 },
+                DeviceCommand::SetBrightness { brightness: 15 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_integration_settings() {
+        let mut settings = IntegrationSettings {
+            poll_interval_secs: 60,
+            discovery_interval_secs: Some(300),
+            auth_interval_secs: None,
+            scan_targets: Vec::new(),
+        };
+        assert_eq!(settings.validate(), Ok(()));
+        settings.scan_targets = vec!["10.0.20.0/33".into()];
+        assert!(settings.validate().is_err());
+        settings.scan_targets = vec!["10.0.20.7".into(), "10.0.20.4/30".into()];
+        assert_eq!(settings.validate(), Ok(()));
+        assert_eq!(
+            settings.scan_hosts(),
+            [
+                Ipv4Addr::new(10, 0, 20, 7),
+                Ipv4Addr::new(10, 0, 20, 5),
+                Ipv4Addr::new(10, 0, 20, 6)
+            ]
+        );
+        settings.scan_targets.clear();
+        settings.discovery_interval_secs = Some(1);
+        assert!(settings.validate().is_err());
+        settings.discovery_interval_secs = None;
+        settings.poll_interval_secs = 0;
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn test_scan_target_hosts() {
+        assert_eq!(
+            scan_target_hosts(" 192.168.50.9 "),
+            Ok(vec![Ipv4Addr::new(192, 168, 50, 9)])
+        );
+        let hosts = scan_target_hosts("192.168.50.77/24").unwrap();
+        assert_eq!(hosts.len(), 254);
+        assert_eq!(hosts[0], Ipv4Addr::new(192, 168, 50, 1));
+        assert_eq!(hosts[253], Ipv4Addr::new(192, 168, 50, 254));
+        assert_eq!(scan_target_hosts("192.168.50.9/32").unwrap().len(), 1);
+        assert_eq!(scan_target_hosts("10.1.0.0/22").unwrap().len(), 1022);
+        assert!(scan_target_hosts("10.1.0.0/21").is_err());
+        assert!(scan_target_hosts("plug.lan").is_err());
+        assert!(scan_target_hosts("10.1.0.0/x").is_err());
+    }
+}
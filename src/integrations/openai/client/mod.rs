@@ -1,11 +1,15 @@
 use {
-    crate::integrations::iron_nest::{execute_function, types::Device},
-    chrono::Utc,
+    crate::integrations::iron_nest::{
+        driver::DriverRegistry,
+        events::EventBus,
+        execute_function, get_scenes,
+        types::{Capability, Device, DeviceType},
+    },
     futures::future::join_all,
     leptos::prelude::*,
     log::info,
-    serde_json::json,
-    sqlx::{PgPool, Row},
+    serde_json::{Value, json},
+    sqlx::PgPool,
 };
 
 cfg_if::cfg_if! { if #[cfg(feature = "ssr")] {
@@ -14,7 +18,8 @@ cfg_if::cfg_if! { if #[cfg(feature = "ssr")] {
     async_openai::{
         types::{
             ChatCompletionFunctionsArgs,
-            ChatCompletionRequestUserMessageArgs, ChatCompletionToolArgs, ChatCompletionToolType,
+            ChatCompletionRequestUserMessageArgs, ChatCompletionTool, ChatCompletionToolArgs,
+            ChatCompletionToolType,
             CreateChatCompletionRequestArgs,ChatCompletionMessageToolCall,
             ChatCompletionRequestAssistantMessageArgs,
             ChatCompletionRequestToolMessageArgs,
@@ -30,7 +35,12 @@ cfg_if::cfg_if! { if #[cfg(feature = "ssr")] {
 
     impl Device {
         pub fn format_for_openapi(&self) -> String {
-            format!("{} - {} - {} - {}\n", self.name, self.device_type, self.ip, self.power_state)
+            format!(
+                "{} - {} - {} - {} - {}\n",
+                self.id,
+                self.label(),
+                self.device_type, self.ip, self.power_state
+            )
         }
     }
 
@@ -39,55 +49,95 @@ cfg_if::cfg_if! { if #[cfg(feature = "ssr")] {
     }
 }}
 
-pub async fn open_api_command(text: String, pool: &PgPool) -> Result<String, ServerFnError> {
-    println!("calling assistant with {text:?}");
-    let client = Client::new();
-
-    let mut tp_link_plug_ips: Vec<String> = Vec::new();
-    let mut tp_link_light_ips: Vec<String> = Vec::new();
-    let mut roku_ips: Vec<String> = Vec::new();
-    let mut devices: Vec<Device> = Vec::new();
+fn device_tool(name: &str, description: &str, parameters: Value) -> ChatCompletionTool {
+    ChatCompletionToolArgs::default()
+        .r#type(ChatCompletionToolType::Function)
+        .function(
+            ChatCompletionFunctionsArgs::default()
+                .name(name)
+                .description(description)
+                .parameters(parameters)
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap()
+}
 
-    let rows = sqlx::query("SELECT id, name, device_type, ip, power_state, last_seen FROM devices")
-        .fetch_all(pool)
-        .await?;
+/// Maps a tool call onto `execute_function`. Capability tools share their name
+/// with the `DeviceCommand` tag so they are forwarded as a `device_command`.
+async fn execute_tool_call(
+    pool: &PgPool,
+    driver_registry: &DriverRegistry,
+    event_bus: &EventBus,
+    function_name: String,
+    mut function_args: Value,
+) -> Value {
+    let function_name = match function_name.as_str() {
+        "set_power" | "set_brightness" | "set_color" | "keypress" | "launch_app" => {
+            function_args["command"] = json!(function_name);
+            "device_command".to_string()
+        }
+        _ => function_name,
+    };
+    execute_function(
+        pool,
+        driver_registry,
+        event_bus,
+        function_name,
+        function_args,
+    )
+    .await
+    .unwrap_or_else(|e| json!({ "error": e.to_string() }))
+}
 
-    for row in rows {
-        let state_value: i32 = row.get("power_state");
-        let state: i32 = state_value;
-        let ip: String = row.get("ip");
-        // let battery_percentage_value: i64 = row.get("battery_percentage");
-        // let battery_percentage: u64 = battery_percentage_value
-        //     .try_into()
-        //     .expect("Value out of range for u64");
+pub async fn open_api_command(
+    text: String,
+    pool: &PgPool,
+    driver_registry: &DriverRegistry,
+    event_bus: &EventBus,
+) -> Result<String, ServerFnError> {
+    println!("calling assistant with {text:?}");
+    let client = Client::new();
 
-        devices.push(Device {
-            id: row.get("id"),
-            name: row.get("name"),
-            device_type: row.get("device_type"),
-            ip: ip.to_string(),
-            power_state: state,
-            battery_percentage: 0,
-            last_seen: Utc::now(),
-            mac_address: None,
-            child_id: None,
-        });
+    let query = "
+        SELECT id, name, device_type, ip, power_state, battery_percentage, last_seen, mac_address, child_id, room_id, tags, display_name, icon, online
+        FROM device
+        ORDER BY id
+    ";
+    let devices = sqlx::query_as::<_, Device>(query).fetch_all(pool).await?;
 
-        let device_type: String = row.get("device_type");
-        match device_type.as_str() {
-            "roku" => roku_ips.push(ip.to_string()),
-            "smart-plug" => tp_link_plug_ips.push(ip),
-            "smart-light" => tp_link_light_ips.push(ip),
-            _ => (),
-        }
-    }
+    let device_ids = |capability: Capability| -> Vec<i64> {
+        devices
+            .iter()
+            .filter(|device| driver_registry.supports(device.device_type, capability))
+            .map(|device| device.id)
+            .collect()
+    };
+    let power_ids = device_ids(Capability::OnOff);
+    let brightness_ids = device_ids(Capability::Brightness);
+    let color_ids = device_ids(Capability::Color);
+    let keypress_ids = device_ids(Capability::Keypress);
+    let launch_app_ids = device_ids(Capability::LaunchApp);
+    let scene_names: Vec<String> = get_scenes(pool)
+        .await?
+        .into_iter()
+        .map(|scene| scene.name)
+        .collect();
+    let roku_ids: Vec<i64> = devices
+        .iter()
+        .filter(|device| device.device_type == DeviceType::RokuTv)
+        .map(|device| device.id)
+        .collect();
 
     let initial_system_prompt = format!(
         "You are a home assistant named Iron Nest.
-        Here are the following device names:
+        Here are the following devices (id - name - type - ip - power state).
+        Always refer to devices by id:
         {:?}
+        Scenes that can be activated: {scene_names:?}
         Respond to the following input: {text}",
-        format_devices(devices)
+        format_devices(devices.clone())
     );
 
     info!("Prompt Length: {}", initial_system_prompt.len());
@@ -102,174 +152,85 @@ pub async fn open_api_command(text: String, pool: &PgPool) -> Result<String, Ser
             .into()
     ])
     .tools(vec![
-        ChatCompletionToolArgs::default()
-            .r#type(ChatCompletionToolType::Function)
-            .function(
-                ChatCompletionFunctionsArgs::default()
-                    .name("roku_send_keypress")
-                    .description("Send a keypress to a roku tv device")
-                    .parameters(json!({
-                        "type": "object",
-                        "properties": {
-                            "ip": { 
-                                "type": "string", 
-                                "enum": roku_ips,
-                            },
-                            "key": { 
-                                "type": "string", 
-                                "enum": [ 
-                                    "powerOn", "powerOff", "home", "rev", "fwd", "play", "select", "left", "right", "down", "up", "back", 
-                                    "replay", "info", "backspace", "enter", "volumeDown", "volumeUp", "volumeMute", "inputTuner", 
-                                    "inputHDMI1", "inputHDMI2", "inputHDMI3", "inputHDMI4", "inputAV1", "channelUp", "channelDown"
-                                ]
-                            },
-                        },
-                        "required": ["key", "ip"],
-                    }))
-                    .build().unwrap()
-            )
-            .build().unwrap(),
-        ChatCompletionToolArgs::default()
-            .r#type(ChatCompletionToolType::Function)
-            .function(
-                ChatCompletionFunctionsArgs::default()
-                    .name("tplink_turn_plug_on")
-                    .description("Turn on tplink smart plug")
-                    .parameters(json!({
-                        "type": "object",
-                        "properties": {
-                            "ip": { 
-                                "type": "string", 
-                                "enum": tp_link_plug_ips,
-                            },
-                        },
-                        "required": ["ip"],
-                    }))
-                    .build().unwrap()
-            )
-            .build().unwrap(),
-        ChatCompletionToolArgs::default()
-            .r#type(ChatCompletionToolType::Function)
-            .function(
-                ChatCompletionFunctionsArgs::default()
-                    .name("tplink_turn_plug_off")
-                    .description("Turn off tplink smart plug")
-                    .parameters(json!({
-                        "type": "object",
-                        "properties": {
-                            "ip": { 
-                                "type": "string", 
-                                "enum": tp_link_plug_ips,
-                            },
-                        },
-                        "required": ["ip"],
-                    }))
-                    .build().unwrap()
-            )
-            .build().unwrap(),
-        ChatCompletionToolArgs::default()
-            .r#type(ChatCompletionToolType::Function)
-            .function(
-                ChatCompletionFunctionsArgs::default()
-                    .name("tplink_turn_light_on_off")
-                    .description("Turn on or off tplink smart light")
-                    .parameters(json!({
-                        "type": "object",
-                        "properties": {
-                            "ip": { 
-                                "type": "string", 
-                                "enum": tp_link_light_ips,
-                            },
-                            "state": {
-                                "type": "number",
-                                "enum": [0, 1],
-                            }
-                        },
-                        "required": ["ip", "state"],
-                    }))
-                    .build().unwrap()
-            )
-            .build().unwrap(),
-        ChatCompletionToolArgs::default()
-            .r#type(ChatCompletionToolType::Function)
-            .function(
-                ChatCompletionFunctionsArgs::default()
-                    .name("tplink_set_light_brightness")
-                    .description("Set tplink smart light brightness (1 - 100)")
-                    .parameters(json!({
-                        "type": "object",
-                        "properties": {
-                            "ip": { 
-                                "type": "string", 
-                                "enum": tp_link_light_ips,
-                            },
-                            "brightness": {
-                                "type": "number",
-                            }
-                        },
-                        "required": ["ip", "brightness"],
-                    }))
-                    .build().unwrap()
-            )
-            .build().unwrap(),
-        ChatCompletionToolArgs::default()
-            .r#type(ChatCompletionToolType::Function)
-            .function(
-                ChatCompletionFunctionsArgs::default()
-                    .name("roku_search")
-                    .description("Open the Roku search page with the given search params")
-                    .parameters(json!({
-                        "type": "object",
-                        "properties": {
-                            "query": {
-                                "type": "string",
-                                "description": "The value to search for on the roku",
-                            },
-                        },
-                        "required": [],
-                    }))
-                    .build().unwrap()
-            )
-            .build().unwrap(),
-        ChatCompletionToolArgs::default()
-            .r#type(ChatCompletionToolType::Function)
-            .function(
-                ChatCompletionFunctionsArgs::default()
-                    .name("roku_launch_app")
-                    .description("")
-                    .parameters(json!({
-                        "type": "object",
-                        "properties": {
-                            "app_id": {
-                                "type": "string",
-                                "description": "Roku app_id or name, e,g YouTube or 837",
-                            },
-                        },
-                        "required": [],
-                    }))
-                    .build().unwrap()
-            )
-            .build().unwrap(),
-            ChatCompletionToolArgs::default()
-                .r#type(ChatCompletionToolType::Function)
-                .function(
-                    ChatCompletionFunctionsArgs::default()
-                        .name("stoplight_toggle")
-                        .description("Toggle current state of red, green, or yellow by name")
-                        .parameters(json!({
-                            "type": "object",
-                            "properties": {
-                                "color": {
-                                    "type": "string",
-                                    "description": "The color light to toggle",
-                                    "enum": ["red", "green", "yellow"],
-                                },
-                            },
-                            "required": [],
-                        }))
-                        .build().unwrap()
-                )
-                .build().unwrap(),
+        device_tool("set_power", "Turn a device on or off", json!({
+            "type": "object",
+            "properties": {
+                "device_id": { "type": "integer", "enum": power_ids },
+                "on": { "type": "boolean" },
+            },
+            "required": ["device_id", "on"],
+        })),
+        device_tool("set_brightness", "Set a light or dimmer brightness (1 - 100)", json!({
+            "type": "object",
+            "properties": {
+                "device_id": { "type": "integer", "enum": brightness_ids },
+                "brightness": { "type": "integer", "minimum": 1, "maximum": 100 },
+            },
+            "required": ["device_id", "brightness"],
+        })),
+        device_tool("set_color", "Set a light color as a hex string, e.g. #ff0000", json!({
+            "type": "object",
+            "properties": {
+                "device_id": { "type": "integer", "enum": color_ids },
+                "color": { "type": "string" },
+            },
+            "required": ["device_id", "color"],
+        })),
+        device_tool("keypress", "Send a keypress to a roku tv device", json!({
+            "type": "object",
+            "properties": {
+                "device_id": { "type": "integer", "enum": keypress_ids },
+                "key": {
+                    "type": "string",
+                    "enum": [
+                        "powerOn", "powerOff", "home", "rev", "fwd", "play", "select", "left", "right", "down", "up", "back",
+                        "replay", "info", "backspace", "enter", "volumeDown", "volumeUp", "volumeMute", "inputTuner",
+                        "inputHDMI1", "inputHDMI2", "inputHDMI3", "inputHDMI4", "inputAV1", "channelUp", "channelDown"
+                    ]
+                },
+            },
+            "required": ["device_id", "key"],
+        })),
+        device_tool("launch_app", "Launch an app on a roku tv device", json!({
+            "type": "object",
+            "properties": {
+                "device_id": { "type": "integer", "enum": launch_app_ids },
+                "app_id": {
+                    "type": "string",
+                    "description": "Roku app_id or name, e,g YouTube or 837",
+                },
+            },
+            "required": ["device_id", "app_id"],
+        })),
+        device_tool("roku_search", "Open the Roku search page with the given search params", json!({
+            "type": "object",
+            "properties": {
+                "device_id": { "type": "integer", "enum": roku_ids },
+                "query": {
+                    "type": "string",
+                    "description": "The value to search for on the roku",
+                },
+            },
+            "required": ["device_id", "query"],
+        })),
+        device_tool("activate_scene", "Restore a saved scene, setting several devices at once", json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "enum": scene_names },
+            },
+            "required": ["name"],
+        })),
+        device_tool("stoplight_toggle", "Toggle current state of red, green, or yellow by name", json!({
+            "type": "object",
+            "properties": {
+                "color": {
+                    "type": "string",
+                    "description": "The color light to toggle",
+                    "enum": ["red", "green", "yellow"],
+                },
+            },
+            "required": ["color"],
+        })),
     ])
     .build().unwrap();
 
@@ -290,7 +251,14 @@ pub async fn open_api_command(text: String, pool: &PgPool) -> Result<String, Ser
         let tool_call_futs = tool_calls.iter().map(|tool_call| async {
             let function_name = tool_call.function.name.to_string();
             let function_args: serde_json::Value = tool_call.function.arguments.parse().unwrap();
-            let function_response = execute_function(function_name, function_args).await;
+            let function_response = execute_tool_call(
+                pool,
+                driver_registry,
+                event_bus,
+                function_name,
+                function_args,
+            )
+            .await;
 
             (tool_call.clone(), function_response.to_string())
         });
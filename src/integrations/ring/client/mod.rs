@@ -3,7 +3,7 @@ use {
         AuthResponse, CameraEventsRes, DevicesRes, Doorbot, LocationsRes, RingCamera,
         RingCameraSnapshot, SocketTicketRes, VideoSearchRes,
     },
-    crate::integrations::iron_nest::{get_auth_from_db, insert_auth, types::AuthState},
+    crate::integrations::iron_nest::{secrets::SecretStore, types::AuthState},
     base64::{Engine, engine::general_purpose::STANDARD as base64},
     chrono::{DateTime, Duration, Local, Utc},
     chrono_tz::US::Eastern,
@@ -11,7 +11,6 @@ use {
     log::{error, info},
     reqwest::{self, Client, Method, Response},
     serde::de::DeserializeOwned,
-    sqlx::PgPool,
     std::{collections::HashMap, num::ParseFloatError, str, sync::Arc},
     uuid::Uuid,
 };
@@ -70,11 +69,14 @@ pub enum RingRestClientError {
 
 impl RingRestClient {
     #[allow(clippy::new_without_default)]
-    pub async fn new(pool: PgPool) -> Self {
-        let auth_db_pool = pool.clone();
+    pub async fn new(secrets: SecretStore) -> Self {
+        let state = secrets.get_auth("ring").await.unwrap_or_else(|e| {
+            error!("Failed to load Ring tokens: {e}");
+            AuthState::default()
+        });
         Self {
-            pool,
-            state: get_auth_from_db(&auth_db_pool, "ring").await,
+            secrets,
+            state,
             client: reqwest::Client::new(),
         }
     }
@@ -111,17 +113,15 @@ impl RingRestClient {
             let auth_res = serde_json::from_str::<AuthResponse>(&text)
                 .unwrap_or_else(|_| panic!("error requesting: {text}"));
 
-            insert_auth(
-                &self.pool,
-                "ring",
-                AuthState {
-                    auth_token: auth_res.access_token,
-                    refresh_token: auth_res.refresh_token,
-                    hardware_id: Uuid::new_v4().to_string(),
-                },
-            )
-            .await;
-            "Login successful".to_string()
+            let state = AuthState {
+                auth_token: auth_res.access_token,
+                refresh_token: auth_res.refresh_token,
+                hardware_id: Uuid::new_v4().to_string(),
+            };
+            match self.secrets.set_auth("ring", &state).await {
+                Ok(()) => "Login successful".to_string(),
+                Err(e) => format!("Failed to store tokens: {e}"),
+            }
         } else {
             res.text().await.unwrap()
         }
@@ -313,5 +313,5 @@ fn get_end_of_today() -> i64 {
 pub struct RingRestClient {
     pub state: AuthState,
     pub client: Client,
-    pub pool: PgPool,
+    pub secrets: SecretStore,
 }
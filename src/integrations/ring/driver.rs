@@ -0,0 +1,10 @@
+use crate::integrations::iron_nest::{driver::DeviceDriver, types::Capability};
+
+/// Ring doorbells are read-only from IronNest, so no commands are supported.
+pub struct RingDoorbellDriver;
+
+impl DeviceDriver for RingDoorbellDriver {
+    fn capabilities(&self) -> &'static [Capability] {
+        &[]
+    }
+}
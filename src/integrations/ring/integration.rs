@@ -0,0 +1,109 @@
+use {
+    super::{client::RingRestClient, get_ring_camera},
+    crate::integrations::iron_nest::{
+        events::EventBus,
+        insert_cameras_into_db, insert_devices_into_db,
+        supervisor::{Every, HealthFuture, Integration, IntegrationFuture, PollFuture},
+        types::{Device, DeviceType, IntegrationSettings},
+    },
+    anyhow::Context,
+    chrono::Utc,
+    futures::FutureExt,
+    log::info,
+    sqlx::PgPool,
+    std::{sync::Arc, time::Duration},
+};
+
+/// Refreshes the doorbells every poll and the auth token every five hours by
+/// default.
+pub struct RingIntegration {
+    pool: PgPool,
+    event_bus: EventBus,
+    ring_rest_client: Arc<RingRestClient>,
+    auth: Every,
+}
+
+impl RingIntegration {
+    pub fn new(pool: PgPool, event_bus: EventBus, ring_rest_client: Arc<RingRestClient>) -> Self {
+        Self {
+            pool,
+            event_bus,
+            ring_rest_client,
+            auth: Every::new(Duration::from_secs(5 * 60 * 60)),
+        }
+    }
+}
+
+impl Integration for RingIntegration {
+    fn name(&self) -> &'static str {
+        "ring"
+    }
+
+    fn configure(&self, settings: &IntegrationSettings) {
+        self.auth
+            .set_period(settings.auth_interval().unwrap_or(settings.poll_interval()));
+    }
+
+    fn start(&self) -> IntegrationFuture<'_> {
+        async move {
+            self.auth.reset();
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn poll(&self) -> PollFuture<'_> {
+        async move {
+            if self.auth.due() {
+                info!("Refreshing Ring auth token");
+                self.ring_rest_client.refresh_auth_token().await;
+            }
+
+            info!("Refreshing Ring Device Data");
+            let ring_devices = self
+                .ring_rest_client
+                .get_devices()
+                .await
+                .context("Failed to get devices")?;
+
+            let doorbots = ring_devices
+                .doorbots
+                .into_iter()
+                .chain(ring_devices.authorized_doorbots)
+                .collect::<Vec<_>>();
+
+            let mut cameras = Vec::with_capacity(doorbots.len());
+            for doorbot in doorbots.iter() {
+                cameras.push(get_ring_camera(&self.ring_rest_client, doorbot).await)
+            }
+
+            let devices = cameras
+                .iter()
+                .map(|camera| Device {
+                    id: 0,
+                    name: camera.description.to_string(),
+                    ip: camera.id.to_string(),
+                    device_type: DeviceType::RingDoorbell,
+                    power_state: 1,
+                    battery_percentage: camera.health,
+                    last_seen: Utc::now(),
+                    mac_address: None,
+                    child_id: None,
+                    room_id: None,
+                    tags: Vec::new(),
+                    display_name: None,
+                    icon: None,
+                    online: true,
+                })
+                .collect::<Vec<_>>();
+            insert_cameras_into_db(&self.pool, &cameras).await?;
+            insert_devices_into_db(&self.pool, &self.event_bus, &devices).await?;
+            Ok(devices.len())
+        }
+        .boxed()
+    }
+
+    fn health(&self) -> HealthFuture<'_> {
+        async move { self.ring_rest_client.secrets.auth_health(self.name()).await }.boxed()
+    }
+}
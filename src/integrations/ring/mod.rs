@@ -3,4 +3,8 @@ pub mod types;
 cfg_if::cfg_if! { if #[cfg(feature = "ssr")] {
   pub mod client;
   pub use client::*;
+  mod driver;
+  pub use driver::*;
+  mod integration;
+  pub use integration::*;
 }}
@@ -1,6 +1,8 @@
 use {
-    super::types::{ActionApp, Apps, RokuDeviceInfo, RokuDiscoverRes},
-    base64::Engine,
+    super::types::{
+        ActionApp, Apps, MediaPlayer, NowPlaying, PlaybackState, RokuDeviceInfo, RokuDiscoverRes,
+        TvActiveChannel,
+    },
     futures::prelude::*,
     serde_json::json,
     serde_xml_rs::from_str,
@@ -36,80 +38,133 @@ pub async fn roku_discover() -> Vec<RokuDiscoverRes> {
     devices
 }
 
-pub async fn roku_get_apps(ip: &str) -> Apps {
-    let apps = get(ip, "query/apps").await;
-    from_str(&apps).unwrap()
+#[derive(Debug, thiserror::Error)]
+pub enum RokuError {
+    #[error("Request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Invalid response: {0}")]
+    Xml(#[from] serde_xml_rs::Error),
+}
+
+pub async fn roku_get_apps(ip: &str) -> Result<Apps, RokuError> {
+    let apps = get(ip, "query/apps").await?;
+    Ok(from_str(&apps)?)
+}
+
+pub async fn roku_get_media_player(ip: &str) -> Result<MediaPlayer, RokuError> {
+    let player = get(ip, "query/media-player").await?;
+    Ok(from_str(&player)?)
 }
 
-pub async fn roku_get_media_player(ip: &str) -> String {
-    get(ip, "query/media-player").await
+pub async fn roku_get_tv_active_channel(ip: &str) -> Result<TvActiveChannel, RokuError> {
+    let channel = get(ip, "query/tv-active-channel").await?;
+    Ok(from_str(&channel)?)
 }
 
-pub async fn roku_get_active_app(ip: &str) -> ActionApp {
-    let app_text = get(ip, "query/active-app").await;
-    from_str(&app_text).unwrap()
+/// The active app and what its media player is doing. The program title is
+/// looked up only while a TV input is active.
+pub async fn roku_now_playing(ip: &str) -> Result<NowPlaying, RokuError> {
+    let active_app = roku_get_active_app(ip).await?;
+    let player = roku_get_media_player(ip).await?;
+    let title = if is_tv_input(&active_app) {
+        roku_get_tv_active_channel(ip)
+            .await
+            .ok()
+            .and_then(|channel| channel.channel)
+            .and_then(|channel| channel.program_title)
+    } else {
+        None
+    };
+    Ok(now_playing(active_app, player, title))
 }
 
-pub async fn roku_get_device_info(ip: &str) -> RokuDeviceInfo {
-    let app_text = get(ip, "query/device-info").await;
-    from_str(&app_text).unwrap()
+fn is_tv_input(active_app: &ActionApp) -> bool {
+    active_app
+        .app
+        .first()
+        .is_some_and(|app| app.app_type.as_deref() == Some("tvin"))
 }
 
-pub async fn roku_get_channel_icon(ip: &str, app_id: &str) -> String {
+fn now_playing(active_app: ActionApp, player: MediaPlayer, title: Option<String>) -> NowPlaying {
+    let app = active_app.app.into_iter().next();
+    NowPlaying {
+        app_id: app.as_ref().and_then(|app| app.id.clone()),
+        app_name: app
+            .map(|app| app.value)
+            .or(player.plugin.map(|plugin| plugin.name)),
+        state: PlaybackState::from_roku(&player.state),
+        position_ms: player.position.as_deref().and_then(parse_ms),
+        duration_ms: player.duration.as_deref().and_then(parse_ms),
+        title,
+    }
+}
+
+/// Parses the media player's "53463 ms" times.
+fn parse_ms(time: &str) -> Option<u64> {
+    time.trim().trim_end_matches("ms").trim().parse().ok()
+}
+
+pub async fn roku_get_active_app(ip: &str) -> Result<ActionApp, RokuError> {
+    let app_text = get(ip, "query/active-app").await?;
+    Ok(from_str(&app_text)?)
+}
+
+pub async fn roku_get_device_info(ip: &str) -> Result<RokuDeviceInfo, RokuError> {
+    let app_text = get(ip, "query/device-info").await?;
+    Ok(from_str(&app_text)?)
+}
+
+pub async fn roku_get_channel_icon(ip: &str, app_id: &str) -> Result<Vec<u8>, RokuError> {
     let roku_url = format!("http://{ip}:8060/query/icon/{app_id}");
     let client = reqwest::Client::new();
 
-    let res_bytes = client
+    Ok(client
         .get(roku_url)
         .send()
-        .await
-        .unwrap()
+        .await?
+        .error_for_status()?
         .bytes()
-        .await
-        .unwrap();
-
-    base64::engine::general_purpose::STANDARD.encode(res_bytes)
+        .await?
+        .to_vec())
 }
 
-pub async fn roku_send_keypress(ip: &str, key: &str) -> serde_json::Value {
+pub async fn roku_send_keypress(ip: &str, key: &str) -> Result<serde_json::Value, RokuError> {
     post(ip, format!("keypress/{key}").as_str()).await
 }
 
-pub async fn roku_search(ip: &str, query: &str) -> serde_json::Value {
+pub async fn roku_search(ip: &str, query: &str) -> Result<serde_json::Value, RokuError> {
     post(ip, format!("search/browse?{query}=&matchAny=true").as_str()).await
 }
 
-pub async fn roku_launch_app(ip: &str, app_id: &str) -> serde_json::Value {
+pub async fn roku_launch_app(ip: &str, app_id: &str) -> Result<serde_json::Value, RokuError> {
     post(ip, format!("launch/{app_id}").as_str()).await
 }
 
-pub async fn post(ip: &str, query: &str) -> serde_json::Value {
+pub async fn post(ip: &str, query: &str) -> Result<serde_json::Value, RokuError> {
     let roku_url = format!("http://{ip}:8060/{query}");
     println!("roku url: {roku_url}");
     let client = reqwest::Client::new();
 
-    match client.post(&roku_url).send().await {
-        Ok(data) => println!("input: {data:?}"),
-        Err(err) => println!("Error! {err}"),
-    };
+    let data = client.post(&roku_url).send().await?.error_for_status()?;
+    println!("input: {data:?}");
 
-    json!({
+    Ok(json!({
         "success": true,
-    })
+    }))
 }
 
-pub async fn get(ip: &str, query: &str) -> String {
+pub async fn get(ip: &str, query: &str) -> Result<String, RokuError> {
     let roku_url = format!("http://{ip}:8060/{query}");
     let client = reqwest::Client::new();
 
-    client
+    Ok(client
         .get(roku_url)
         .send()
-        .await
-        .unwrap()
+        .await?
+        .error_for_status()?
         .text()
-        .await
-        .unwrap()
+        .await?)
 }
 
 pub async fn roku_ws() {
@@ -133,3 +188,84 @@ pub async fn roku_ws() {
     })
     .await;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_now_playing() {
+        let active_app: ActionApp = from_str(
+            r#"<active-app>
+                <app id="12" type="appl" version="4.1.218">Netflix</app>
+            </active-app>"#,
+        )
+        .unwrap();
+        let player: MediaPlayer = from_str(
+            r#"<player error="false" state="play">
+                <plugin bandwidth="10000 bps" id="12" name="Netflix"/>
+                <format audio="aac" captions="none" drm="none" video="mpeg4_10b"/>
+                <position>53463 ms</position>
+                <duration>5400000 ms</duration>
+                <is_live>false</is_live>
+            </player>"#,
+        )
+        .unwrap();
+        assert!(!is_tv_input(&active_app));
+        assert_eq!(
+            now_playing(active_app, player, None),
+            NowPlaying {
+                app_id: Some("12".to_string()),
+                app_name: Some("Netflix".to_string()),
+                state: PlaybackState::Playing,
+                position_ms: Some(53463),
+                duration_ms: Some(5400000),
+                title: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_now_playing_home_screen() {
+        let active_app: ActionApp = from_str(
+            r#"<active-app>
+                <app>Roku</app>
+                <screensaver id="55545" type="ssvr" version="2.0.1">Default screensaver</screensaver>
+            </active-app>"#,
+        )
+        .unwrap();
+        let player: MediaPlayer = from_str(r#"<player error="false" state="close"/>"#).unwrap();
+        assert_eq!(
+            now_playing(active_app, player, None),
+            NowPlaying {
+                app_name: Some("Roku".to_string()),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_tv_active_channel() {
+        let active_app: ActionApp = from_str(
+            r#"<active-app>
+                <app id="tvinput.dtv" type="tvin" version="1.0.0">Antenna TV</app>
+            </active-app>"#,
+        )
+        .unwrap();
+        let channel: TvActiveChannel = from_str(
+            r#"<tv-channel>
+                <channel>
+                    <number>14.3</number>
+                    <name>getTV</name>
+                    <program-title>Airwolf</program-title>
+                </channel>
+            </tv-channel>"#,
+        )
+        .unwrap();
+        assert!(is_tv_input(&active_app));
+        assert_eq!(
+            channel.channel.and_then(|channel| channel.program_title),
+            Some("Airwolf".to_string())
+        );
+    }
+}
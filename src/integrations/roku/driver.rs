@@ -0,0 +1,70 @@
+use {
+    super::client::{RokuError, roku_get_device_info, roku_launch_app, roku_send_keypress},
+    crate::integrations::iron_nest::{
+        driver::{DeviceDriver, DriverError, DriverFuture, StateFuture},
+        types::{Capability, Device, DeviceState},
+    },
+    futures::FutureExt,
+    std::time::Duration,
+};
+
+fn device_error(device: &Device) -> impl FnOnce(RokuError) -> DriverError + '_ {
+    move |e| DriverError::Device(format!("{}: {e}", device.label()))
+}
+
+pub struct RokuTvDriver;
+
+impl DeviceDriver for RokuTvDriver {
+    fn capabilities(&self) -> &'static [Capability] {
+        &[
+            Capability::OnOff,
+            Capability::Keypress,
+            Capability::LaunchApp,
+        ]
+    }
+
+    fn set_power<'a>(&'a self, device: &'a Device, on: bool) -> DriverFuture<'a> {
+        self.keypress(device, if on { "PowerOn" } else { "PowerOff" })
+    }
+
+    fn keypress<'a>(&'a self, device: &'a Device, key: &'a str) -> DriverFuture<'a> {
+        async move {
+            roku_send_keypress(&device.ip, key)
+                .await
+                .map(|_| ())
+                .map_err(device_error(device))
+        }
+        .boxed()
+    }
+
+    fn launch_app<'a>(&'a self, device: &'a Device, app_id: &'a str) -> DriverFuture<'a> {
+        async move {
+            roku_launch_app(&device.ip, app_id)
+                .await
+                .map(|_| ())
+                .map_err(device_error(device))
+        }
+        .boxed()
+    }
+
+    /// Anything other than `PowerOn` (`DisplayOff`, `Ready`, `Headless`) means
+    /// the screen is off.
+    fn get_state<'a>(&'a self, device: &'a Device) -> StateFuture<'a> {
+        async move {
+            let device_info = roku_get_device_info(&device.ip)
+                .await
+                .map_err(device_error(device))?;
+            Ok(Some(DeviceState {
+                on: device_info.power_mode == "PowerOn",
+                ..Default::default()
+            }))
+        }
+        .boxed()
+    }
+
+    /// A TV coming out of standby can take several seconds before it reports
+    /// `PowerOn`.
+    fn read_back_window(&self) -> Duration {
+        Duration::from_secs(10)
+    }
+}
@@ -0,0 +1,173 @@
+use {
+    super::{roku_discover, roku_get_device_info, roku_now_playing},
+    crate::integrations::iron_nest::{
+        events::EventBus,
+        extract_ip, get_devices_by_type, insert_devices_into_db, normalize_mac, refresh_roku_apps,
+        supervisor::{Every, Integration, IntegrationFuture, PollFuture},
+        types::{Device, DeviceType, IntegrationSettings},
+        update_now_playing,
+    },
+    chrono::Utc,
+    futures::{FutureExt, future::join_all},
+    sqlx::PgPool,
+    std::time::Duration,
+};
+
+/// How long a Roku gets to answer the now-playing queries, so one that's
+/// unplugged doesn't hold up the others.
+const NOW_PLAYING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Polls what every known Roku is playing, and rediscovers Rokus over SSDP and
+/// refreshes their channel catalogs every hour by default.
+pub struct RokuIntegration {
+    pool: PgPool,
+    event_bus: EventBus,
+    discovery: Every,
+}
+
+impl RokuIntegration {
+    pub fn new(pool: PgPool, event_bus: EventBus) -> Self {
+        Self {
+            pool,
+            event_bus,
+            discovery: Every::new(Duration::from_secs(3600)),
+        }
+    }
+
+    async fn discover(&self) -> anyhow::Result<usize> {
+        let roku_devices = roku_discover().await;
+        let mut devices: Vec<Device> = Vec::new();
+
+        for device in roku_devices.iter() {
+            let Ok(ip) = extract_ip(&device.location) else {
+                self.event_bus.integration_error(
+                    self.name(),
+                    format!("Invalid location {}", device.location),
+                );
+                continue;
+            };
+            let device_info = match roku_get_device_info(&ip).await {
+                Ok(device_info) => device_info,
+                Err(e) => {
+                    self.event_bus.integration_error(
+                        self.name(),
+                        format!("Failed to get device info from {ip}: {e}"),
+                    );
+                    continue;
+                }
+            };
+            let power_state = if device_info.power_mode == "PowerOn" {
+                1
+            } else {
+                0
+            };
+            let mac_address = device_info
+                .wifi_mac
+                .as_deref()
+                .or(device_info.ethernet_mac.as_deref())
+                .and_then(normalize_mac);
+            devices.push(Device {
+                id: 0,
+                name: device_info.user_device_name,
+                device_type: DeviceType::RokuTv,
+                ip,
+                power_state,
+                battery_percentage: 0,
+                last_seen: Utc::now(),
+                mac_address,
+                child_id: None,
+                room_id: None,
+                tags: Vec::new(),
+                display_name: None,
+                icon: None,
+                online: true,
+            });
+        }
+
+        insert_devices_into_db(&self.pool, &self.event_bus, &devices).await?;
+        Ok(devices.len())
+    }
+
+    async fn refresh_apps(&self, device: &Device) {
+        if let Err(e) = refresh_roku_apps(&self.pool, device).await {
+            self.event_bus.integration_error(
+                self.name(),
+                format!("Failed to refresh the apps of {}: {e}", device.ip),
+            );
+        }
+    }
+
+    async fn poll_now_playing(&self, device: &Device) {
+        let now_playing =
+            match tokio::time::timeout(NOW_PLAYING_TIMEOUT, roku_now_playing(&device.ip)).await {
+                Ok(Ok(now_playing)) => now_playing,
+                Ok(Err(e)) => {
+                    log::warn!("Failed to get now playing from {}: {e}", device.ip);
+                    return;
+                }
+                Err(_) => {
+                    log::warn!("Timed out getting now playing from {}", device.ip);
+                    return;
+                }
+            };
+        if let Err(e) =
+            update_now_playing(&self.pool, &self.event_bus, device.id, &now_playing).await
+        {
+            self.event_bus.integration_error(
+                self.name(),
+                format!("Failed to store now playing of {}: {e}", device.ip),
+            );
+        }
+    }
+}
+
+impl Integration for RokuIntegration {
+    fn name(&self) -> &'static str {
+        "roku"
+    }
+
+    fn configure(&self, settings: &IntegrationSettings) {
+        self.discovery.set_period(
+            settings
+                .discovery_interval()
+                .unwrap_or(settings.poll_interval()),
+        );
+    }
+
+    fn start(&self) -> IntegrationFuture<'_> {
+        async move {
+            self.discovery.reset();
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn poll(&self) -> PollFuture<'_> {
+        async move {
+            let discovery_due = self.discovery.due();
+            let discovered = if discovery_due {
+                self.discover().await?
+            } else {
+                0
+            };
+            let rokus = get_devices_by_type(&self.pool, DeviceType::RokuTv).await?;
+            if discovery_due {
+                join_all(
+                    rokus
+                        .iter()
+                        .filter(|device| device.online)
+                        .map(|device| self.refresh_apps(device)),
+                )
+                .await;
+            }
+            join_all(rokus.iter().map(|device| self.poll_now_playing(device))).await;
+            Ok(discovered)
+        }
+        .boxed()
+    }
+
+    fn refresh(&self) -> PollFuture<'_> {
+        self.discovery.reset();
+        self.poll()
+    }
+}
@@ -13,21 +13,133 @@ pub struct ActionApp {
     pub app: Vec<App>,
 }
 
+/// The home screen is reported without an id or type.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct App {
-    pub id: String,
-    #[serde(rename = "type")]
-    pub app_type: String,
-    #[serde(rename = "$value")]
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(rename = "type", default)]
+    pub app_type: Option<String>,
+    #[serde(rename = "$value", default)]
     pub value: String,
 }
 
+/// Response of `query/media-player`. Every element is missing while nothing
+/// is playing.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct MediaPlayer {
+    pub state: String,
+    #[serde(default)]
+    pub plugin: Option<MediaPlugin>,
+    /// e.g. "53463 ms".
+    #[serde(default)]
+    pub position: Option<String>,
+    #[serde(default)]
+    pub duration: Option<String>,
+    #[serde(default)]
+    pub is_live: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct MediaPlugin {
+    pub id: String,
+    pub name: String,
+}
+
+/// Response of `query/tv-active-channel`, only answered by Roku TVs.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct TvActiveChannel {
+    #[serde(default)]
+    pub channel: Option<TvChannel>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct TvChannel {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub program_title: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaybackState {
+    #[default]
+    Idle,
+    Buffering,
+    Playing,
+    Paused,
+}
+
+impl PlaybackState {
+    /// Maps the media player's `state` attribute.
+    pub fn from_roku(state: &str) -> Self {
+        match state {
+            "play" => Self::Playing,
+            "pause" => Self::Paused,
+            "buffer" | "startup" | "open" => Self::Buffering,
+            _ => Self::Idle,
+        }
+    }
+}
+
+impl std::fmt::Display for PlaybackState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Idle => write!(f, "Idle"),
+            Self::Buffering => write!(f, "Buffering"),
+            Self::Playing => write!(f, "Playing"),
+            Self::Paused => write!(f, "Paused"),
+        }
+    }
+}
+
+/// What a Roku is showing, stored in `device.now_playing` by the Roku job.
+/// `app_id` is `None` on the home screen.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Clone)]
+pub struct NowPlaying {
+    pub app_id: Option<String>,
+    pub app_name: Option<String>,
+    pub state: PlaybackState,
+    pub position_ms: Option<u64>,
+    pub duration_ms: Option<u64>,
+    /// Program title, reported by the TV tuner only.
+    pub title: Option<String>,
+}
+
+impl NowPlaying {
+    /// Whether anything but the playback position differs, which is what's
+    /// worth an event.
+    pub fn changed_from(&self, other: &Self) -> bool {
+        self.app_id != other.app_id
+            || self.app_name != other.app_name
+            || self.state != other.state
+            || self.duration_ms != other.duration_ms
+            || self.title != other.title
+    }
+}
+
+/// A Roku's last known `NowPlaying`, for the dashboard.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+pub struct RokuNowPlaying {
+    pub device_id: i64,
+    pub name: String,
+    #[cfg_attr(feature = "ssr", sqlx(json))]
+    pub now_playing: NowPlaying,
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct RokuDeviceInfo {
     #[serde(rename = "user-device-name")]
     pub user_device_name: String,
     #[serde(rename = "power-mode")]
     pub power_mode: String,
+    #[serde(rename = "wifi-mac", default)]
+    pub wifi_mac: Option<String>,
+    #[serde(rename = "ethernet-mac", default)]
+    pub ethernet_mac: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -51,17 +163,24 @@ pub struct AppsApp {
     pub version: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct AppsWithIcon {
-    #[serde(rename = "app", default)]
-    pub apps: Vec<AppsAppWithIcon>,
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct AppsAppWithIcon {
+/// A channel installed on a Roku, as stored in `roku_app` by the Roku job.
+/// `icon_cid` points to the icon in `ipld_blobs`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RokuApp {
+    pub device_id: i64,
+    pub app_id: String,
     pub name: String,
-    pub id: String,
     pub app_type: String,
     pub version: String,
-    pub icon: String,
+    pub icon_cid: Option<String>,
+}
+
+impl RokuApp {
+    /// Where the icon is served from. Icons are addressed by their content, so
+    /// the response can be cached forever.
+    pub fn icon_url(&self) -> Option<String> {
+        self.icon_cid
+            .as_ref()
+            .map(|cid| format!("/api/roku/icons/{cid}"))
+    }
 }
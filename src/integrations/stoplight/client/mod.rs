@@ -13,8 +13,11 @@ pub async fn stoplight_get_state() -> Result<Stoplight, Box<dyn Error>> {
     let js = jetstream::new(nats);
     let kv = js.get_key_value(STOPLIGHT_BUCKET).await?;
 
-    let stoplight_value = kv.get(STOPLIGHT_SUBJECT).await.unwrap().unwrap();
-    let value: Stoplight = serde_json::from_slice(&stoplight_value).unwrap();
+    let stoplight_value = kv
+        .get(STOPLIGHT_SUBJECT)
+        .await?
+        .ok_or("stoplight state hasn't been set")?;
+    let value: Stoplight = serde_json::from_slice(&stoplight_value)?;
     Ok(value)
 }
 
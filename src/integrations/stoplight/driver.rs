@@ -0,0 +1,33 @@
+use {
+    super::client::toggle_stoplight,
+    crate::integrations::iron_nest::{
+        driver::{DeviceDriver, DriverError, DriverFuture},
+        types::{Capability, Device},
+    },
+    futures::FutureExt,
+};
+
+/// The stoplight has a single lamp per color, so `set_color` toggles the lamp
+/// named by `color` (`red`, `yellow` or `green`).
+pub struct StoplightDriver;
+
+impl DeviceDriver for StoplightDriver {
+    fn capabilities(&self) -> &'static [Capability] {
+        &[Capability::Color]
+    }
+
+    fn set_color<'a>(&'a self, _device: &'a Device, color: &'a str) -> DriverFuture<'a> {
+        async move {
+            if !matches!(color, "red" | "yellow" | "green") {
+                return Err(DriverError::InvalidArgs(format!(
+                    "Unknown stoplight color: {color}"
+                )));
+            }
+            toggle_stoplight(color)
+                .await
+                .map(|_| ())
+                .map_err(|e| DriverError::Device(e.to_string()))
+        }
+        .boxed()
+    }
+}
@@ -1,160 +1,482 @@
 use {
-    super::types::{DeviceData, GetSysInfo, TPLinkDiscoveryRes, TPLinkDiscoverySysInfo},
+    super::types::{
+        DeviceData, EmeterRealtime, GetSysInfo, KasaCloud, KasaFamily, LightStateChange,
+        PreferredState, TPLinkDiscoveryRes, TPLinkDiscoverySysInfo, WifiNetwork,
+    },
+    futures::{StreamExt, stream},
     log::{info, trace, warn},
+    protocol::{KEY, MAX_FRAME_LEN, decrypt, encrypt, read_frame, write_frame},
+    serde::{Deserialize, Serialize, de::DeserializeOwned},
     serde_json::{Value, json},
     std::{
-        error::Error,
+        collections::HashSet,
         io,
-        net::{IpAddr, Ipv4Addr},
+        net::{AddrParseError, IpAddr, Ipv4Addr, SocketAddr},
     },
     tokio::{
-        io::{AsyncReadExt, AsyncWriteExt},
         net::{TcpStream, UdpSocket},
-        time::{Duration, timeout},
+        time::{Duration, Instant, sleep_until, timeout},
     },
+    transport::{KLAP_DISCOVERY_PORT, KLAP_DISCOVERY_QUERY, Transport},
 };
 
-const KEY: u8 = 0xAB;
+pub(super) mod klap;
+pub(super) mod protocol;
+mod smart;
+mod transport;
+
+pub use {klap::Credentials, transport::set_credentials};
+
+const PORT: u16 = 9999;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Covers sending the request and reading the whole response.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Discovery requests are sent this many targets at a time, so scanning a
+/// range doesn't flood the network or overrun the socket's receive buffer.
+const PROBE_BATCH_SIZE: usize = 64;
+const PROBE_BATCH_INTERVAL: Duration = Duration::from_millis(50);
+/// Devices found over KLAP are asked for their sysinfo this many at a time.
+const SYSINFO_CONCURRENCY: usize = 8;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TplinkError {
+    #[error("Invalid IP address: {0}")]
+    InvalidIp(#[from] AddrParseError),
+
+    #[error("Connection failed: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Timed out {0}")]
+    Timeout(&'static str),
+
+    #[error("Response of {0} bytes is too large")]
+    FrameTooLarge(usize),
+
+    #[error("Invalid response: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("{method} failed with err_code {err_code}: {err_msg}")]
+    Device {
+        method: String,
+        err_code: i64,
+        err_msg: String,
+    },
 
-pub async fn discover_devices() -> Result<Vec<DeviceData>, Box<dyn Error + Send>> {
-    let port = 9999;
-    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port))
-        .await
-        .unwrap();
-    socket.set_broadcast(true).unwrap();
+    #[error("{0}")]
+    InvalidInput(String),
+
+    #[error("HTTP request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("KLAP {0}")]
+    Klap(String),
+
+    #[error("The device didn't accept the TP-Link account, set it on the integrations page")]
+    Auth,
+}
+
+impl TplinkError {
+    /// The device doesn't have the module or method, e.g. a plug without an
+    /// emeter. -1 and -2 are "module not support" and "method not support".
+    pub fn is_unsupported(&self) -> bool {
+        matches!(
+            self,
+            Self::Device {
+                err_code: -1 | -2,
+                ..
+            }
+        )
+    }
+}
+
+/// Broadcasts a sysinfo request on the local network, on the legacy port and
+/// the KLAP one, and also sends it to each of `scan_hosts` for devices on
+/// subnets the broadcast doesn't reach. Collects the devices that answer
+/// within 2.5 seconds of each other.
+pub async fn discover_devices(scan_hosts: &[Ipv4Addr]) -> Result<Vec<DeviceData>, TplinkError> {
+    let targets = std::iter::once(Ipv4Addr::BROADCAST)
+        .chain(scan_hosts.iter().copied())
+        .map(|ip| SocketAddr::from((ip, PORT)))
+        .collect::<Vec<_>>();
+    discover_devices_at(&targets, Duration::from_millis(2500)).await
+}
+
+/// Sends the discovery request to each of `targets`, broadcast or unicast,
+/// and collects answers until none arrive for `timeout_duration`. The same
+/// addresses are asked on the KLAP discovery port, and devices that only
+/// answer there are asked for their sysinfo over KLAP. A device that answers
+/// more than once, e.g. to both the broadcast and a scanned range, is only
+/// returned once.
+pub async fn discover_devices_at(
+    targets: &[SocketAddr],
+    timeout_duration: Duration,
+) -> Result<Vec<DeviceData>, TplinkError> {
+    // Devices answer to the port the request came from
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    socket.set_broadcast(true)?;
     let request = TPLinkDiscoveryRes {
         system: TPLinkDiscoverySysInfo {
             get_sysinfo: GetSysInfo::Empty(()),
         },
     };
-    let msg_bytes =
-        serde_json::to_vec(&request).expect("Should be able to serialize hardcoded data w/o error");
+    let msg_bytes = serde_json::to_vec(&request)?;
     let discover_msg = encrypt(&msg_bytes, KEY);
 
-    let broadcast_addr = (Ipv4Addr::BROADCAST, port);
-    socket.send_to(&discover_msg, broadcast_addr).await.unwrap();
+    let mut buf = vec![0; MAX_FRAME_LEN];
 
-    let mut buf = [0; 2048];
-    let timeout_duration = Duration::from_millis(2500);
+    let mut batches = targets.chunks(PROBE_BATCH_SIZE);
+    let mut sending = true;
+    let mut next_batch = Instant::now();
+    let mut deadline = Instant::now() + timeout_duration;
 
     let mut devices = Vec::with_capacity(20);
+    let mut legacy_ips = HashSet::new();
+    let mut klap_ips = HashSet::new();
+    let mut klap_devices = Vec::new();
     loop {
-        match timeout(timeout_duration, socket.recv_from(&mut buf)).await {
-            Ok(Ok((num_bytes, src_addr))) => {
-                let incoming_data = decrypt(&buf, KEY);
-                let incoming_msg_result =
-                    serde_json::from_slice::<TPLinkDiscoveryRes>(&incoming_data[..num_bytes]);
-
-                match incoming_msg_result {
-                    Ok(msg) => match msg.system.get_sysinfo {
-                        GetSysInfo::TPLinkDiscoveryData(mut get_sysinfo) => {
-                            info!(
-                                "Smart Plug or Dimmer from {}: {}",
-                                src_addr, get_sysinfo.alias
-                            );
-                            get_sysinfo.ip = Some(src_addr.ip());
-
-                            if get_sysinfo.model == "ES20M(US)" || get_sysinfo.model == "KS230(US)"
-                            {
-                                devices.push(DeviceData::SmartDimmer(get_sysinfo));
-                            } else {
-                                devices.push(DeviceData::SmartPlug(get_sysinfo));
+        tokio::select! {
+            () = sleep_until(next_batch), if sending => {
+                let Some(batch) = batches.next() else {
+                    sending = false;
+                    continue;
+                };
+                for target in batch {
+                    send_probe(&socket, &discover_msg, *target).await;
+                }
+                next_batch = Instant::now() + PROBE_BATCH_INTERVAL;
+                deadline = Instant::now() + timeout_duration;
+            }
+            received = socket.recv_from(&mut buf) => match received {
+                Ok((num_bytes, src_addr)) if src_addr.port() == KLAP_DISCOVERY_PORT => {
+                    match transport::parse_discovery(&buf[..num_bytes]) {
+                        Ok(result) => match result.klap() {
+                            Some(klap) if klap_ips.insert(src_addr.ip()) => {
+                                klap_devices.push((src_addr.ip(), klap, result));
+                            }
+                            Some(_) => {}
+                            None => info!(
+                                "{} from {src_addr} doesn't use KLAP, skipping it",
+                                result.device_model
+                            ),
+                        },
+                        Err(e) => warn!("Error parsing KLAP discovery response from {src_addr}: {e}"),
+                    }
+                    deadline = Instant::now() + timeout_duration;
+                }
+                Ok((num_bytes, src_addr)) => {
+                    let incoming_data = decrypt(&buf[..num_bytes], KEY);
+                    let incoming_msg_result =
+                        serde_json::from_slice::<TPLinkDiscoveryRes>(&incoming_data);
+
+                    match incoming_msg_result {
+                        Ok(msg) => {
+                            transport::set(src_addr.ip(), Transport::Legacy);
+                            if legacy_ips.insert(src_addr.ip()) {
+                                devices.extend(device_data(msg.system.get_sysinfo, src_addr.ip()));
                             }
                         }
-                        GetSysInfo::TPLinkSmartLightData(mut get_sysinfo) => {
-                            info!("Smart Light from {}: {}", src_addr, get_sysinfo.alias);
-                            get_sysinfo.ip = Some(src_addr.ip());
-                            devices.push(DeviceData::SmartLight(get_sysinfo));
-                        }
-                        GetSysInfo::TPLinkSmartPowerStripData(mut get_sysinfo) => {
-                            info!("Smart Power Strip from {}: {}", src_addr, get_sysinfo.alias);
-                            get_sysinfo.ip = Some(src_addr.ip());
-                            devices.push(DeviceData::SmartPowerStrip(get_sysinfo));
-                        }
-                        GetSysInfo::Empty(()) => trace!("ignoring GetSysInfo::Empty(())"),
-                        GetSysInfo::CatchAll(raw_json) => {
-                            warn!("Catch-all variant triggered, raw JSON: {:?}", raw_json);
+                        Err(e) => {
+                            warn!(
+                                "Error parsing broadcast response from {src_addr}: {e}, {:?}",
+                                String::from_utf8_lossy(&incoming_data)
+                            );
                         }
-                    },
-                    Err(e) => {
-                        let valid_length = incoming_data
-                            .iter()
-                            .take_while(|&&byte| std::str::from_utf8(&[byte]).is_ok())
-                            .count();
-
-                        let valid_data = &incoming_data[..valid_length];
-                        let string_value =
-                            std::str::from_utf8(valid_data).expect("Failed to convert to UTF-8");
-
-                        warn!("Error parsing broadcast response: {e}, {:?}", string_value);
                     }
+                    deadline = Instant::now() + timeout_duration;
                 }
-            }
-            Ok(Err(e)) => {
-                warn!("Error receiving broadcast response: {}", e);
-                break;
-            }
-            Err(_) => {
+                Err(e) => {
+                    warn!("Error receiving broadcast response: {}", e);
+                    break;
+                }
+            },
+            () = sleep_until(deadline) => {
                 trace!("Timeout reached, no more responses.");
                 break;
             }
         }
     }
+
+    // Older firmware answers on both ports, keep using the legacy protocol
+    // for those
+    let mut klap_only = Vec::new();
+    for (ip, klap, result) in klap_devices {
+        if legacy_ips.contains(&ip) {
+            continue;
+        }
+        if klap.smart && result.device_type != "SMART.TAPOPLUG" {
+            info!(
+                "{} {} at {ip} isn't supported yet",
+                result.device_type, result.device_model
+            );
+            continue;
+        }
+        klap_only.push((ip, klap, result));
+    }
+    let klap_found = stream::iter(klap_only)
+        .map(|(ip, klap, result)| async move {
+            transport::set(ip, Transport::Klap(klap));
+            match tplink_get_sysinfo(&ip.to_string()).await {
+                Ok(sysinfo) => device_data(sysinfo, ip),
+                Err(e) => {
+                    warn!(
+                        "Failed to get sysinfo of {} at {ip}: {e}",
+                        result.device_model
+                    );
+                    None
+                }
+            }
+        })
+        .buffer_unordered(SYSINFO_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+    devices.extend(klap_found.into_iter().flatten());
     Ok(devices)
 }
 
-pub async fn send(ip: &str, json: serde_json::Value) -> io::Result<Value> {
-    let _ip: IpAddr = match ip.parse() {
-        Ok(addr) => addr,
-        Err(e) => {
-            eprintln!("Failed to parse IP address '{ip}': {e}");
-            return Err(io::Error::new(io::ErrorKind::InvalidInput, e));
+/// Asks `target` for its sysinfo on the legacy port and the KLAP one. A
+/// failed send, e.g. to a scanned host without a route, is logged so it
+/// doesn't end the whole discovery.
+async fn send_probe(socket: &UdpSocket, discover_msg: &[u8], target: SocketAddr) {
+    let klap_target = SocketAddr::new(target.ip(), KLAP_DISCOVERY_PORT);
+    for (msg, addr) in [
+        (discover_msg, target),
+        (&KLAP_DISCOVERY_QUERY[..], klap_target),
+    ] {
+        if let Err(e) = socket.send_to(msg, addr).await {
+            warn!("Error sending discovery request to {addr}: {e}");
         }
-    };
+    }
+}
+
+/// Sorts a discovered device by the shape of its sysinfo.
+fn device_data(sysinfo: GetSysInfo, ip: IpAddr) -> Option<DeviceData> {
+    match sysinfo {
+        GetSysInfo::TPLinkDiscoveryData(mut get_sysinfo) => {
+            info!("Smart Plug or Dimmer from {}: {}", ip, get_sysinfo.alias);
+            get_sysinfo.ip = Some(ip);
+
+            if get_sysinfo.model == "ES20M(US)" || get_sysinfo.model == "KS230(US)" {
+                Some(DeviceData::SmartDimmer(get_sysinfo))
+            } else {
+                Some(DeviceData::SmartPlug(get_sysinfo))
+            }
+        }
+        GetSysInfo::TPLinkSmartLightData(mut get_sysinfo) => {
+            info!("Smart Light from {}: {}", ip, get_sysinfo.alias);
+            get_sysinfo.ip = Some(ip);
+            Some(DeviceData::SmartLight(get_sysinfo))
+        }
+        GetSysInfo::TPLinkSmartPowerStripData(mut get_sysinfo) => {
+            info!("Smart Power Strip from {}: {}", ip, get_sysinfo.alias);
+            get_sysinfo.ip = Some(ip);
+            Some(DeviceData::SmartPowerStrip(get_sysinfo))
+        }
+        GetSysInfo::Empty(()) => {
+            trace!("ignoring GetSysInfo::Empty(())");
+            None
+        }
+        GetSysInfo::CatchAll(raw_json) => {
+            warn!("Catch-all variant triggered, raw JSON: {:?}", raw_json);
+            None
+        }
+    }
+}
+
+/// Sends a legacy `{module: {method: params}}` request over whichever
+/// protocol the device speaks. Devices that weren't discovered since startup
+/// are tried on the legacy port first.
+pub async fn send(ip: &str, json: serde_json::Value) -> Result<Value, TplinkError> {
+    let ip: IpAddr = ip.parse()?;
+    match transport::get(ip) {
+        Some(Transport::Klap(klap)) => transport::klap_request(ip, klap, &json).await,
+        Some(Transport::Legacy) => request(SocketAddr::new(ip, PORT), &json).await,
+        None => match request(SocketAddr::new(ip, PORT), &json).await {
+            // KLAP firmware doesn't listen on the legacy port at all
+            Err(TplinkError::Io(e)) if e.kind() == io::ErrorKind::ConnectionRefused => {
+                let Some(klap) = transport::probe(ip).await else {
+                    return Err(TplinkError::Io(e));
+                };
+                transport::set(ip, Transport::Klap(klap));
+                transport::klap_request(ip, klap, &json).await
+            }
+            result => {
+                if result.is_ok() {
+                    transport::set(ip, Transport::Legacy);
+                }
+                result
+            }
+        },
+    }
+}
 
-    let mut stream = TcpStream::connect((_ip, 9999)).await?;
+/// Sends one framed request over a new connection and reads the response.
+async fn request(addr: SocketAddr, json: &Value) -> Result<Value, TplinkError> {
+    let mut stream = timeout(CONNECT_TIMEOUT, TcpStream::connect(addr))
+        .await
+        .map_err(|_| TplinkError::Timeout("connecting"))??;
 
-    let msg_bytes =
-        serde_json::to_vec(&json).expect("Should be able to serialize hardcoded data w/o error");
-    let discover_msg = encrypt_with_header(&msg_bytes, KEY);
+    let msg_bytes = serde_json::to_vec(json)?;
+    let response = timeout(REQUEST_TIMEOUT, async {
+        write_frame(&mut stream, &msg_bytes).await?;
+        read_frame(&mut stream).await
+    })
+    .await
+    .map_err(|_| TplinkError::Timeout("waiting for a response"))??;
+    trace!("{addr} responded with {} bytes", response.len());
+    Ok(serde_json::from_slice::<Value>(&response)?)
+}
 
-    stream.write_all(&discover_msg).await.unwrap();
+pub async fn tplink_get_sysinfo(ip: &str) -> Result<GetSysInfo, TplinkError> {
+    let res = send(ip, json!({"system":{"get_sysinfo":{}}})).await?;
+    Ok(serde_json::from_value::<TPLinkDiscoveryRes>(res)?
+        .system
+        .get_sysinfo)
+}
 
-    let mut buf = [0u8; 1024];
-    let bytes_read = stream.read(&mut buf).await?;
-    println!("bytes_read: {bytes_read}");
-    let decrypted_msg = decrypt_with_header(&buf, KEY);
-    let msg = serde_json::from_slice::<Value>(&decrypted_msg).unwrap();
-    println!("msg: {msg:?}");
-    Ok(msg)
+/// Sends a command and fails if any module in the response reports a non-zero
+/// `err_code`, e.g. `{"system":{"set_relay_state":{"err_code":-1,"err_msg":"..."}}}`.
+pub async fn send_command(ip: &str, json: serde_json::Value) -> Result<(), TplinkError> {
+    let res = send(ip, json).await?;
+    check_err_code(&res)
 }
 
-pub async fn tplink_set_alias(ip: &str, alias: &str) {
-    send(ip, json!({"system":{"set_dev_alias":{"alias": alias}}}))
-        .await
-        .unwrap();
+fn check_err_code(res: &Value) -> Result<(), TplinkError> {
+    for (module, methods) in res.as_object().into_iter().flatten() {
+        if module == "context" {
+            continue;
+        }
+        // An unsupported module reports the error on the module itself
+        let results = std::iter::once((module.clone(), methods)).chain(
+            methods
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(|(method, result)| (format!("{module}.{method}"), result)),
+        );
+        for (name, result) in results {
+            let err_code = result["err_code"].as_i64().unwrap_or(0);
+            if err_code != 0 {
+                let err_msg = result["err_msg"].as_str().unwrap_or("unknown error");
+                return Err(TplinkError::Device {
+                    method: name,
+                    err_code,
+                    err_msg: err_msg.to_string(),
+                });
+            }
+        }
+    }
+    Ok(())
 }
 
-pub async fn tplink_reboot(ip: &str) {
-    send(ip, json!({"system":{"reboot":{"delay": 1}}}))
-        .await
-        .unwrap();
+pub async fn tplink_set_alias(ip: &str, alias: &str) -> Result<(), TplinkError> {
+    send_command(ip, json!({"system":{"set_dev_alias":{"alias": alias}}})).await
 }
 
-pub async fn tplink_turn_plug_on(ip: &str) {
-    send(ip, json!({"system":{"set_relay_state":{"state": 1}}}))
-        .await
-        .unwrap();
+pub async fn tplink_set_light_alias(ip: &str, alias: &str) -> Result<(), TplinkError> {
+    send_command(
+        ip,
+        json!({"smartlife.iot.common.sysinfo":{"set_dev_alias":{"alias": alias}}}),
+    )
+    .await
 }
 
-pub async fn tplink_turn_plug_off(ip: &str) {
-    send(ip, json!({"system":{"set_relay_state":{"state": 0}}}))
-        .await
-        .unwrap();
+pub async fn tplink_set_smart_strip_socket_alias(
+    ip: &str,
+    id: &str,
+    alias: &str,
+) -> Result<(), TplinkError> {
+    send_command(
+        ip,
+        json!(
+        {
+            "context": {
+                "child_ids": [id]
+            },
+            "system":{"set_dev_alias":{"alias": alias}}
+        }),
+    )
+    .await
+}
+
+/// Calls `method` of `module` and returns its result, failing on a non-zero
+/// `err_code`.
+async fn query(ip: &str, module: &str, method: &str, params: Value) -> Result<Value, TplinkError> {
+    let mut res = send(ip, json!({module: {method: params}})).await?;
+    check_err_code(&res)?;
+    Ok(res[module][method].take())
+}
+
+pub async fn tplink_reboot(ip: &str, family: KasaFamily) -> Result<(), TplinkError> {
+    query(ip, family.system_module(), "reboot", json!({"delay": 1})).await?;
+    Ok(())
+}
+
+/// Turns the status LED of a plug, dimmer or power strip off or back on.
+pub async fn tplink_set_led_off(ip: &str, off: bool) -> Result<(), TplinkError> {
+    send_command(
+        ip,
+        json!({"system":{"set_led_off":{"off": i32::from(off)}}}),
+    )
+    .await
+}
+
+pub async fn tplink_get_cloud_info(ip: &str, family: KasaFamily) -> Result<KasaCloud, TplinkError> {
+    let info = query(ip, family.cloud_module(), "get_info", json!({})).await?;
+    Ok(serde_json::from_value(info)?)
+}
+
+/// Removes the device from its TP-Link account. It keeps working locally.
+pub async fn tplink_cloud_unbind(ip: &str, family: KasaFamily) -> Result<(), TplinkError> {
+    query(ip, family.cloud_module(), "unbind", json!({})).await?;
+    Ok(())
+}
+
+/// Networks the device can see, for picking one to join.
+pub async fn tplink_get_wifi_networks(
+    ip: &str,
+    family: KasaFamily,
+) -> Result<Vec<WifiNetwork>, TplinkError> {
+    let scan = query(
+        ip,
+        family.netif_module(),
+        "get_scaninfo",
+        json!({"refresh": 1}),
+    )
+    .await?;
+    if scan["ap_list"].is_null() {
+        return Ok(Vec::new());
+    }
+    Ok(serde_json::from_value(scan["ap_list"].clone())?)
+}
+
+/// Has the device join `network`. A factory-reset device runs its own access
+/// point and is at 192.168.0.1 on it, and leaves it once it accepts the
+/// network. The password is only obfuscated on the legacy port.
+pub async fn tplink_set_wifi(
+    ip: &str,
+    family: KasaFamily,
+    network: &WifiNetwork,
+    password: &str,
+) -> Result<(), TplinkError> {
+    let params = json!({
+        "ssid": network.ssid,
+        "password": password,
+        "key_type": network.key_type,
+    });
+    query(ip, family.netif_module(), "set_stainfo", params).await?;
+    Ok(())
+}
+
+pub async fn tplink_turn_plug_on(ip: &str) -> Result<(), TplinkError> {
+    send_command(ip, json!({"system":{"set_relay_state":{"state": 1}}})).await
+}
+
+pub async fn tplink_turn_plug_off(ip: &str) -> Result<(), TplinkError> {
+    send_command(ip, json!({"system":{"set_relay_state":{"state": 0}}})).await
 }
 
-pub async fn tplink_turn_smart_strip_socket_off(ip: &str, id: &str) {
-    send(
+pub async fn tplink_turn_smart_strip_socket_off(ip: &str, id: &str) -> Result<(), TplinkError> {
+    send_command(
         ip,
         json!(
         {
@@ -165,11 +487,19 @@ pub async fn tplink_turn_smart_strip_socket_off(ip: &str, id: &str) {
         }),
     )
     .await
-    .unwrap();
 }
 
-pub async fn tplink_turn_smart_strip_socket_on(ip: &str, id: &str) {
-    send(
+/// Turns every outlet of a power strip on or off at once.
+pub async fn tplink_set_all_smart_strip_sockets(ip: &str, on: bool) -> Result<(), TplinkError> {
+    send_command(
+        ip,
+        json!({"system":{"set_relay_state":{"state": i32::from(on)}}}),
+    )
+    .await
+}
+
+pub async fn tplink_turn_smart_strip_socket_on(ip: &str, id: &str) -> Result<(), TplinkError> {
+    send_command(
         ip,
         json!(
         {
@@ -180,133 +510,229 @@ pub async fn tplink_turn_smart_strip_socket_on(ip: &str, id: &str) {
         }),
     )
     .await
-    .unwrap();
 }
 
-pub async fn tplink_set_dimmer_brightness(ip: &str, brightness: &u8) {
-    send(
+pub async fn tplink_set_dimmer_brightness(ip: &str, brightness: &u8) -> Result<(), TplinkError> {
+    send_command(
         ip,
         json!({"smartlife.iot.dimmer":{"set_dimmer_transition":{"brightness": brightness, "duration": 1}}}),
     )
     .await
-    .unwrap();
 }
 
-pub async fn tplink_set_dimmer_inactivity_timeout(ip: &str, timeout: &u8) {
-    send(
+pub async fn tplink_set_dimmer_inactivity_timeout(
+    ip: &str,
+    timeout: &u8,
+) -> Result<(), TplinkError> {
+    send_command(
         ip,
         json!({"smartlife.iot.dimmer":{"set_cold_time": {"cold_time": timeout}}}),
     )
     .await
-    .unwrap();
 }
 
 // https://github.com/python-kasa/python-kasa/blob/123ea107b1e7536bc5dfc8b93111cc5c7e8d066b/tests/fakeprotocol_iot.py#L445
 const LIGHT_SERVICE: &str = "smartlife.iot.smartbulb.lightingservice";
 
-pub async fn tplink_turn_light_on_off(ip: &str, state: u8) {
-    send(
+pub async fn tplink_turn_light_on_off(ip: &str, state: u8) -> Result<(), TplinkError> {
+    send_command(
         ip,
         json!({LIGHT_SERVICE:{"transition_light_state":{"on_off":state,"transition_period":0}}}),
     )
     .await
-    .unwrap();
 }
 
-pub async fn tplink_set_light_brightness(ip: &str, brightness: u8) {
-    send(ip, json!({LIGHT_SERVICE:{"transition_light_state":{"brightness":brightness,"transition_period":0}}}))
+pub async fn tplink_set_light_brightness(ip: &str, brightness: u8) -> Result<(), TplinkError> {
+    send_command(ip, json!({LIGHT_SERVICE:{"transition_light_state":{"brightness":brightness,"transition_period":0}}}))
         .await
-        .unwrap();
-}
-
-pub async fn tplink_set_light_hsl(ip: &str, color: String) {
-    match csscolorparser::parse(&color) {
-        Ok(color) => {
-            let [h, s, v, _a] = color.to_hsva();
-            let hue = h as u8;
-            let saturation = (s * 100.) as u8;
-            let value = (v * 100.) as u8;
-            let color_temp = 0u8;
-            // https://github.com/python-kasa/python-kasa/blob/123ea107b1e7536bc5dfc8b93111cc5c7e8d066b/kasa/iot/iotbulb.py#L407
-            send(
-                ip,
-                json!({
-                        LIGHT_SERVICE:{
-                        "transition_light_state":{
-                            "hue": hue,
-                            "saturation": saturation,
-                            "brightness": value,
-                            "color_temp": color_temp,
-                            "transition_period": 0
-                        }
-                    }
-                }),
-            )
-            .await
-            .unwrap();
-        }
-        Err(e) => {
-            leptos::logging::log!("Failed to parse color '{}': {}", color, e);
-        }
-    }
 }
 
-pub async fn tplink_kasa_get_energy_usage(ip: &str, id: &str) -> io::Result<Value> {
-    send(
+pub async fn tplink_set_light_hsl(ip: &str, color: String) -> Result<(), TplinkError> {
+    let color = csscolorparser::parse(&color)
+        .map_err(|e| TplinkError::InvalidInput(format!("Failed to parse color '{color}': {e}")))?;
+    let [h, s, v, _a] = color.to_hsva();
+    let hue = h as u16;
+    let saturation = (s * 100.) as u8;
+    let value = (v * 100.) as u8;
+    tplink_set_light_hsv(ip, hue, saturation, value).await
+}
+
+pub async fn tplink_set_light_hsv(
+    ip: &str,
+    hue: u16,
+    saturation: u8,
+    brightness: u8,
+) -> Result<(), TplinkError> {
+    // https://github.com/python-kasa/python-kasa/blob/123ea107b1e7536bc5dfc8b93111cc5c7e8d066b/kasa/iot/iotbulb.py#L407
+    send_command(
         ip,
-        json!({"context": {
-                "child_ids": [id]
-            },"system":{"get_energy_usage":{}}}),
+        json!({
+                LIGHT_SERVICE:{
+                "transition_light_state":{
+                    "hue": hue,
+                    "saturation": saturation,
+                    "brightness": brightness,
+                    "color_temp": 0,
+                    "transition_period": 0
+                }
+            }
+        }),
     )
     .await
 }
 
-pub async fn tplink_kasa_get_emeter_data(ip: &str, id: &str) -> io::Result<Value> {
-    send(
+pub async fn tplink_set_light_color_temp(ip: &str, color_temp: u16) -> Result<(), TplinkError> {
+    send_command(
         ip,
-        json!({"context": {
-                "child_ids": [id]
-            },"system":{"get_emeter_data":{}}}),
+        json!({LIGHT_SERVICE:{"transition_light_state":{"color_temp":color_temp,"transition_period":0}}}),
     )
     .await
 }
 
-fn encrypt_with_header(input: &[u8], first_key: u8) -> Vec<u8> {
-    (input.len() as u32)
-        .to_be_bytes()
-        .into_iter()
-        .chain(input.iter().scan(first_key, |key, byte| {
-            let result = *byte ^ *key;
-            *key = result;
-            Some(result)
-        }))
-        .collect()
+/// Changes the fields set in `change`, fading over its `transition_period`.
+pub async fn tplink_transition_light_state(
+    ip: &str,
+    change: &LightStateChange,
+) -> Result<(), TplinkError> {
+    send_command(
+        ip,
+        json!({LIGHT_SERVICE:{"transition_light_state": change}}),
+    )
+    .await
+}
+
+/// Overwrites the preset with the same index as `preset`.
+pub async fn tplink_set_light_preset(ip: &str, preset: &PreferredState) -> Result<(), TplinkError> {
+    send_command(ip, json!({LIGHT_SERVICE:{"set_preferred_state": preset}})).await
+}
+
+/// Reads the realtime emeter values of a plug, or of a single power strip
+/// outlet when `child_id` is given. Devices without an emeter fail with an
+/// error that `is_unsupported`.
+pub async fn tplink_kasa_get_emeter_realtime(
+    ip: &str,
+    child_id: Option<&str>,
+) -> Result<EmeterRealtime, TplinkError> {
+    let mut request = json!({"emeter":{"get_realtime":{}}});
+    if let Some(child_id) = child_id {
+        request["context"] = json!({"child_ids": [child_id]});
+    }
+    let res = send(ip, request).await?;
+    check_err_code(&res)?;
+    Ok(serde_json::from_value(
+        res["emeter"]["get_realtime"].clone(),
+    )?)
+}
+
+/// Module of the on/off rules plugs, dimmers and power strip outlets run on
+/// their own clock.
+pub const SCHEDULE: &str = "schedule";
+/// Module of the timer that flips the relay after a delay.
+pub const COUNT_DOWN: &str = "count_down";
+
+/// A request for one method of a rule module, addressed to a single power
+/// strip outlet when `child_id` is given.
+fn rule_request(module: &str, method: &str, params: Value, child_id: Option<&str>) -> Value {
+    let mut request = json!({module: {method: params}});
+    if let Some(child_id) = child_id {
+        request["context"] = json!({"child_ids": [child_id]});
+    }
+    request
 }
 
-fn decrypt(input: &[u8], first_key: u8) -> Vec<u8> {
-    let mut buf = input.to_vec();
-    let mut key = first_key;
-    for item in &mut buf {
-        let next_key = *item;
-        *item ^= key;
-        key = next_key;
+/// Reads the rules of `module`, [`SCHEDULE`] or [`COUNT_DOWN`].
+pub async fn tplink_get_rules<T: DeserializeOwned>(
+    ip: &str,
+    child_id: Option<&str>,
+    module: &str,
+) -> Result<Vec<T>, TplinkError> {
+    let res = send(ip, rule_request(module, "get_rules", json!({}), child_id)).await?;
+    check_err_code(&res)?;
+    let rule_list = &res[module]["get_rules"]["rule_list"];
+    if rule_list.is_null() {
+        return Ok(Vec::new());
+    }
+    Ok(serde_json::from_value(rule_list.clone())?)
+}
+
+/// Adds a rule to `module` and returns the id the device gave it.
+pub async fn tplink_add_rule<T: Serialize>(
+    ip: &str,
+    child_id: Option<&str>,
+    module: &str,
+    rule: &T,
+) -> Result<String, TplinkError> {
+    #[derive(Deserialize)]
+    struct AddedRule {
+        id: String,
+    }
+
+    let mut params = serde_json::to_value(rule)?;
+    if let Some(params) = params.as_object_mut() {
+        params.remove("id");
     }
-    buf
+    let res = send(ip, rule_request(module, "add_rule", params, child_id)).await?;
+    check_err_code(&res)?;
+    Ok(serde_json::from_value::<AddedRule>(res[module]["add_rule"].clone())?.id)
 }
 
-fn encrypt(input: &[u8], first_key: u8) -> Vec<u8> {
-    let mut buf = input.to_vec();
-    let mut key = first_key;
-    for byte in &mut buf {
-        *byte ^= key;
-        key = *byte;
+/// Replaces the rule in `module` with the same id as `rule`.
+pub async fn tplink_edit_rule<T: Serialize>(
+    ip: &str,
+    child_id: Option<&str>,
+    module: &str,
+    rule: &T,
+) -> Result<(), TplinkError> {
+    let params = serde_json::to_value(rule)?;
+    if !params["id"].is_string() {
+        return Err(TplinkError::InvalidInput(
+            "Only rules the device has an id for can be edited".to_string(),
+        ));
     }
-    buf
+    send_command(ip, rule_request(module, "edit_rule", params, child_id)).await
 }
 
-fn decrypt_with_header(input: &[u8], first_key: u8) -> Vec<u8> {
-    let len = u32::from_be_bytes(input[0..4].try_into().unwrap());
-    let mut msg = decrypt(&input[4..], first_key);
-    msg.truncate(len as usize);
-    msg
+pub async fn tplink_delete_rule(
+    ip: &str,
+    child_id: Option<&str>,
+    module: &str,
+    id: &str,
+) -> Result<(), TplinkError> {
+    send_command(
+        ip,
+        rule_request(module, "delete_rule", json!({"id": id}), child_id),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_err_code() {
+        assert!(check_err_code(&json!({"system":{"set_relay_state":{"err_code":0}}})).is_ok());
+        assert!(
+            check_err_code(&json!({
+                "context": {"child_ids": ["00"]},
+                "system": {"set_relay_state": {"err_code": 0}}
+            }))
+            .is_ok()
+        );
+
+        let err = check_err_code(&json!({
+            "system": {"set_relay_state": {"err_code": -3, "err_msg": "invalid argument"}}
+        }))
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "system.set_relay_state failed with err_code -3: invalid argument"
+        );
+
+        let err = check_err_code(&json!({
+            "emeter": {"err_code": -1, "err_msg": "module not support"}
+        }))
+        .unwrap_err();
+        assert!(err.is_unsupported());
+    }
 }
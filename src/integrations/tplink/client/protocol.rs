@@ -0,0 +1,126 @@
+use {
+    super::TplinkError,
+    tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+};
+
+/// First key of the autokey XOR cipher the Kasa protocol uses.
+pub const KEY: u8 = 0xAB;
+
+/// Responses are a few KB at most, even a power strip's sysinfo or a month
+/// of emeter history. Anything past this is a misbehaving device or not a
+/// Kasa device at all.
+pub const MAX_FRAME_LEN: usize = 64 * 1024;
+
+/// Each byte is XORed with the previous ciphertext byte, starting with
+/// `first_key`.
+pub fn encrypt(input: &[u8], first_key: u8) -> Vec<u8> {
+    let mut buf = input.to_vec();
+    let mut key = first_key;
+    for byte in &mut buf {
+        *byte ^= key;
+        key = *byte;
+    }
+    buf
+}
+
+pub fn decrypt(input: &[u8], first_key: u8) -> Vec<u8> {
+    let mut buf = input.to_vec();
+    let mut key = first_key;
+    for item in &mut buf {
+        let next_key = *item;
+        *item ^= key;
+        key = next_key;
+    }
+    buf
+}
+
+/// Encrypts `payload` and prefixes it with its big endian `u32` length, the
+/// framing used over TCP. UDP discovery sends bare `encrypt`ed payloads.
+pub fn encode_frame(payload: &[u8]) -> Vec<u8> {
+    let mut frame = (payload.len() as u32).to_be_bytes().to_vec();
+    frame.extend(encrypt(payload, KEY));
+    frame
+}
+
+pub async fn write_frame<W>(writer: &mut W, payload: &[u8]) -> Result<(), TplinkError>
+where
+    W: AsyncWrite + Unpin,
+{
+    writer.write_all(&encode_frame(payload)).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Reads the length header and then the whole payload, however many reads
+/// it takes to arrive.
+pub async fn read_frame<R>(reader: &mut R) -> Result<Vec<u8>, TplinkError>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut header = [0u8; 4];
+    reader.read_exact(&mut header).await?;
+    let len = u32::from_be_bytes(header) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(TplinkError::FrameTooLarge(len));
+    }
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload).await?;
+    Ok(decrypt(&payload, KEY))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, serde_json::json, std::io};
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let msg = br#"{"system":{"get_sysinfo":{}}}"#;
+        let encrypted = encrypt(msg, KEY);
+        assert_ne!(&encrypted[..], &msg[..]);
+        // '{' ^ 0xAB, then each byte XORed with the previous ciphertext byte
+        assert_eq!(&encrypted[..4], &[0xd0, 0xf2, 0x81, 0xf8]);
+        assert_eq!(decrypt(&encrypted, KEY), msg);
+
+        assert!(encrypt(&[], KEY).is_empty());
+        let all_bytes = (0..=255).collect::<Vec<u8>>();
+        assert_eq!(decrypt(&encrypt(&all_bytes, KEY), KEY), all_bytes);
+    }
+
+    #[tokio::test]
+    async fn test_frame_round_trip() {
+        // Bigger than the single 1024 byte read this replaced, and split
+        // across several reads by the small duplex buffer
+        let children = (0..6)
+            .map(|i| json!({"id": format!("80061E2B{i:02}"), "alias": "x".repeat(200), "state": 1}))
+            .collect::<Vec<_>>();
+        let payload =
+            serde_json::to_vec(&json!({"system": {"get_sysinfo": {"children": children}}}))
+                .unwrap();
+        assert!(payload.len() > 1024);
+
+        let (mut client, mut server) = tokio::io::duplex(256);
+        let writer = {
+            let payload = payload.clone();
+            tokio::spawn(async move { write_frame(&mut server, &payload).await })
+        };
+        assert_eq!(read_frame(&mut client).await.unwrap(), payload);
+        writer.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_frame_errors() {
+        let mut oversized = &((MAX_FRAME_LEN + 1) as u32).to_be_bytes()[..];
+        assert!(matches!(
+            read_frame(&mut oversized).await,
+            Err(TplinkError::FrameTooLarge(_))
+        ));
+
+        // The connection closed halfway through the payload
+        let frame = encode_frame(b"{}");
+        let mut truncated = &frame[..frame.len() - 1];
+        assert!(matches!(
+            read_frame(&mut truncated).await,
+            Err(TplinkError::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof
+        ));
+    }
+}
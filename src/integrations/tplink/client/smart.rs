@@ -0,0 +1,172 @@
+//! Tapo devices speak the SMART protocol over KLAP, one `{"method", "params"}`
+//! request at a time, instead of the legacy `{module: {method: params}}`
+//! requests. These translate the legacy requests the command functions send
+//! for plugs, and the responses back, so the rest of the integration doesn't
+//! need to know which protocol a device speaks.
+
+use {
+    super::TplinkError,
+    base64::{Engine, engine::general_purpose::STANDARD as base64},
+    serde_json::{Value, json},
+};
+
+fn error(err_code: i64, err_msg: &str) -> Value {
+    json!({"err_code": err_code, "err_msg": err_msg})
+}
+
+/// The SMART request for one legacy method, or the error a legacy device
+/// would have answered with.
+pub fn translate_request(module: &str, method: &str, params: &Value) -> Result<Value, Value> {
+    match (module, method) {
+        ("system", "get_sysinfo") => Ok(json!({"method": "get_device_info"})),
+        ("system", "set_relay_state") => Ok(json!({
+            "method": "set_device_info",
+            "params": {"device_on": params["state"].as_i64().unwrap_or(0) != 0},
+        })),
+        ("system", "set_dev_alias") => Ok(json!({
+            "method": "set_device_info",
+            "params": {"nickname": base64.encode(params["alias"].as_str().unwrap_or_default())},
+        })),
+        ("system", "reboot") => Ok(json!({
+            "method": "device_reboot",
+            "params": {"delay": params["delay"].as_i64().unwrap_or(1)},
+        })),
+        ("emeter", "get_realtime") => Ok(json!({"method": "get_energy_usage"})),
+        ("system", _) | ("emeter", _) => Err(error(-2, "method not support")),
+        _ => Err(error(-1, "module not support")),
+    }
+}
+
+/// Maps the `result` of a SMART response onto what a legacy device returns
+/// for `module.method`.
+pub fn translate_response(module: &str, method: &str, response: &Value) -> Value {
+    let error_code = response["error_code"].as_i64().unwrap_or(0);
+    if error_code != 0 {
+        // Plugs without an energy meter reject get_energy_usage
+        return match module {
+            "emeter" => error(-1, "module not support"),
+            _ => error(error_code, "SMART request failed"),
+        };
+    }
+    let result = &response["result"];
+    match (module, method) {
+        ("system", "get_sysinfo") => sysinfo(result),
+        ("emeter", "get_realtime") => json!({
+            "err_code": 0,
+            "power_mw": result["current_power"],
+        }),
+        _ => json!({"err_code": 0}),
+    }
+}
+
+/// Builds the legacy sysinfo of a plug from `get_device_info`.
+fn sysinfo(info: &Value) -> Value {
+    let alias = info["nickname"]
+        .as_str()
+        .and_then(|nickname| base64.decode(nickname).ok())
+        .and_then(|nickname| String::from_utf8(nickname).ok())
+        .unwrap_or_default();
+    let model = info["model"].as_str().unwrap_or_default();
+    json!({
+        "active_mode": "none",
+        "alias": alias,
+        "dev_name": model,
+        "deviceId": info["device_id"],
+        "err_code": 0,
+        "feature": "TIM",
+        "hwId": info["hw_id"],
+        "hw_ver": info["hw_ver"],
+        "icon_hash": "",
+        "latitude_i": 0,
+        "led_off": 0,
+        "longitude_i": 0,
+        "mac": info["mac"].as_str().unwrap_or_default().replace('-', ":"),
+        "mic_type": info["type"],
+        "model": model,
+        "obd_src": "tplink",
+        "oemId": info["oem_id"],
+        "on_time": info["on_time"].as_i64().unwrap_or(0),
+        "relay_state": i32::from(info["device_on"].as_bool().unwrap_or(false)),
+        "rssi": info["rssi"].as_i64().unwrap_or(0),
+        "status": "new",
+        "sw_ver": info["fw_ver"],
+        "updating": 0,
+    })
+}
+
+/// Sends each method of a legacy request as its own SMART request through
+/// `send` and assembles the legacy response.
+pub async fn request<F, Fut>(json: &Value, send: F) -> Result<Value, TplinkError>
+where
+    F: Fn(Value) -> Fut,
+    Fut: Future<Output = Result<Value, TplinkError>>,
+{
+    let mut response = json!({});
+    if json.get("context").is_some() {
+        // Tapo power strips address outlets with control_child instead
+        for module in json.as_object().into_iter().flatten().map(|(m, _)| m) {
+            if module != "context" {
+                response[module] = error(-2, "method not support");
+            }
+        }
+        return Ok(response);
+    }
+    for (module, methods) in json.as_object().into_iter().flatten() {
+        let Some(methods) = methods.as_object() else {
+            continue;
+        };
+        for (method, params) in methods {
+            let result = match translate_request(module, method, params) {
+                Ok(smart_request) => {
+                    translate_response(module, method, &send(smart_request).await?)
+                }
+                Err(error) if error["err_code"] == -1 => {
+                    response[module] = error;
+                    break;
+                }
+                Err(error) => error,
+            };
+            response[module][method] = result;
+        }
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate() {
+        assert_eq!(
+            translate_request("system", "set_relay_state", &json!({"state": 1})).unwrap(),
+            json!({"method": "set_device_info", "params": {"device_on": true}})
+        );
+        assert_eq!(
+            translate_request("smartlife.iot.dimmer", "set_cold_time", &json!({})).unwrap_err()["err_code"],
+            -1
+        );
+
+        let info = json!({
+            "error_code": 0,
+            "result": {
+                "device_id": "80223E",
+                "device_on": true,
+                "mac": "5C-62-8B-00-11-22",
+                "model": "P110",
+                "nickname": base64.encode("Space Heater"),
+                "type": "SMART.TAPOPLUG",
+            }
+        });
+        let sysinfo = translate_response("system", "get_sysinfo", &info);
+        assert_eq!(sysinfo["alias"], "Space Heater");
+        assert_eq!(sysinfo["relay_state"], 1);
+        assert_eq!(sysinfo["mac"], "5C:62:8B:00:11:22");
+
+        let rejected = json!({"error_code": -1, "msg": "Method not found"});
+        assert_eq!(
+            translate_response("emeter", "get_realtime", &rejected)["err_code"],
+            -1
+        );
+    }
+}
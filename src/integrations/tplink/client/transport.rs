@@ -0,0 +1,216 @@
+//! Which protocol each device speaks, and the logged in KLAP sessions. The
+//! command functions only get an IP, so this is kept per address for the
+//! whole process rather than passed to them.
+
+use {
+    super::{
+        TplinkError,
+        klap::{Credentials, KlapSession, KlapVersion, default_credentials},
+        smart,
+    },
+    serde::Deserialize,
+    serde_json::Value,
+    std::{
+        collections::HashMap,
+        net::{IpAddr, Ipv4Addr, SocketAddr},
+        sync::{Arc, LazyLock, Mutex},
+        time::Duration,
+    },
+    tokio::net::UdpSocket,
+};
+
+pub const KLAP_DISCOVERY_PORT: u16 = 20002;
+
+/// Asks devices listening on 20002 to describe themselves. Without the RSA
+/// key python-kasa adds, devices still answer with their model and how to
+/// connect.
+pub const KLAP_DISCOVERY_QUERY: [u8; 16] = [
+    0x02, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46, 0x3c, 0xb5, 0xd3,
+];
+
+/// Discovery answers start with a header of this length before the JSON.
+const KLAP_DISCOVERY_HEADER_LEN: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Klap {
+    pub port: u16,
+    pub version: KlapVersion,
+    /// Tapo devices take SMART requests, newer Kasa firmware the legacy
+    /// ones.
+    pub smart: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transport {
+    /// XOR obfuscated JSON over TCP 9999.
+    Legacy,
+    /// AES encrypted requests over HTTP after a KLAP handshake.
+    Klap(Klap),
+}
+
+#[derive(Deserialize)]
+struct DiscoveryResponse {
+    result: DiscoveryResult,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct DiscoveryResult {
+    pub device_type: String,
+    pub device_model: String,
+    mgt_encrypt_schm: EncryptionScheme,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct EncryptionScheme {
+    encrypt_type: Option<String>,
+    http_port: Option<u16>,
+    lv: Option<u8>,
+}
+
+impl DiscoveryResult {
+    /// Devices that use the older AES "securePassthrough" transport instead
+    /// of KLAP aren't supported.
+    pub fn klap(&self) -> Option<Klap> {
+        let scheme = &self.mgt_encrypt_schm;
+        (scheme.encrypt_type.as_deref() == Some("KLAP")).then(|| Klap {
+            port: scheme.http_port.unwrap_or(80),
+            version: match scheme.lv {
+                Some(2..) => KlapVersion::V2,
+                _ => KlapVersion::V1,
+            },
+            smart: self.device_type.starts_with("SMART."),
+        })
+    }
+}
+
+pub fn parse_discovery(packet: &[u8]) -> Result<DiscoveryResult, TplinkError> {
+    let json = packet
+        .get(KLAP_DISCOVERY_HEADER_LEN..)
+        .ok_or_else(|| TplinkError::Klap("discovery answer is too short".to_string()))?;
+    Ok(serde_json::from_slice::<DiscoveryResponse>(json)?.result)
+}
+
+#[derive(Default)]
+struct Registry {
+    transports: HashMap<IpAddr, Transport>,
+    sessions: HashMap<IpAddr, Arc<KlapSession>>,
+    credentials: Option<Credentials>,
+}
+
+static REGISTRY: LazyLock<Mutex<Registry>> = LazyLock::new(Mutex::default);
+
+/// The account KLAP devices are bound to. Sessions logged in with other
+/// credentials are dropped.
+pub fn set_credentials(credentials: Option<Credentials>) {
+    let mut registry = REGISTRY.lock().unwrap();
+    if registry.credentials != credentials {
+        registry.credentials = credentials;
+        registry.sessions.clear();
+    }
+}
+
+pub fn get(ip: IpAddr) -> Option<Transport> {
+    REGISTRY.lock().unwrap().transports.get(&ip).copied()
+}
+
+pub fn set(ip: IpAddr, transport: Transport) {
+    let mut registry = REGISTRY.lock().unwrap();
+    if registry.transports.insert(ip, transport) != Some(transport) {
+        registry.sessions.remove(&ip);
+    }
+}
+
+/// Asks a single device whether it speaks KLAP.
+pub async fn probe(ip: IpAddr) -> Option<Klap> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await.ok()?;
+    socket
+        .send_to(&KLAP_DISCOVERY_QUERY, (ip, KLAP_DISCOVERY_PORT))
+        .await
+        .ok()?;
+    let mut buf = vec![0; 4096];
+    let (len, _) = tokio::time::timeout(Duration::from_secs(1), socket.recv_from(&mut buf))
+        .await
+        .ok()?
+        .ok()?;
+    parse_discovery(&buf[..len]).ok()?.klap()
+}
+
+async fn session(ip: IpAddr, klap: Klap) -> Result<Arc<KlapSession>, TplinkError> {
+    let credentials = {
+        let registry = REGISTRY.lock().unwrap();
+        if let Some(session) = registry.sessions.get(&ip) {
+            return Ok(session.clone());
+        }
+        registry
+            .credentials
+            .iter()
+            .cloned()
+            .chain(default_credentials())
+            .collect::<Vec<_>>()
+    };
+    let session = Arc::new(
+        KlapSession::handshake(SocketAddr::new(ip, klap.port), klap.version, &credentials).await?,
+    );
+    REGISTRY
+        .lock()
+        .unwrap()
+        .sessions
+        .insert(ip, session.clone());
+    Ok(session)
+}
+
+/// Sends one request, logging in again once if the session expired.
+async fn call(ip: IpAddr, klap: Klap, json: Value) -> Result<Value, TplinkError> {
+    let msg = serde_json::to_vec(&json)?;
+    let response = match session(ip, klap).await?.request(&msg).await {
+        Ok(response) => response,
+        Err(_) => {
+            REGISTRY.lock().unwrap().sessions.remove(&ip);
+            session(ip, klap).await?.request(&msg).await?
+        }
+    };
+    Ok(serde_json::from_slice(&response)?)
+}
+
+/// Sends a legacy `{module: {method: params}}` request to a KLAP device.
+pub async fn klap_request(ip: IpAddr, klap: Klap, json: &Value) -> Result<Value, TplinkError> {
+    if klap.smart {
+        smart::request(json, |request| call(ip, klap, request)).await
+    } else {
+        call(ip, klap, json.clone()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_discovery() {
+        let json = br#"{"error_code": 0, "result": {
+            "device_id": "00000000000000000000000000000000",
+            "device_type": "SMART.TAPOPLUG",
+            "device_model": "P110(US)",
+            "ip": "192.168.1.20",
+            "mac": "5C-62-8B-00-11-22",
+            "mgt_encrypt_schm": {"is_support_https": false, "encrypt_type": "KLAP", "http_port": 80, "lv": 2}
+        }}"#;
+        let packet = [&[0u8; KLAP_DISCOVERY_HEADER_LEN][..], json].concat();
+        let result = parse_discovery(&packet).unwrap();
+        assert_eq!(result.device_model, "P110(US)");
+        assert_eq!(
+            result.klap(),
+            Some(Klap {
+                port: 80,
+                version: KlapVersion::V2,
+                smart: true,
+            })
+        );
+
+        let aes = br#"{"result": {"device_type": "SMART.TAPOBULB", "device_model": "L530",
+            "mgt_encrypt_schm": {"encrypt_type": "AES", "http_port": 80}}}"#;
+        let packet = [&[0u8; KLAP_DISCOVERY_HEADER_LEN][..], aes].concat();
+        assert_eq!(parse_discovery(&packet).unwrap().klap(), None);
+        assert!(parse_discovery(&[0u8; 4]).is_err());
+    }
+}
@@ -0,0 +1,327 @@
+use {
+    super::{
+        client::{
+            TplinkError, tplink_get_sysinfo, tplink_set_dimmer_brightness,
+            tplink_set_light_brightness, tplink_set_light_color_temp, tplink_set_light_hsl,
+            tplink_set_light_hsv, tplink_transition_light_state, tplink_turn_light_on_off,
+            tplink_turn_plug_off, tplink_turn_plug_on, tplink_turn_smart_strip_socket_off,
+            tplink_turn_smart_strip_socket_on,
+        },
+        types::{GetSysInfo, LightStateChange, TPLinkSmartLightData},
+    },
+    crate::integrations::iron_nest::{
+        driver::{DeviceDriver, DriverError, DriverFuture, StateFuture},
+        types::{Capability, Device, DeviceState},
+    },
+    futures::FutureExt,
+};
+
+fn device_error(device: &Device) -> impl FnOnce(TplinkError) -> DriverError + '_ {
+    move |e| DriverError::Device(format!("{}: {e}", device.label()))
+}
+
+async fn get_sysinfo(device: &Device) -> Result<GetSysInfo, DriverError> {
+    tplink_get_sysinfo(&device.ip)
+        .await
+        .map_err(device_error(device))
+}
+
+fn unexpected_sysinfo(device: &Device) -> DriverError {
+    DriverError::Device(format!(
+        "{} returned sysinfo for a different device type",
+        device.label()
+    ))
+}
+
+pub struct KasaPlugDriver;
+
+impl DeviceDriver for KasaPlugDriver {
+    fn capabilities(&self) -> &'static [Capability] {
+        &[Capability::OnOff]
+    }
+
+    fn set_power<'a>(&'a self, device: &'a Device, on: bool) -> DriverFuture<'a> {
+        async move {
+            if on {
+                tplink_turn_plug_on(&device.ip).await
+            } else {
+                tplink_turn_plug_off(&device.ip).await
+            }
+            .map_err(device_error(device))
+        }
+        .boxed()
+    }
+
+    fn get_state<'a>(&'a self, device: &'a Device) -> StateFuture<'a> {
+        async move {
+            let GetSysInfo::TPLinkDiscoveryData(sysinfo) = get_sysinfo(device).await? else {
+                return Err(unexpected_sysinfo(device));
+            };
+            Ok(Some(DeviceState {
+                on: sysinfo.relay_state != 0,
+                ..Default::default()
+            }))
+        }
+        .boxed()
+    }
+}
+
+pub struct KasaLightDriver;
+
+async fn light_sysinfo(device: &Device) -> Result<TPLinkSmartLightData, DriverError> {
+    match get_sysinfo(device).await? {
+        GetSysInfo::TPLinkSmartLightData(sysinfo) => Ok(sysinfo),
+        _ => Err(unexpected_sysinfo(device)),
+    }
+}
+
+/// Bulbs ignore color temperatures outside their range instead of failing.
+async fn check_color_temp(device: &Device, color_temp: u16) -> Result<(), DriverError> {
+    let sysinfo = light_sysinfo(device).await?;
+    match sysinfo.color_temp_range() {
+        Some((min, max)) if (min..=max).contains(&color_temp) => Ok(()),
+        Some((min, max)) => Err(DriverError::InvalidArgs(format!(
+            "{} supports {min}K to {max}K, not {color_temp}K",
+            device.label()
+        ))),
+        None => Err(DriverError::InvalidArgs(format!(
+            "{} doesn't have an adjustable white",
+            device.label()
+        ))),
+    }
+}
+
+/// Like `DeviceState::commands`, color temperature wins over hue and
+/// saturation.
+fn light_state_change(state: &DeviceState, transition_ms: u32) -> LightStateChange {
+    let mut change = LightStateChange {
+        on_off: Some(state.on.into()),
+        transition_period: transition_ms,
+        ..LightStateChange::default()
+    };
+    if !state.on {
+        return change;
+    }
+    change.brightness = state.brightness;
+    match (
+        state.color_temp.filter(|&k| k > 0),
+        state.hue,
+        state.saturation,
+    ) {
+        (Some(color_temp), _, _) => change.color_temp = Some(color_temp),
+        (None, Some(hue), Some(saturation)) => {
+            change.hue = Some(hue);
+            change.saturation = Some(saturation);
+            change.color_temp = Some(0);
+        }
+        _ => {}
+    }
+    change
+}
+
+impl DeviceDriver for KasaLightDriver {
+    fn capabilities(&self) -> &'static [Capability] {
+        &[
+            Capability::OnOff,
+            Capability::Brightness,
+            Capability::Color,
+            Capability::ColorTemp,
+            Capability::Presets,
+            Capability::Transition,
+        ]
+    }
+
+    fn set_power<'a>(&'a self, device: &'a Device, on: bool) -> DriverFuture<'a> {
+        async move {
+            tplink_turn_light_on_off(&device.ip, on.into())
+                .await
+                .map_err(device_error(device))
+        }
+        .boxed()
+    }
+
+    fn set_brightness<'a>(&'a self, device: &'a Device, brightness: u8) -> DriverFuture<'a> {
+        async move {
+            tplink_set_light_brightness(&device.ip, brightness)
+                .await
+                .map_err(device_error(device))
+        }
+        .boxed()
+    }
+
+    fn set_color<'a>(&'a self, device: &'a Device, color: &'a str) -> DriverFuture<'a> {
+        async move {
+            tplink_set_light_hsl(&device.ip, color.to_string())
+                .await
+                .map_err(device_error(device))
+        }
+        .boxed()
+    }
+
+    fn set_hsv<'a>(
+        &'a self,
+        device: &'a Device,
+        hue: u16,
+        saturation: u8,
+        brightness: u8,
+    ) -> DriverFuture<'a> {
+        async move {
+            tplink_set_light_hsv(&device.ip, hue, saturation, brightness)
+                .await
+                .map_err(device_error(device))
+        }
+        .boxed()
+    }
+
+    fn set_color_temp<'a>(&'a self, device: &'a Device, color_temp: u16) -> DriverFuture<'a> {
+        async move {
+            check_color_temp(device, color_temp).await?;
+            tplink_set_light_color_temp(&device.ip, color_temp)
+                .await
+                .map_err(device_error(device))
+        }
+        .boxed()
+    }
+
+    fn apply_preset<'a>(&'a self, device: &'a Device, index: u8) -> DriverFuture<'a> {
+        async move {
+            let sysinfo = light_sysinfo(device).await?;
+            let preset = sysinfo
+                .preferred_state
+                .iter()
+                .find(|preset| preset.index == index)
+                .ok_or_else(|| {
+                    DriverError::InvalidArgs(format!("{} has no preset {index}", device.label()))
+                })?;
+            tplink_transition_light_state(&device.ip, &preset.into())
+                .await
+                .map_err(device_error(device))
+        }
+        .boxed()
+    }
+
+    fn fade_to<'a>(
+        &'a self,
+        device: &'a Device,
+        state: &'a DeviceState,
+        transition_ms: u32,
+    ) -> DriverFuture<'a> {
+        async move {
+            let change = light_state_change(state, transition_ms);
+            if let Some(color_temp) = change.color_temp.filter(|&k| k > 0) {
+                check_color_temp(device, color_temp).await?;
+            }
+            tplink_transition_light_state(&device.ip, &change)
+                .await
+                .map_err(device_error(device))
+        }
+        .boxed()
+    }
+
+    fn get_state<'a>(&'a self, device: &'a Device) -> StateFuture<'a> {
+        async move {
+            let light_state = light_sysinfo(device).await?.light_state;
+            let state = match light_state.dft_on_state {
+                // While off the bulb reports the state it will turn on to
+                Some(dft) if light_state.on_off == 0 => DeviceState {
+                    on: false,
+                    brightness: u8::try_from(dft.brightness).ok(),
+                    hue: u16::try_from(dft.hue).ok(),
+                    saturation: u8::try_from(dft.saturation).ok(),
+                    color_temp: u16::try_from(dft.color_temp).ok(),
+                },
+                _ => DeviceState {
+                    on: light_state.on_off != 0,
+                    brightness: light_state.brightness,
+                    hue: light_state.hue,
+                    saturation: light_state.saturation,
+                    color_temp: light_state.color_temp,
+                },
+            };
+            Ok(Some(state))
+        }
+        .boxed()
+    }
+}
+
+pub struct KasaDimmerDriver;
+
+impl DeviceDriver for KasaDimmerDriver {
+    fn capabilities(&self) -> &'static [Capability] {
+        &[Capability::OnOff, Capability::Brightness]
+    }
+
+    fn set_power<'a>(&'a self, device: &'a Device, on: bool) -> DriverFuture<'a> {
+        KasaPlugDriver.set_power(device, on)
+    }
+
+    fn set_brightness<'a>(&'a self, device: &'a Device, brightness: u8) -> DriverFuture<'a> {
+        async move {
+            tplink_set_dimmer_brightness(&device.ip, &brightness)
+                .await
+                .map_err(device_error(device))
+        }
+        .boxed()
+    }
+
+    fn get_state<'a>(&'a self, device: &'a Device) -> StateFuture<'a> {
+        async move {
+            let GetSysInfo::TPLinkDiscoveryData(sysinfo) = get_sysinfo(device).await? else {
+                return Err(unexpected_sysinfo(device));
+            };
+            Ok(Some(DeviceState {
+                on: sysinfo.relay_state != 0,
+                brightness: sysinfo.brightness,
+                ..Default::default()
+            }))
+        }
+        .boxed()
+    }
+}
+
+pub struct KasaPowerStripDriver;
+
+fn outlet_id(device: &Device) -> Result<&str, DriverError> {
+    device.child_id.as_deref().ok_or_else(|| {
+        DriverError::InvalidArgs(format!("{} has no outlet child_id", device.label()))
+    })
+}
+
+impl DeviceDriver for KasaPowerStripDriver {
+    fn capabilities(&self) -> &'static [Capability] {
+        &[Capability::OnOff]
+    }
+
+    fn set_power<'a>(&'a self, device: &'a Device, on: bool) -> DriverFuture<'a> {
+        async move {
+            let child_id = outlet_id(device)?;
+            if on {
+                tplink_turn_smart_strip_socket_on(&device.ip, child_id).await
+            } else {
+                tplink_turn_smart_strip_socket_off(&device.ip, child_id).await
+            }
+            .map_err(device_error(device))
+        }
+        .boxed()
+    }
+
+    fn get_state<'a>(&'a self, device: &'a Device) -> StateFuture<'a> {
+        async move {
+            let child_id = outlet_id(device)?;
+            let GetSysInfo::TPLinkSmartPowerStripData(sysinfo) = get_sysinfo(device).await? else {
+                return Err(unexpected_sysinfo(device));
+            };
+            let outlet = sysinfo.outlet(child_id).ok_or_else(|| {
+                DriverError::Device(format!(
+                    "{} didn't report outlet {child_id}",
+                    device.label()
+                ))
+            })?;
+            Ok(Some(DeviceState {
+                on: outlet.state != 0,
+                ..Default::default()
+            }))
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,88 @@
+use {
+    super::{Credentials, set_credentials},
+    crate::integrations::iron_nest::{
+        events::EventBus,
+        poll_tplink_energy, refresh_tplink_devices,
+        secrets::SecretStore,
+        supervisor::{Every, Integration, IntegrationFuture, PollFuture},
+        types::IntegrationSettings,
+    },
+    futures::FutureExt,
+    sqlx::PgPool,
+    std::{collections::HashSet, net::Ipv4Addr, sync::Mutex as SyncMutex, time::Duration},
+    tokio::sync::Mutex,
+};
+
+/// Reads the emeters every poll and rediscovers devices every five minutes by
+/// default. Discovery also probes the configured scan targets for devices on
+/// other subnets. Devices on KLAP firmware log in with the optional TP-Link
+/// account.
+pub struct TplinkIntegration {
+    pool: PgPool,
+    event_bus: EventBus,
+    secrets: SecretStore,
+    discovery: Every,
+    scan_hosts: SyncMutex<Vec<Ipv4Addr>>,
+    without_emeter: Mutex<HashSet<i64>>,
+}
+
+impl TplinkIntegration {
+    pub fn new(pool: PgPool, event_bus: EventBus, secrets: SecretStore) -> Self {
+        Self {
+            pool,
+            event_bus,
+            secrets,
+            discovery: Every::new(Duration::from_secs(300)),
+            scan_hosts: SyncMutex::default(),
+            without_emeter: Mutex::default(),
+        }
+    }
+}
+
+impl Integration for TplinkIntegration {
+    fn name(&self) -> &'static str {
+        "tplink"
+    }
+
+    fn configure(&self, settings: &IntegrationSettings) {
+        self.discovery.set_period(
+            settings
+                .discovery_interval()
+                .unwrap_or(settings.poll_interval()),
+        );
+        *self.scan_hosts.lock().unwrap() = settings.scan_hosts();
+    }
+
+    fn start(&self) -> IntegrationFuture<'_> {
+        async move {
+            self.discovery.reset();
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn poll(&self) -> PollFuture<'_> {
+        async move {
+            let username = self.secrets.get(self.name(), "username").await?;
+            let password = self.secrets.get(self.name(), "password").await?;
+            set_credentials(
+                username.map(|username| Credentials::new(&username, &password.unwrap_or_default())),
+            );
+
+            let discovery = if self.discovery.due() {
+                let scan_hosts = self.scan_hosts.lock().unwrap().clone();
+                refresh_tplink_devices(self.pool.clone(), &self.event_bus, &scan_hosts).await
+            } else {
+                Ok(0)
+            };
+            poll_tplink_energy(&self.pool, &mut *self.without_emeter.lock().await).await;
+            discovery
+        }
+        .boxed()
+    }
+
+    fn refresh(&self) -> PollFuture<'_> {
+        self.discovery.reset();
+        self.poll()
+    }
+}
@@ -3,4 +3,12 @@ pub mod types;
 cfg_if::cfg_if! { if #[cfg(feature = "ssr")] {
   mod client;
   pub use client::*;
+  mod driver;
+  pub use driver::*;
+  mod integration;
+  pub use integration::*;
+  #[cfg(test)]
+  mod simulator;
+  #[cfg(test)]
+  mod tests;
 }}
@@ -0,0 +1,532 @@
+//! A fake Kasa device for tests. It answers discovery over UDP and commands
+//! over TCP on port 9999 of a loopback address, like a real device on the
+//! LAN, and keeps the state commands change so tests can read it back.
+//!
+//! Fixtures are dumps in python-kasa's format: the response to every
+//! supported `{module: {method: ...}}`, with `system.get_sysinfo` holding
+//! the device's state. Tapo fixtures instead hold the result of each SMART
+//! method, with `get_device_info` holding the state.
+//!
+//! Devices started with [`SimulatedDevice::start_klap`] stand in for newer
+//! firmware: they answer discovery on 20002 and commands over KLAP on an
+//! HTTP port of their own instead.
+
+use {
+    super::client::{
+        klap::{Credentials, KlapCipher, KlapVersion, auth_hash, client_hash, server_hash},
+        protocol::{KEY, MAX_FRAME_LEN, decrypt, encrypt, read_frame, write_frame},
+    },
+    axum::{
+        Router,
+        body::Bytes,
+        extract::{Query, State},
+        http::{HeaderMap, StatusCode, header},
+        response::IntoResponse,
+        routing::post,
+    },
+    rand::RngCore,
+    serde::Deserialize,
+    serde_json::{Value, json},
+    std::{
+        io,
+        net::{IpAddr, Ipv4Addr, SocketAddr},
+        sync::{Arc, Mutex},
+    },
+    tokio::{
+        net::{TcpListener, UdpSocket},
+        task::JoinHandle,
+    },
+};
+
+pub const HS110_PLUG: &str = include_str!("fixtures/hs110.json");
+pub const KL130_BULB: &str = include_str!("fixtures/kl130.json");
+pub const KS230_DIMMER: &str = include_str!("fixtures/ks230.json");
+pub const HS300_POWER_STRIP: &str = include_str!("fixtures/hs300.json");
+pub const P110_TAPO_PLUG: &str = include_str!("fixtures/p110.json");
+
+const PORT: u16 = 9999;
+const KLAP_DISCOVERY_PORT: u16 = 20002;
+
+pub struct SimulatedDevice {
+    ip: Ipv4Addr,
+    state: Arc<Mutex<Value>>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Drop for SimulatedDevice {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+impl SimulatedDevice {
+    /// Starts answering on `ip`, which should be a loopback address no other
+    /// test uses, e.g. `127.0.0.21`.
+    pub async fn start(ip: Ipv4Addr, fixture: &str) -> io::Result<Self> {
+        let state = Arc::new(Mutex::new(serde_json::from_str::<Value>(fixture)?));
+        let udp = UdpSocket::bind((ip, PORT)).await?;
+        let tcp = TcpListener::bind((ip, PORT)).await?;
+        let tasks = vec![
+            tokio::spawn(serve_udp(udp, state.clone())),
+            tokio::spawn(serve_tcp(tcp, state.clone())),
+        ];
+        Ok(Self { ip, state, tasks })
+    }
+
+    /// Starts answering on `ip` like a device on KLAP firmware bound to
+    /// `credentials`. Fixtures with `get_device_info` answer as Tapo devices.
+    pub async fn start_klap(
+        ip: Ipv4Addr,
+        fixture: &str,
+        credentials: &Credentials,
+    ) -> io::Result<Self> {
+        let state = Arc::new(Mutex::new(serde_json::from_str::<Value>(fixture)?));
+        let smart = state.lock().unwrap().get("get_device_info").is_some();
+        let version = KlapVersion::V2;
+        let server = Arc::new(KlapServer {
+            state: state.clone(),
+            smart,
+            version,
+            auth_hash: auth_hash(version, credentials),
+            session: Mutex::default(),
+        });
+        let http = TcpListener::bind((ip, 0)).await?;
+        let http_port = http.local_addr()?.port();
+        let app = Router::new()
+            .route("/app/handshake1", post(handshake1))
+            .route("/app/handshake2", post(handshake2))
+            .route("/app/request", post(klap_request))
+            .with_state(server);
+
+        let (device_type, device_model) = {
+            let state = state.lock().unwrap();
+            if smart {
+                let info = &state["get_device_info"];
+                (info["type"].clone(), info["model"].clone())
+            } else {
+                let sysinfo = &state["system"]["get_sysinfo"];
+                (json!("IOT.SMARTPLUGSWITCH"), sysinfo["model"].clone())
+            }
+        };
+        let discovery = json!({
+            "error_code": 0,
+            "result": {
+                "device_type": device_type,
+                "device_model": device_model,
+                "ip": ip.to_string(),
+                "mgt_encrypt_schm": {
+                    "is_support_https": false,
+                    "encrypt_type": "KLAP",
+                    "http_port": http_port,
+                    "lv": 2,
+                },
+            },
+        });
+        let udp = UdpSocket::bind((ip, KLAP_DISCOVERY_PORT)).await?;
+        let tasks = vec![
+            tokio::spawn(serve_klap_discovery(udp, discovery)),
+            tokio::spawn(async move {
+                let _ = axum::serve(http, app).await;
+            }),
+        ];
+        Ok(Self { ip, state, tasks })
+    }
+
+    pub fn ip(&self) -> String {
+        self.ip.to_string()
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(self.ip), PORT)
+    }
+
+    /// The current `system.get_sysinfo`.
+    pub fn sysinfo(&self) -> Value {
+        self.state.lock().unwrap()["system"]["get_sysinfo"].clone()
+    }
+
+    /// The current `get_device_info` of a Tapo device.
+    pub fn device_info(&self) -> Value {
+        self.state.lock().unwrap()["get_device_info"].clone()
+    }
+
+    /// The current state of a module other than `system`, e.g. `cnCloud`.
+    pub fn module(&self, module: &str) -> Value {
+        self.state.lock().unwrap()[module].clone()
+    }
+}
+
+async fn serve_klap_discovery(socket: UdpSocket, discovery: Value) {
+    // IronNest skips the header, so leave it blank
+    let response = [&[0u8; 16][..], &serde_json::to_vec(&discovery).unwrap()].concat();
+    let mut buf = vec![0; 1024];
+    while let Ok((_, src)) = socket.recv_from(&mut buf).await {
+        let _ = socket.send_to(&response, src).await;
+    }
+}
+
+struct KlapServer {
+    state: Arc<Mutex<Value>>,
+    smart: bool,
+    version: KlapVersion,
+    auth_hash: Vec<u8>,
+    session: Mutex<Option<KlapServerSession>>,
+}
+
+struct KlapServerSession {
+    cookie: String,
+    local_seed: Vec<u8>,
+    remote_seed: [u8; 16],
+    logged_in: bool,
+}
+
+impl KlapServer {
+    /// The session the request's cookie belongs to, if any.
+    fn session<'a>(
+        session: &'a mut Option<KlapServerSession>,
+        headers: &HeaderMap,
+    ) -> Option<&'a mut KlapServerSession> {
+        let cookie = headers.get(header::COOKIE)?.to_str().ok()?;
+        session.as_mut().filter(|session| session.cookie == cookie)
+    }
+}
+
+async fn handshake1(State(server): State<Arc<KlapServer>>, body: Bytes) -> impl IntoResponse {
+    let mut remote_seed = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut remote_seed);
+    let cookie = format!("TP_SESSIONID={:032X}", rand::random::<u128>());
+    // Answers with the account it's bound to whatever the client knows
+    let hash = server_hash(server.version, &body, &remote_seed, &server.auth_hash);
+    *server.session.lock().unwrap() = Some(KlapServerSession {
+        cookie: cookie.clone(),
+        local_seed: body.to_vec(),
+        remote_seed,
+        logged_in: false,
+    });
+    (
+        [(header::SET_COOKIE, format!("{cookie};TIMEOUT=86400"))],
+        [&remote_seed[..], &hash].concat(),
+    )
+}
+
+async fn handshake2(
+    State(server): State<Arc<KlapServer>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    let mut session = server.session.lock().unwrap();
+    let Some(session) = KlapServer::session(&mut session, &headers) else {
+        return StatusCode::FORBIDDEN;
+    };
+    let expected = client_hash(
+        server.version,
+        &session.local_seed,
+        &session.remote_seed,
+        &server.auth_hash,
+    );
+    if body[..] != expected {
+        return StatusCode::FORBIDDEN;
+    }
+    session.logged_in = true;
+    StatusCode::OK
+}
+
+#[derive(Deserialize)]
+struct Seq {
+    seq: i32,
+}
+
+async fn klap_request(
+    State(server): State<Arc<KlapServer>>,
+    Query(Seq { seq }): Query<Seq>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Vec<u8>, StatusCode> {
+    let cipher = {
+        let mut session = server.session.lock().unwrap();
+        match KlapServer::session(&mut session, &headers) {
+            Some(session) if session.logged_in => {
+                KlapCipher::new(&session.local_seed, &session.remote_seed, &server.auth_hash)
+            }
+            _ => return Err(StatusCode::FORBIDDEN),
+        }
+    };
+    let request = cipher
+        .decrypt(seq, &body)
+        .ok()
+        .and_then(|request| serde_json::from_slice::<Value>(&request).ok())
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    let response = {
+        let mut state = server.state.lock().unwrap();
+        if server.smart {
+            handle_smart(&mut state, &request)
+        } else {
+            handle(&mut state, &request)
+        }
+    };
+    Ok(cipher.encrypt(seq, &serde_json::to_vec(&response).unwrap()))
+}
+
+/// Answers a SMART `{"method", "params"}` request the way a Tapo device
+/// would.
+fn handle_smart(state: &mut Value, request: &Value) -> Value {
+    let method = request["method"].as_str().unwrap_or_default();
+    match method {
+        "set_device_info" => {
+            for (field, value) in request["params"].as_object().into_iter().flatten() {
+                state["get_device_info"][field] = value.clone();
+            }
+            json!({"error_code": 0})
+        }
+        "device_reboot" => json!({"error_code": 0}),
+        method => match state.get(method) {
+            Some(result) => json!({"error_code": 0, "result": result}),
+            None => json!({"error_code": -1, "msg": "Method not support"}),
+        },
+    }
+}
+
+async fn serve_udp(socket: UdpSocket, state: Arc<Mutex<Value>>) {
+    let mut buf = vec![0; MAX_FRAME_LEN];
+    while let Ok((len, src)) = socket.recv_from(&mut buf).await {
+        let Ok(request) = serde_json::from_slice::<Value>(&decrypt(&buf[..len], KEY)) else {
+            continue;
+        };
+        let response = handle(&mut state.lock().unwrap(), &request);
+        let response = encrypt(&serde_json::to_vec(&response).unwrap(), KEY);
+        let _ = socket.send_to(&response, src).await;
+    }
+}
+
+async fn serve_tcp(listener: TcpListener, state: Arc<Mutex<Value>>) {
+    while let Ok((mut stream, _)) = listener.accept().await {
+        let state = state.clone();
+        tokio::spawn(async move {
+            // Clients send one request per connection, but nothing stops
+            // them from sending more
+            while let Ok(request) = read_frame(&mut stream).await {
+                let Ok(request) = serde_json::from_slice::<Value>(&request) else {
+                    return;
+                };
+                let response = handle(&mut state.lock().unwrap(), &request);
+                let response = serde_json::to_vec(&response).unwrap();
+                if write_frame(&mut stream, &response).await.is_err() {
+                    return;
+                }
+            }
+        });
+    }
+}
+
+fn error(err_code: i64, err_msg: &str) -> Value {
+    json!({"err_code": err_code, "err_msg": err_msg})
+}
+
+/// Answers each `{module: {method: params}}` of `request` the way a device
+/// would, updating `state` for the commands IronNest sends.
+fn handle(state: &mut Value, request: &Value) -> Value {
+    let child_ids = request["context"]["child_ids"]
+        .as_array()
+        .map(|ids| {
+            ids.iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let mut response = json!({});
+    for (module, methods) in request.as_object().into_iter().flatten() {
+        if module == "context" {
+            continue;
+        }
+        if state.get(module).is_none() {
+            response[module] = error(-1, "module not support");
+            continue;
+        }
+        let mut results = json!({});
+        for (method, params) in methods.as_object().into_iter().flatten() {
+            results[method] = call(state, &child_ids, module, method, params);
+        }
+        response[module] = results;
+    }
+    response
+}
+
+fn call(
+    state: &mut Value,
+    child_ids: &[String],
+    module: &str,
+    method: &str,
+    params: &Value,
+) -> Value {
+    let ok = json!({"err_code": 0});
+    let sysinfo = &mut state["system"]["get_sysinfo"];
+    match (module, method) {
+        ("system", "get_sysinfo") => sysinfo.clone(),
+        // Without a context a power strip switches every outlet
+        ("system", "set_relay_state") if child_ids.is_empty() && sysinfo["children"].is_array() => {
+            for child in sysinfo["children"].as_array_mut().into_iter().flatten() {
+                child["state"] = params["state"].clone();
+            }
+            ok
+        }
+        ("system", "set_relay_state") => {
+            set_on_device_or_children(sysinfo, child_ids, "relay_state", "state", &params["state"]);
+            ok
+        }
+        // Outlets that are off draw nothing, the others what the fixture has
+        ("emeter", "get_realtime") if !child_ids.is_empty() => {
+            let on = sysinfo["children"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|child| is_selected(child, child_ids))
+                .any(|child| child["state"] == 1);
+            let mut realtime = state["emeter"]["get_realtime"].clone();
+            if !on {
+                realtime["current_ma"] = json!(0);
+                realtime["power_mw"] = json!(0);
+            }
+            realtime
+        }
+        ("system" | "smartlife.iot.common.sysinfo", "set_dev_alias") => {
+            set_on_device_or_children(sysinfo, child_ids, "alias", "alias", &params["alias"]);
+            ok
+        }
+        ("system" | "smartlife.iot.common.system", "reboot") => ok,
+        ("system", "set_led_off") => {
+            sysinfo["led_off"] = params["off"].clone();
+            ok
+        }
+        ("cnCloud" | "smartlife.iot.common.cloud", "unbind") => {
+            state[module]["get_info"]["binded"] = json!(0);
+            state[module]["get_info"]["username"] = json!("");
+            ok
+        }
+        // Kept where a test can see which network the device was moved to
+        ("netif" | "smartlife.iot.common.softaponboarding", "set_stainfo") => {
+            if params["ssid"].as_str().is_none_or(str::is_empty) || params.get("key_type").is_none()
+            {
+                return error(-3, "invalid argument");
+            }
+            state[module]["stainfo"] = params.clone();
+            ok
+        }
+        ("smartlife.iot.smartbulb.lightingservice", "get_light_state") => {
+            sysinfo["light_state"].clone()
+        }
+        ("smartlife.iot.smartbulb.lightingservice", "transition_light_state") => {
+            for (field, value) in params.as_object().into_iter().flatten() {
+                if field != "transition_period" {
+                    sysinfo["light_state"][field] = value.clone();
+                }
+            }
+            let mut light_state = sysinfo["light_state"].clone();
+            light_state["err_code"] = json!(0);
+            light_state
+        }
+        ("smartlife.iot.smartbulb.lightingservice", "set_preferred_state") => {
+            let mut presets = sysinfo["preferred_state"]
+                .as_array_mut()
+                .into_iter()
+                .flatten();
+            match presets.find(|preset| preset["index"] == params["index"]) {
+                Some(preset) => {
+                    *preset = params.clone();
+                    ok
+                }
+                None => error(-3, "invalid argument"),
+            }
+        }
+        ("smartlife.iot.dimmer", "set_dimmer_transition") => {
+            sysinfo["brightness"] = params["brightness"].clone();
+            ok
+        }
+        ("smartlife.iot.dimmer", "set_cold_time") => ok,
+        ("schedule" | "count_down", method) if method.ends_with("_rule") => {
+            edit_rules(&mut state[module], module, method, params)
+        }
+        // Anything else the fixture has a canned response for
+        (module, method) => match state[module].get(method) {
+            Some(response) => response.clone(),
+            None => error(-2, "method not support"),
+        },
+    }
+}
+
+/// Adds, edits and deletes the `rule_list` of a rule module. Rules aren't
+/// kept per power strip outlet.
+fn edit_rules(module_state: &mut Value, module: &str, method: &str, params: &Value) -> Value {
+    let Some(rules) = module_state["get_rules"]["rule_list"].as_array_mut() else {
+        return error(-2, "method not support");
+    };
+    let required: &[&str] = match module {
+        "schedule" => &["enable", "wday", "stime_opt", "smin", "sact"],
+        _ => &["enable", "delay", "act"],
+    };
+    if method != "delete_rule" && required.iter().any(|field| params.get(field).is_none()) {
+        return error(-3, "invalid argument");
+    }
+    let position = |rules: &[Value]| rules.iter().position(|rule| rule["id"] == params["id"]);
+    match method {
+        "add_rule" => {
+            // Only one countdown can be set at a time
+            if module == "count_down" && !rules.is_empty() {
+                return error(-10, "table is full");
+            }
+            let id = format!("{:032X}", rand::random::<u128>());
+            let mut rule = params.clone();
+            rule["id"] = json!(id);
+            if module == "count_down" {
+                rule["remain"] = rule["delay"].clone();
+            }
+            rules.push(rule);
+            json!({"id": id, "err_code": 0})
+        }
+        "edit_rule" => match position(rules) {
+            Some(index) => {
+                rules[index] = params.clone();
+                json!({"err_code": 0})
+            }
+            None => error(-14, "entry not exist"),
+        },
+        "delete_rule" => match position(rules) {
+            Some(index) => {
+                rules.remove(index);
+                json!({"err_code": 0})
+            }
+            None => error(-14, "entry not exist"),
+        },
+        _ => error(-2, "method not support"),
+    }
+}
+
+/// Power strips address outlets through `context.child_ids`, the strip's
+/// `deviceId` followed by the outlet's index. Fixtures report either that or
+/// just the index as the outlet's `id`.
+fn is_selected(child: &Value, child_ids: &[String]) -> bool {
+    let id = child["id"].as_str().unwrap_or_default();
+    child_ids.iter().any(|child_id| child_id.ends_with(id))
+}
+
+fn set_on_device_or_children(
+    sysinfo: &mut Value,
+    child_ids: &[String],
+    field: &str,
+    child_field: &str,
+    value: &Value,
+) {
+    if child_ids.is_empty() {
+        sysinfo[field] = value.clone();
+        return;
+    }
+    let children = sysinfo["children"].as_array_mut().into_iter().flatten();
+    for child in children {
+        if is_selected(child, child_ids) {
+            child[child_field] = value.clone();
+        }
+    }
+}
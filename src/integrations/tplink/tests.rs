@@ -0,0 +1,556 @@
+//! Discovery and commands against simulated devices on loopback. Each test
+//! uses its own addresses so they can run in parallel.
+
+use {
+    super::{
+        KasaLightDriver, KasaPowerStripDriver,
+        client::{
+            COUNT_DOWN, Credentials, SCHEDULE, TplinkError, discover_devices_at, send,
+            set_credentials, tplink_add_rule, tplink_cloud_unbind, tplink_delete_rule,
+            tplink_edit_rule, tplink_get_cloud_info, tplink_get_rules, tplink_get_sysinfo,
+            tplink_get_wifi_networks, tplink_kasa_get_emeter_realtime, tplink_reboot,
+            tplink_set_all_smart_strip_sockets, tplink_set_dimmer_brightness, tplink_set_led_off,
+            tplink_set_light_hsv, tplink_set_light_preset, tplink_set_smart_strip_socket_alias,
+            tplink_set_wifi, tplink_turn_plug_off, tplink_turn_plug_on,
+            tplink_turn_smart_strip_socket_on,
+        },
+        simulator::{
+            HS110_PLUG, HS300_POWER_STRIP, KL130_BULB, KS230_DIMMER, P110_TAPO_PLUG,
+            SimulatedDevice,
+        },
+        types::{
+            CountdownRule, DeviceData, GetSysInfo, KasaFamily, PreferredState, RuleTime,
+            ScheduleRule, color_temp_range,
+        },
+    },
+    crate::integrations::iron_nest::{
+        driver::{DeviceDriver, DriverError},
+        types::{Device, DeviceState, DeviceType, IntegrationSettings},
+    },
+    chrono::Utc,
+    serde_json::json,
+    std::{net::Ipv4Addr, time::Duration},
+};
+
+async fn simulate(last_octet: u8, fixture: &str) -> SimulatedDevice {
+    SimulatedDevice::start(Ipv4Addr::new(127, 0, 0, last_octet), fixture)
+        .await
+        .unwrap()
+}
+
+fn device(device_type: DeviceType, ip: String, child_id: Option<String>) -> Device {
+    Device {
+        id: 1,
+        name: "Simulated".to_string(),
+        device_type,
+        ip,
+        power_state: 0,
+        battery_percentage: 0,
+        last_seen: Utc::now(),
+        mac_address: None,
+        child_id,
+        room_id: None,
+        tags: Vec::new(),
+        display_name: None,
+        icon: None,
+        online: true,
+    }
+}
+
+#[tokio::test]
+async fn test_discover_devices() {
+    let devices = [
+        simulate(31, HS110_PLUG).await,
+        simulate(32, KL130_BULB).await,
+        simulate(33, KS230_DIMMER).await,
+        simulate(34, HS300_POWER_STRIP).await,
+    ];
+    let targets = devices
+        .iter()
+        .map(SimulatedDevice::addr)
+        .collect::<Vec<_>>();
+
+    let mut found = discover_devices_at(&targets, Duration::from_millis(300))
+        .await
+        .unwrap();
+    assert_eq!(found.len(), 4);
+    found.sort_by_key(|device| match device {
+        DeviceData::SmartPlug(data) | DeviceData::SmartDimmer(data) => data.ip,
+        DeviceData::SmartLight(data) => data.ip,
+        DeviceData::SmartPowerStrip(data) => data.ip,
+    });
+
+    let [plug, bulb, dimmer, strip] = &found[..] else {
+        panic!("expected 4 devices, found {found:?}");
+    };
+    let DeviceData::SmartPlug(plug) = plug else {
+        panic!("expected a plug, found {plug:?}");
+    };
+    assert_eq!(plug.alias, "Fish Tank Pump");
+    assert_eq!(plug.ip, Some(Ipv4Addr::new(127, 0, 0, 31).into()));
+    let DeviceData::SmartLight(bulb) = bulb else {
+        panic!("expected a bulb, found {bulb:?}");
+    };
+    assert_eq!(bulb.light_state.color_temp, Some(2700));
+    let DeviceData::SmartDimmer(dimmer) = dimmer else {
+        panic!("expected a dimmer, found {dimmer:?}");
+    };
+    assert_eq!(dimmer.brightness, Some(40));
+    // The strip's sysinfo doesn't fit in the 1024 byte read `send` used to do
+    let DeviceData::SmartPowerStrip(strip) = strip else {
+        panic!("expected a power strip, found {strip:?}");
+    };
+    assert_eq!(strip.children.len(), 6);
+    assert_eq!(strip.children[0].alias, "Aquarium Heater");
+}
+
+#[tokio::test]
+async fn test_plug_and_dimmer_commands() {
+    let plug = simulate(41, HS110_PLUG).await;
+    tplink_turn_plug_on(&plug.ip()).await.unwrap();
+    assert_eq!(plug.sysinfo()["relay_state"], 1);
+    let GetSysInfo::TPLinkDiscoveryData(sysinfo) = tplink_get_sysinfo(&plug.ip()).await.unwrap()
+    else {
+        panic!("expected plug sysinfo");
+    };
+    assert_eq!(sysinfo.relay_state, 1);
+    tplink_turn_plug_off(&plug.ip()).await.unwrap();
+    assert_eq!(plug.sysinfo()["relay_state"], 0);
+
+    let realtime = tplink_kasa_get_emeter_realtime(&plug.ip(), None)
+        .await
+        .unwrap();
+    assert_eq!(realtime.power(), Some(48.21));
+
+    let dimmer = simulate(42, KS230_DIMMER).await;
+    tplink_set_dimmer_brightness(&dimmer.ip(), &80)
+        .await
+        .unwrap();
+    assert_eq!(dimmer.sysinfo()["brightness"], 80);
+    let err = tplink_kasa_get_emeter_realtime(&dimmer.ip(), None)
+        .await
+        .unwrap_err();
+    assert!(err.is_unsupported(), "{err}");
+}
+
+#[tokio::test]
+async fn test_light_commands() {
+    let bulb = simulate(51, KL130_BULB).await;
+    tplink_set_light_hsv(&bulb.ip(), 240, 100, 60)
+        .await
+        .unwrap();
+    let light = device(DeviceType::KasaLight, bulb.ip(), None);
+    assert_eq!(
+        KasaLightDriver.get_state(&light).await.unwrap(),
+        Some(DeviceState {
+            on: true,
+            brightness: Some(60),
+            hue: Some(240),
+            saturation: Some(100),
+            color_temp: Some(0),
+        })
+    );
+
+    KasaLightDriver.set_power(&light, false).await.unwrap();
+    assert_eq!(bulb.sysinfo()["light_state"]["on_off"], 0);
+
+    let res = send(&bulb.ip(), json!({"system": {"no_such_method": {}}}))
+        .await
+        .unwrap();
+    assert_eq!(res["system"]["no_such_method"]["err_code"], -2);
+}
+
+#[tokio::test]
+async fn test_light_color_temp_and_presets() {
+    assert_eq!(color_temp_range("KL120(US)"), (2700, 5000));
+    assert_eq!(color_temp_range("KL130(US)"), (2500, 9000));
+    assert_eq!(color_temp_range("KL999(US)"), (2700, 5000));
+
+    let bulb = simulate(52, KL130_BULB).await;
+    let light = device(DeviceType::KasaLight, bulb.ip(), None);
+    let err = KasaLightDriver
+        .set_color_temp(&light, 1000)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, DriverError::InvalidArgs(_)), "{err}");
+    KasaLightDriver.set_color_temp(&light, 6500).await.unwrap();
+    assert_eq!(bulb.sysinfo()["light_state"]["color_temp"], 6500);
+
+    // The second preset is a blue
+    KasaLightDriver.apply_preset(&light, 1).await.unwrap();
+    let light_state = bulb.sysinfo()["light_state"].clone();
+    assert_eq!(light_state["hue"], 240);
+    assert_eq!(light_state["saturation"], 100);
+    assert_eq!(light_state["color_temp"], 0);
+    assert!(KasaLightDriver.apply_preset(&light, 7).await.is_err());
+
+    let warm = DeviceState {
+        on: true,
+        brightness: Some(30),
+        color_temp: Some(2700),
+        ..DeviceState::default()
+    };
+    KasaLightDriver.fade_to(&light, &warm, 1500).await.unwrap();
+    assert_eq!(
+        KasaLightDriver.get_state(&light).await.unwrap().unwrap(),
+        DeviceState {
+            hue: Some(240),
+            saturation: Some(100),
+            ..warm
+        }
+    );
+
+    let preset = PreferredState {
+        index: 0,
+        hue: 0,
+        saturation: 0,
+        color_temp: 4000,
+        brightness: 80,
+    };
+    tplink_set_light_preset(&bulb.ip(), &preset).await.unwrap();
+    assert_eq!(bulb.sysinfo()["preferred_state"][0]["color_temp"], 4000);
+}
+
+#[tokio::test]
+async fn test_power_strip_commands() {
+    let strip = simulate(61, HS300_POWER_STRIP).await;
+    let device_id = strip.sysinfo()["deviceId"].as_str().unwrap().to_string();
+    let outlet_id = format!("{device_id}03");
+
+    tplink_turn_smart_strip_socket_on(&strip.ip(), &outlet_id)
+        .await
+        .unwrap();
+    tplink_set_smart_strip_socket_alias(&strip.ip(), &outlet_id, "Lamp")
+        .await
+        .unwrap();
+    let children = strip.sysinfo()["children"].clone();
+    assert_eq!(children[3]["state"], 1);
+    assert_eq!(children[3]["alias"], "Lamp");
+    assert_eq!(children[2]["state"], 0);
+
+    let outlet = device(DeviceType::KasaPowerStrip, strip.ip(), Some(outlet_id));
+    assert!(
+        KasaPowerStripDriver
+            .get_state(&outlet)
+            .await
+            .unwrap()
+            .unwrap()
+            .on
+    );
+    KasaPowerStripDriver
+        .set_power(&outlet, false)
+        .await
+        .unwrap();
+    assert_eq!(strip.sysinfo()["children"][3]["state"], 0);
+}
+
+#[tokio::test]
+async fn test_power_strip_outlets() {
+    // Current firmware reports outlet ids with the strip's id prepended
+    let mut fixture = serde_json::from_str::<serde_json::Value>(HS300_POWER_STRIP).unwrap();
+    let sysinfo = &mut fixture["system"]["get_sysinfo"];
+    let device_id = sysinfo["deviceId"].as_str().unwrap().to_string();
+    for child in sysinfo["children"].as_array_mut().unwrap() {
+        child["id"] = json!(format!("{device_id}{}", child["id"].as_str().unwrap()));
+    }
+    let strip = simulate(62, &fixture.to_string()).await;
+
+    let GetSysInfo::TPLinkSmartPowerStripData(sysinfo) =
+        tplink_get_sysinfo(&strip.ip()).await.unwrap()
+    else {
+        panic!("expected a power strip");
+    };
+    let outlet_id = format!("{device_id}01");
+    assert_eq!(sysinfo.outlet_child_id(&sysinfo.children[1]), outlet_id);
+    assert_eq!(sysinfo.outlet(&outlet_id).unwrap().alias, "Outlet 2");
+
+    tplink_set_all_smart_strip_sockets(&strip.ip(), true)
+        .await
+        .unwrap();
+    assert!(
+        strip.sysinfo()["children"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|child| child["state"] == 1)
+    );
+
+    let outlet = device(
+        DeviceType::KasaPowerStrip,
+        strip.ip(),
+        Some(outlet_id.clone()),
+    );
+    KasaPowerStripDriver
+        .set_power(&outlet, false)
+        .await
+        .unwrap();
+    let state = KasaPowerStripDriver.get_state(&outlet).await.unwrap();
+    assert!(!state.unwrap().on);
+    assert_eq!(strip.sysinfo()["children"][0]["state"], 1);
+
+    // Each outlet has its own emeter
+    let off = tplink_kasa_get_emeter_realtime(&strip.ip(), Some(&outlet_id))
+        .await
+        .unwrap();
+    assert_eq!(off.power(), Some(0.));
+    let on = tplink_kasa_get_emeter_realtime(&strip.ip(), Some(&format!("{device_id}00")))
+        .await
+        .unwrap();
+    assert_eq!(on.power(), Some(12.5));
+}
+
+#[tokio::test]
+async fn test_device_management() {
+    let plug = simulate(101, HS110_PLUG).await;
+
+    tplink_set_led_off(&plug.ip(), true).await.unwrap();
+    assert_eq!(plug.sysinfo()["led_off"], 1);
+    tplink_reboot(&plug.ip(), KasaFamily::Plug).await.unwrap();
+
+    let cloud = tplink_get_cloud_info(&plug.ip(), KasaFamily::Plug)
+        .await
+        .unwrap();
+    assert_eq!(cloud.binded, 1);
+    assert_eq!(cloud.username, "owner@example.com");
+    tplink_cloud_unbind(&plug.ip(), KasaFamily::Plug)
+        .await
+        .unwrap();
+    let cloud = tplink_get_cloud_info(&plug.ip(), KasaFamily::Plug)
+        .await
+        .unwrap();
+    assert_eq!(cloud.binded, 0);
+
+    let networks = tplink_get_wifi_networks(&plug.ip(), KasaFamily::Plug)
+        .await
+        .unwrap();
+    assert_eq!(networks.len(), 2);
+    assert_eq!(networks[0].ssid, "HomeNet");
+    tplink_set_wifi(&plug.ip(), KasaFamily::Plug, &networks[0], "hunter22")
+        .await
+        .unwrap();
+    let stainfo = &plug.module("netif")["stainfo"];
+    assert_eq!(stainfo["ssid"], "HomeNet");
+    assert_eq!(stainfo["key_type"], 3);
+    assert_eq!(stainfo["password"], "hunter22");
+
+    // The bulb fixture has no cloud module
+    let bulb = simulate(102, KL130_BULB).await;
+    let err = tplink_get_cloud_info(&bulb.ip(), KasaFamily::Light)
+        .await
+        .unwrap_err();
+    assert!(err.is_unsupported(), "{err}");
+}
+
+#[tokio::test]
+async fn test_unreachable_device() {
+    // Nothing listens on this address, so the connection is refused rather
+    // than timing out
+    let err = tplink_get_sysinfo("127.0.0.71").await.unwrap_err();
+    assert!(matches!(err, TplinkError::Io(_)), "{err}");
+    assert!(matches!(
+        tplink_get_sysinfo("not an ip").await,
+        Err(TplinkError::InvalidIp(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_scan_range_discovery() {
+    // On a /25, one device in each batch of probes
+    let plug = SimulatedDevice::start(Ipv4Addr::new(127, 0, 1, 10), HS110_PLUG)
+        .await
+        .unwrap();
+    let bulb = SimulatedDevice::start(Ipv4Addr::new(127, 0, 1, 100), KL130_BULB)
+        .await
+        .unwrap();
+    let settings = IntegrationSettings {
+        scan_targets: vec!["127.0.1.10".into(), "127.0.1.0/25".into()],
+        ..IntegrationSettings::default()
+    };
+    assert_eq!(settings.validate(), Ok(()));
+    let hosts = settings.scan_hosts();
+    assert_eq!(hosts.len(), 126);
+
+    // The plug is asked twice, like a device that also answers the broadcast
+    let targets = std::iter::once(plug.addr())
+        .chain(hosts.into_iter().map(|ip| (ip, plug.addr().port()).into()))
+        .collect::<Vec<_>>();
+    let found = discover_devices_at(&targets, Duration::from_millis(300))
+        .await
+        .unwrap();
+    let mut ips = found
+        .iter()
+        .map(|device| match device {
+            DeviceData::SmartPlug(data) | DeviceData::SmartDimmer(data) => data.ip,
+            DeviceData::SmartLight(data) => data.ip,
+            DeviceData::SmartPowerStrip(data) => data.ip,
+        })
+        .collect::<Vec<_>>();
+    ips.sort();
+    assert_eq!(
+        ips,
+        [Some(plug.addr().ip()), Some(bulb.addr().ip())],
+        "found {found:?}"
+    );
+}
+
+#[tokio::test]
+async fn test_klap_kasa_plug() {
+    // Never bound to an account, so it takes the default credentials
+    let plug = SimulatedDevice::start_klap(
+        Ipv4Addr::new(127, 0, 0, 81),
+        HS110_PLUG,
+        &Credentials::new("kasa@tp-link.net", "kasaSetup"),
+    )
+    .await
+    .unwrap();
+
+    let found = discover_devices_at(&[plug.addr()], Duration::from_millis(300))
+        .await
+        .unwrap();
+    let [DeviceData::SmartPlug(sysinfo)] = &found[..] else {
+        panic!("expected a plug, found {found:?}");
+    };
+    assert_eq!(sysinfo.alias, "Fish Tank Pump");
+    assert_eq!(sysinfo.ip, Some(Ipv4Addr::new(127, 0, 0, 81).into()));
+
+    tplink_turn_plug_on(&plug.ip()).await.unwrap();
+    assert_eq!(plug.sysinfo()["relay_state"], 1);
+    let realtime = tplink_kasa_get_emeter_realtime(&plug.ip(), None)
+        .await
+        .unwrap();
+    assert_eq!(realtime.power(), Some(48.21));
+}
+
+#[tokio::test]
+async fn test_klap_tapo_plug() {
+    let credentials = Credentials::new("owner@example.com", "correct horse");
+    let plug =
+        SimulatedDevice::start_klap(Ipv4Addr::new(127, 0, 0, 82), P110_TAPO_PLUG, &credentials)
+            .await
+            .unwrap();
+    set_credentials(Some(credentials));
+
+    // Not discovered, so the transport is found when the legacy port refuses
+    tplink_turn_plug_on(&plug.ip()).await.unwrap();
+    assert_eq!(plug.device_info()["device_on"], true);
+
+    let found = discover_devices_at(&[plug.addr()], Duration::from_millis(300))
+        .await
+        .unwrap();
+    let [DeviceData::SmartPlug(sysinfo)] = &found[..] else {
+        panic!("expected a plug, found {found:?}");
+    };
+    assert_eq!(sysinfo.alias, "Space Heater");
+    assert_eq!(sysinfo.model, "P110");
+    assert_eq!(sysinfo.relay_state, 1);
+
+    tplink_turn_plug_off(&plug.ip()).await.unwrap();
+    assert_eq!(plug.device_info()["device_on"], false);
+    let realtime = tplink_kasa_get_emeter_realtime(&plug.ip(), None)
+        .await
+        .unwrap();
+    assert_eq!(realtime.power(), Some(1325.));
+}
+
+#[tokio::test]
+async fn test_klap_wrong_credentials() {
+    let plug = SimulatedDevice::start_klap(
+        Ipv4Addr::new(127, 0, 0, 83),
+        P110_TAPO_PLUG,
+        &Credentials::new("someone-else@example.com", "hunter2"),
+    )
+    .await
+    .unwrap();
+    let err = tplink_turn_plug_on(&plug.ip()).await.unwrap_err();
+    assert!(matches!(err, TplinkError::Auth), "{err}");
+    assert_eq!(plug.device_info()["device_on"], false);
+}
+
+#[tokio::test]
+async fn test_schedule_and_countdown_rules() {
+    let plug = simulate(91, HS110_PLUG).await;
+    let ip = plug.ip();
+
+    // Made in the Kasa app
+    let rules = tplink_get_rules::<ScheduleRule>(&ip, None, SCHEDULE)
+        .await
+        .unwrap();
+    let [pump_on] = &rules[..] else {
+        panic!("expected one rule, found {rules:?}");
+    };
+    assert_eq!(pump_on.time_label(), "07:00");
+    assert_eq!(pump_on.sact, 1);
+
+    let sunset = ScheduleRule {
+        name: "Lights out".to_string(),
+        wday: [0, 1, 1, 1, 1, 1, 0],
+        stime_opt: RuleTime::Sunset,
+        sact: 0,
+        ..ScheduleRule::default()
+    };
+    let id = tplink_add_rule(&ip, None, SCHEDULE, &sunset).await.unwrap();
+    tplink_edit_rule(
+        &ip,
+        None,
+        SCHEDULE,
+        &ScheduleRule {
+            enable: 0,
+            ..pump_on.clone()
+        },
+    )
+    .await
+    .unwrap();
+    let rules = tplink_get_rules::<ScheduleRule>(&ip, None, SCHEDULE)
+        .await
+        .unwrap();
+    assert_eq!(rules[0].enable, 0);
+    assert_eq!(
+        rules[1],
+        ScheduleRule {
+            id: Some(id.clone()),
+            ..sunset.clone()
+        }
+    );
+
+    let err = tplink_edit_rule(&ip, None, SCHEDULE, &sunset)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, TplinkError::InvalidInput(_)), "{err}");
+    tplink_delete_rule(&ip, None, SCHEDULE, &id).await.unwrap();
+    assert_eq!(
+        tplink_get_rules::<ScheduleRule>(&ip, None, SCHEDULE)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    let countdown = CountdownRule {
+        name: "Feeding".to_string(),
+        enable: 1,
+        delay: 600,
+        act: 1,
+        ..CountdownRule::default()
+    };
+    let id = tplink_add_rule(&ip, None, COUNT_DOWN, &countdown)
+        .await
+        .unwrap();
+    // Devices keep a single countdown
+    let err = tplink_add_rule(&ip, None, COUNT_DOWN, &countdown)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, TplinkError::Device { .. }), "{err}");
+    let countdowns = tplink_get_rules::<CountdownRule>(&ip, None, COUNT_DOWN)
+        .await
+        .unwrap();
+    assert_eq!(countdowns[0].remain, Some(600));
+    tplink_delete_rule(&ip, None, COUNT_DOWN, &id)
+        .await
+        .unwrap();
+
+    let bulb = simulate(92, KL130_BULB).await;
+    let err = tplink_get_rules::<CountdownRule>(&bulb.ip(), None, COUNT_DOWN)
+        .await
+        .unwrap_err();
+    assert!(err.is_unsupported(), "{err}");
+}
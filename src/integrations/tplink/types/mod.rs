@@ -1,4 +1,5 @@
 use {
+    crate::integrations::iron_nest::types::{Device, DeviceType},
     serde::{Deserialize, Serialize},
     std::net::IpAddr,
 };
@@ -53,6 +54,26 @@ pub struct TPLinkSmartPowerStripRes {
     pub ip: Option<IpAddr>,
 }
 
+impl TPLinkSmartPowerStripRes {
+    /// The id `outlet` is addressed by in `context.child_ids`, also stored as
+    /// its device's `child_id`. Most firmware reports that full id, the
+    /// strip's `deviceId` followed by the outlet's index, but some only the
+    /// index.
+    pub fn outlet_child_id(&self, outlet: &Child) -> String {
+        if outlet.id.starts_with(&self.device_id) {
+            outlet.id.clone()
+        } else {
+            format!("{}{}", self.device_id, outlet.id)
+        }
+    }
+
+    pub fn outlet(&self, child_id: &str) -> Option<&Child> {
+        self.children
+            .iter()
+            .find(|outlet| self.outlet_child_id(outlet) == child_id)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Child {
     pub alias: String,
@@ -92,6 +113,9 @@ pub struct TPLinkDiscoveryData {
     pub oem_id: String,
     pub on_time: i64,
     pub relay_state: i32,
+    /// Only reported by dimmers.
+    #[serde(default)]
+    pub brightness: Option<u8>,
     pub rssi: i64,
     pub status: String,
     pub sw_ver: String,
@@ -110,12 +134,51 @@ pub enum DeviceData {
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct TPLinkSmartLightData {
     pub alias: String,
+    #[serde(default)]
+    pub model: String,
+    #[serde(default)]
+    pub mic_mac: Option<String>,
     pub light_state: LightState,
     pub is_dimmable: u8,
     pub is_color: u8,
+    #[serde(default)]
+    pub is_variable_color_temp: u8,
+    /// The presets saved in the Kasa app, by index.
+    #[serde(default)]
+    pub preferred_state: Vec<PreferredState>,
     pub ip: Option<IpAddr>,
 }
 
+impl TPLinkSmartLightData {
+    /// The lowest and highest color temperature in kelvin, `None` for bulbs
+    /// with a fixed white.
+    pub fn color_temp_range(&self) -> Option<(u16, u16)> {
+        (self.is_variable_color_temp != 0).then(|| color_temp_range(&self.model))
+    }
+}
+
+/// Kelvin range of a bulb model, from python-kasa's table. Models it doesn't
+/// list get the narrowest common range.
+pub fn color_temp_range(model: &str) -> (u16, u16) {
+    const RANGES: &[(&str, (u16, u16))] = &[
+        ("KL120(EU)", (2700, 6500)),
+        ("KL120(US)", (2700, 5000)),
+        ("KL125", (2500, 6500)),
+        ("KL130", (2500, 9000)),
+        ("KL135", (2500, 6500)),
+        ("KL430", (2500, 9000)),
+        ("LB120", (2700, 6500)),
+        ("LB130", (2500, 9000)),
+        ("LB230", (2500, 9000)),
+        ("KB130", (2500, 9000)),
+    ];
+    RANGES
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|(_, range)| *range)
+        .unwrap_or((2700, 5000))
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct ControlProtocols {
     pub name: String,
@@ -125,6 +188,17 @@ pub struct ControlProtocols {
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct LightState {
     pub on_off: i32,
+    #[serde(default)]
+    pub hue: Option<u16>,
+    #[serde(default)]
+    pub saturation: Option<u8>,
+    #[serde(default)]
+    pub color_temp: Option<u16>,
+    #[serde(default)]
+    pub brightness: Option<u8>,
+    /// The state the bulb turns on to, only reported while it's off.
+    #[serde(default)]
+    pub dft_on_state: Option<DefaultOnState>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -136,7 +210,9 @@ pub struct DefaultOnState {
     pub brightness: u32,
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+/// A preset saved on a bulb. A non-zero `color_temp` is a white preset,
+/// otherwise it's a color.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
 pub struct PreferredState {
     pub index: u8,
     pub hue: u32,
@@ -144,3 +220,275 @@ pub struct PreferredState {
     pub color_temp: u32,
     pub brightness: u32,
 }
+
+/// What the light controls in the device modal need from a Kasa bulb.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct KasaLight {
+    pub color_temp_range: Option<(u16, u16)>,
+    pub presets: Vec<PreferredState>,
+    /// The current white, 0 while the bulb shows a color.
+    pub color_temp: Option<u16>,
+}
+
+/// Parameters of `transition_light_state`. Fields left `None` keep their
+/// current value.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct LightStateChange {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_off: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hue: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub saturation: Option<u8>,
+    /// 0 switches the bulb to color mode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_temp: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brightness: Option<u8>,
+    /// Milliseconds to fade over.
+    pub transition_period: u32,
+}
+
+impl From<&PreferredState> for LightStateChange {
+    fn from(preset: &PreferredState) -> Self {
+        let color = preset.color_temp == 0;
+        Self {
+            on_off: Some(1),
+            hue: color.then(|| preset.hue.try_into().ok()).flatten(),
+            saturation: color.then(|| preset.saturation.try_into().ok()).flatten(),
+            color_temp: preset.color_temp.try_into().ok(),
+            brightness: preset.brightness.try_into().ok(),
+            transition_period: 0,
+        }
+    }
+}
+
+/// Response to `emeter.get_realtime`. Older hardware reports volts, amps,
+/// watts and kWh while newer revisions report milli-units and watt hours, so
+/// only one set of fields is present.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct EmeterRealtime {
+    pub voltage: Option<f64>,
+    pub current: Option<f64>,
+    pub power: Option<f64>,
+    pub total: Option<f64>,
+    pub voltage_mv: Option<f64>,
+    pub current_ma: Option<f64>,
+    pub power_mw: Option<f64>,
+    pub total_wh: Option<f64>,
+}
+
+impl EmeterRealtime {
+    pub fn voltage(&self) -> Option<f64> {
+        self.voltage.or(self.voltage_mv.map(|mv| mv / 1000.))
+    }
+
+    pub fn current(&self) -> Option<f64> {
+        self.current.or(self.current_ma.map(|ma| ma / 1000.))
+    }
+
+    pub fn power(&self) -> Option<f64> {
+        self.power.or(self.power_mw.map(|mw| mw / 1000.))
+    }
+
+    pub fn total_kwh(&self) -> Option<f64> {
+        self.total.or(self.total_wh.map(|wh| wh / 1000.))
+    }
+}
+
+/// When a schedule rule fires.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(from = "i8", into = "i8")]
+pub enum RuleTime {
+    /// At `smin` minutes after midnight.
+    #[default]
+    Minutes,
+    Sunrise,
+    Sunset,
+}
+
+impl From<i8> for RuleTime {
+    fn from(value: i8) -> Self {
+        match value {
+            1 => Self::Sunrise,
+            2 => Self::Sunset,
+            _ => Self::Minutes,
+        }
+    }
+}
+
+impl From<RuleTime> for i8 {
+    fn from(value: RuleTime) -> Self {
+        match value {
+            RuleTime::Minutes => 0,
+            RuleTime::Sunrise => 1,
+            RuleTime::Sunset => 2,
+        }
+    }
+}
+
+fn no_action() -> i8 {
+    -1
+}
+
+/// A rule in the `schedule` module, which the device runs on its own clock.
+/// Only the start action is used; the end fields are kept so edits don't
+/// clear rules made in the Kasa app.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct ScheduleRule {
+    /// Assigned by the device, `None` for a rule that hasn't been added yet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub name: String,
+    pub enable: u8,
+    /// Days the rule runs on, Sunday first.
+    pub wday: [u8; 7],
+    pub repeat: u8,
+    pub stime_opt: RuleTime,
+    pub smin: u16,
+    /// 1 turns the device on, 0 turns it off.
+    pub sact: i8,
+    #[serde(default = "no_action")]
+    pub etime_opt: i8,
+    #[serde(default)]
+    pub emin: u16,
+    #[serde(default = "no_action")]
+    pub eact: i8,
+}
+
+/// Turns the device on at 07:00 every day.
+impl Default for ScheduleRule {
+    fn default() -> Self {
+        Self {
+            id: None,
+            name: String::new(),
+            enable: 1,
+            wday: [1; 7],
+            repeat: 1,
+            stime_opt: RuleTime::Minutes,
+            smin: 7 * 60,
+            sact: 1,
+            etime_opt: no_action(),
+            emin: 0,
+            eact: no_action(),
+        }
+    }
+}
+
+impl ScheduleRule {
+    /// "07:30", "Sunrise" or "Sunset".
+    pub fn time_label(&self) -> String {
+        match self.stime_opt {
+            RuleTime::Minutes => format!("{:02}:{:02}", self.smin / 60, self.smin % 60),
+            RuleTime::Sunrise => "Sunrise".to_string(),
+            RuleTime::Sunset => "Sunset".to_string(),
+        }
+    }
+}
+
+/// A rule in the `count_down` module. Devices keep at most one, which flips
+/// the relay to `act` once `delay` seconds have passed.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct CountdownRule {
+    /// Assigned by the device, `None` for a rule that hasn't been added yet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub name: String,
+    pub enable: u8,
+    pub delay: u32,
+    pub act: u8,
+    /// Seconds left while the countdown runs, only reported by the device.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remain: Option<u32>,
+}
+
+/// An outlet of a power strip, listed in the device modal of each of its
+/// siblings. `power` is the latest emeter reading, for strips that meter each
+/// outlet.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PowerStripOutlet {
+    pub device: Device,
+    pub power: Option<f64>,
+}
+
+/// The on-device rules of a plug, dimmer or power strip outlet.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct KasaRules {
+    pub schedule: Vec<ScheduleRule>,
+    pub count_down: Vec<CountdownRule>,
+}
+
+/// Bulbs answer the modules every Kasa device has under different names than
+/// plugs, dimmers and power strips.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum KasaFamily {
+    Plug,
+    Light,
+}
+
+impl KasaFamily {
+    pub fn of(device_type: DeviceType) -> Option<Self> {
+        match device_type {
+            DeviceType::KasaPlug | DeviceType::KasaDimmer | DeviceType::KasaPowerStrip => {
+                Some(Self::Plug)
+            }
+            DeviceType::KasaLight => Some(Self::Light),
+            _ => None,
+        }
+    }
+
+    pub fn system_module(self) -> &'static str {
+        match self {
+            Self::Plug => "system",
+            Self::Light => "smartlife.iot.common.system",
+        }
+    }
+
+    pub fn cloud_module(self) -> &'static str {
+        match self {
+            Self::Plug => "cnCloud",
+            Self::Light => "smartlife.iot.common.cloud",
+        }
+    }
+
+    pub fn netif_module(self) -> &'static str {
+        match self {
+            Self::Plug => "netif",
+            Self::Light => "smartlife.iot.common.softaponboarding",
+        }
+    }
+}
+
+/// The TP-Link cloud account a device is bound to, from `get_info` of its
+/// cloud module.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct KasaCloud {
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub server: String,
+    pub binded: u8,
+    #[serde(default)]
+    pub cld_connection: u8,
+}
+
+/// What the management panel in the device modal shows. `led_off` is `None`
+/// for bulbs, which have no status LED, and `cloud` for devices without a
+/// cloud module.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct KasaDeviceInfo {
+    pub alias: String,
+    pub model: String,
+    pub led_off: Option<bool>,
+    pub cloud: Option<KasaCloud>,
+}
+
+/// A network found by a device's Wi-Fi scan. `key_type` is 0 for open
+/// networks, 1 for WEP, 2 for WPA and 3 for WPA2.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct WifiNetwork {
+    pub ssid: String,
+    pub key_type: u8,
+}
@@ -1,12 +1,12 @@
 use {
-    super::types::TuyaDeviceRes,
+    super::types::{TuyaDeviceRes, TuyaFactoryInfoRes},
     chrono::Utc,
     hmac::{Hmac, Mac},
     http::{HeaderMap, HeaderName, HeaderValue, Method},
     reqwest::Client,
     serde::{Deserialize, Serialize},
     sha2::{Digest, Sha256},
-    std::{env, error::Error},
+    std::error::Error,
     url::Url,
 };
 
@@ -31,33 +31,66 @@ pub struct TuyaAuthValues {
     pub uid: String,
 }
 
+/// Credentials of the Tuya cloud project, from the secret store.
+#[derive(Clone)]
+pub struct TuyaCredentials {
+    pub client_id: String,
+    pub api_key: String,
+}
+
 #[derive(Debug)]
 pub struct TuyaRestClient {
     pub state: State,
 }
 
-pub async fn get_refresh_token() -> Result<TuyaAuthRes, Box<dyn Error>> {
-    let res = request("/v1.0/token?grant_type=1", "").await;
+pub async fn get_refresh_token(
+    credentials: &TuyaCredentials,
+) -> Result<TuyaAuthRes, Box<dyn Error>> {
+    let res = request(credentials, "/v1.0/token?grant_type=1", "").await;
     let tuya_auth: TuyaAuthRes = serde_json::from_str(&res)?;
-    println!("{tuya_auth:?}");
     Ok(tuya_auth)
 }
 
-pub async fn get_devices(user_id: &str, token: &str) -> Result<TuyaDeviceRes, Box<dyn Error>> {
-    let res = request(&format!("/v1.0/users/{user_id}/devices"), token).await;
-    println!("get_devices res: {res:?}");
+pub async fn get_devices(
+    credentials: &TuyaCredentials,
+    user_id: &str,
+    token: &str,
+) -> Result<TuyaDeviceRes, Box<dyn Error>> {
+    let res = request(
+        credentials,
+        &format!("/v1.0/users/{user_id}/devices"),
+        token,
+    )
+    .await;
     let tuya_devices: TuyaDeviceRes = serde_json::from_str(&res)?;
     Ok(tuya_devices)
 }
 
-pub async fn get_user_id(device_id: &str, token: &str) -> String {
-    request(&format!("/v1.0/devices/{device_id}"), token).await
+pub async fn get_device_factory_infos(
+    credentials: &TuyaCredentials,
+    device_ids: &[&str],
+    token: &str,
+) -> Result<TuyaFactoryInfoRes, Box<dyn Error>> {
+    let res = request(
+        credentials,
+        &format!(
+            "/v1.0/devices/factory-infos?device_ids={}",
+            device_ids.join(",")
+        ),
+        token,
+    )
+    .await;
+    let factory_infos: TuyaFactoryInfoRes = serde_json::from_str(&res)?;
+    Ok(factory_infos)
+}
+
+pub async fn get_user_id(credentials: &TuyaCredentials, device_id: &str, token: &str) -> String {
+    request(credentials, &format!("/v1.0/devices/{device_id}"), token).await
 }
 
-pub async fn request(path: &str, token: &str) -> String {
-    let tuya_client_id =
-        env::var("TUYA_CLIENT_ID").expect("TUYA_CLIENT_ID not found in environment");
-    let tuya_api_key = env::var("TUYA_API_KEY").expect("TUYA_API_KEY not found in environment");
+pub async fn request(credentials: &TuyaCredentials, path: &str, token: &str) -> String {
+    let tuya_client_id = &credentials.client_id;
+    let tuya_api_key = &credentials.api_key;
 
     let api_url = TUYA_API_URL.parse::<Url>().unwrap().join(path).unwrap();
 
@@ -87,7 +120,7 @@ pub async fn request(path: &str, token: &str) -> String {
         payload.push_str(token);
         [("access_token", HeaderValue::from_str(token).unwrap())]
     } else {
-        [("secret", HeaderValue::from_str(&tuya_api_key).unwrap())]
+        [("secret", HeaderValue::from_str(tuya_api_key).unwrap())]
     };
     let now = Utc::now().timestamp_millis().to_string();
     payload.push_str(&now);
@@ -114,7 +147,7 @@ pub async fn request(path: &str, token: &str) -> String {
             .chain([("signature-headers", signature_header_value)])
             .chain(secret_or_access_token_header)
             .chain([
-                ("client_id", HeaderValue::from_str(&tuya_client_id).unwrap()),
+                ("client_id", HeaderValue::from_str(tuya_client_id).unwrap()),
                 ("sign", HeaderValue::from_str(&signature).unwrap()),
                 ("t", HeaderValue::from_str(&now).unwrap()),
                 ("sign_method", HeaderValue::from_static("HMAC-SHA256")),
@@ -0,0 +1,11 @@
+use crate::integrations::iron_nest::{driver::DeviceDriver, types::Capability};
+
+/// The Tuya client only supports cloud discovery so far, so no commands are
+/// supported until local or cloud control is implemented.
+pub struct TuyaLightDriver;
+
+impl DeviceDriver for TuyaLightDriver {
+    fn capabilities(&self) -> &'static [Capability] {
+        &[]
+    }
+}
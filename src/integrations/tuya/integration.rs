@@ -0,0 +1,143 @@
+use {
+    super::{
+        TuyaCredentials, discover_tuya_devices, get_device_factory_infos, get_devices,
+        get_refresh_token,
+    },
+    crate::integrations::iron_nest::{
+        events::EventBus,
+        insert_devices_into_db, normalize_mac, rekey_tuya_device,
+        secrets::SecretStore,
+        supervisor::{HealthFuture, Integration, PollFuture},
+        types::{AuthState, Device, DeviceType},
+    },
+    anyhow::anyhow,
+    chrono::Utc,
+    futures::FutureExt,
+    log::{error, info},
+    sqlx::PgPool,
+    std::{collections::HashMap, net::Ipv4Addr, time::Duration},
+};
+
+/// Refreshes the cloud token and the device list every poll, then looks for
+/// the devices on the local network.
+pub struct TuyaIntegration {
+    pool: PgPool,
+    event_bus: EventBus,
+    secrets: SecretStore,
+}
+
+impl TuyaIntegration {
+    pub fn new(pool: PgPool, event_bus: EventBus, secrets: SecretStore) -> Self {
+        Self {
+            pool,
+            event_bus,
+            secrets,
+        }
+    }
+}
+
+impl Integration for TuyaIntegration {
+    fn name(&self) -> &'static str {
+        "tuya"
+    }
+
+    fn poll(&self) -> PollFuture<'_> {
+        async move {
+            let credentials = TuyaCredentials {
+                client_id: self.secrets.require(self.name(), "client_id").await?,
+                api_key: self.secrets.require(self.name(), "api_key").await?,
+            };
+            let user_id = self.secrets.require(self.name(), "user_id").await?;
+
+            // The boxed errors aren't Send so they can't be held across an await
+            let res = get_refresh_token(&credentials)
+                .await
+                .map_err(|e| anyhow!("Failed to get a token: {e}"))?;
+            let auth_token = res.result.access_token;
+            let state = AuthState {
+                refresh_token: res.result.refresh_token,
+                hardware_id: res.result.uid,
+                auth_token: auth_token.clone(),
+            };
+            self.secrets.set_auth(self.name(), &state).await?;
+
+            let res = get_devices(&credentials, &user_id, &auth_token)
+                .await
+                .map_err(|e| anyhow!("Failed to get devices: {e}"))?;
+            let device_ids = res
+                .result
+                .iter()
+                .map(|device| device.id.as_str())
+                .collect::<Vec<_>>();
+            let macs = match get_device_factory_infos(&credentials, &device_ids, &auth_token)
+                .await
+                .map_err(|e| e.to_string())
+            {
+                Ok(factory_infos) => factory_infos
+                    .result
+                    .into_iter()
+                    .filter_map(|info| Some((info.id, normalize_mac(&info.mac?)?)))
+                    .collect::<HashMap<_, _>>(),
+                Err(e) => {
+                    self.event_bus.integration_error(
+                        self.name(),
+                        format!("Failed to get factory infos: {e}"),
+                    );
+                    HashMap::new()
+                }
+            };
+            for (index, device) in res.result.iter().enumerate() {
+                rekey_tuya_device(
+                    &self.pool,
+                    &device.id,
+                    macs.get(&device.id).map(String::as_str),
+                    index,
+                    &device.name,
+                )
+                .await?;
+            }
+            let devices = res
+                .result
+                .iter()
+                .map(|device| {
+                    let mac_address = macs.get(&device.id).cloned();
+                    // All Tuya devices share the placeholder IP, the Tuya
+                    // device id tells them apart whether or not a MAC is known
+                    let child_id = Some(device.id.clone());
+                    Device {
+                        id: 0,
+                        name: device.name.clone(),
+                        device_type: DeviceType::TuyaLight,
+                        ip: Ipv4Addr::UNSPECIFIED.to_string(),
+                        power_state: 0,
+                        battery_percentage: 0,
+                        last_seen: Utc::now(),
+                        mac_address,
+                        child_id,
+                        room_id: None,
+                        tags: Vec::new(),
+                        display_name: None,
+                        icon: None,
+                        online: true,
+                    }
+                })
+                .collect::<Vec<_>>();
+            insert_devices_into_db(&self.pool, &self.event_bus, &devices).await?;
+
+            // task for local network discovery
+            tokio::task::spawn(async {
+                match tokio::time::timeout(Duration::from_secs(10), discover_tuya_devices()).await {
+                    Ok(Ok(_)) => info!("Local Tuya discovery completed."),
+                    Ok(Err(e)) => error!("Error during local Tuya discovery: {e}"),
+                    Err(_) => error!("Local Tuya discovery timed out."),
+                }
+            });
+            Ok(devices.len())
+        }
+        .boxed()
+    }
+
+    fn health(&self) -> HealthFuture<'_> {
+        async move { self.secrets.auth_health(self.name()).await }.boxed()
+    }
+}
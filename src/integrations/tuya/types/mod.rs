@@ -14,3 +14,14 @@ pub struct TuyaDeviceResResult {
     pub name: String,
     pub product_name: String,
 }
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct TuyaFactoryInfoRes {
+    pub result: Vec<TuyaFactoryInfo>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct TuyaFactoryInfo {
+    pub id: String,
+    pub mac: Option<String>,
+}
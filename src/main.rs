@@ -14,13 +14,19 @@ async fn main() {
         dotenv::dotenv,
         iron_nest::{
             components::layout::App,
-            handlers::roku_keypress_handler,
+            handlers::{roku_icon_handler, roku_keypress_handler},
             integrations::{
                 iron_nest::{
+                    audit_log::run_audit_log,
                     client::AppState,
                     cron::CronClient,
-                    mish::{create_mish_state_modification_bus, register_native_queries},
+                    driver::DriverRegistry,
+                    events::EventBus,
+                    mish::register_native_queries,
                     run_devices_tasks,
+                    secrets::SecretStore,
+                    shutdown::{self, Shutdown},
+                    supervisor::Supervisor,
                 },
                 ring::RingRestClient,
             },
@@ -28,13 +34,17 @@ async fn main() {
         },
         leptos::prelude::*,
         leptos_axum::{LeptosRoutes, generate_route_list},
-        log::{LevelFilter, error},
+        log::{LevelFilter, error, info, warn},
         simple_logger::SimpleLogger,
         sqlx::postgres::PgPoolOptions,
-        std::{collections::HashMap, sync::Arc},
-        tokio::sync::RwLock,
+        std::{sync::Arc, time::Duration},
+        tokio::time::{Instant, timeout_at},
     };
 
+    // docker-compose's stop_grace_period is 15s, leave time to exit before
+    // SIGKILL
+    const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
     dotenv().ok();
 
     let postgres_uri = std::env::var("POSTGRES_URI")
@@ -55,19 +65,38 @@ async fn main() {
     let conf = get_configuration(None).unwrap();
     let leptos_options = conf.leptos_options;
     let addr = leptos_options.site_addr;
-    let ring_rest_client = Arc::new(RingRestClient::new(shared_pool.clone()).await);
-    let control_senders = Arc::new(RwLock::new(HashMap::new()));
-    let (mish_state_modification_bus_sender, mish_state_modification_bus_receiver) =
-        create_mish_state_modification_bus();
+    let secrets = SecretStore::load(shared_pool.clone()).expect("couldn't load the master key");
+    if let Err(e) = secrets.import_plaintext_auth().await {
+        error!("Failed to encrypt stored tokens: {e}");
+    }
+    if let Err(e) = secrets.import_env_credentials().await {
+        error!("Failed to import credentials from the environment: {e}");
+    }
+    let ring_rest_client = Arc::new(RingRestClient::new(secrets.clone()).await);
+    let event_bus = EventBus::default();
+    let shutdown = Shutdown::default();
+    let supervisor = Supervisor::new(event_bus.clone());
+    let driver_registry = DriverRegistry::default();
     let app_state = AppState {
         leptos_options: leptos_options.clone(),
         ring_rest_client: ring_rest_client.clone(),
         pool: shared_pool.clone(),
-        cron_client: CronClient::new().await,
-        control_senders: control_senders.clone(),
-        mish_state_modification_bus_sender: mish_state_modification_bus_sender.clone(),
+        cron_client: CronClient::new(driver_registry.clone(), event_bus.clone(), shutdown.clone())
+            .await,
+        secrets: secrets.clone(),
+        supervisor: supervisor.clone(),
+        event_bus: event_bus.clone(),
+        driver_registry: driver_registry.clone(),
     };
 
+    // Subscribed before anything starts publishing
+    let audit_log = tokio::spawn(run_audit_log(
+        shared_pool.clone(),
+        event_bus.subscribe(),
+        shutdown.clone(),
+    ));
+
+    let cron_client = app_state.cron_client.clone();
     app_state
         .cron_client
         .schedule_tasks(&shared_pool)
@@ -79,6 +108,7 @@ async fn main() {
             "/roku/{device_id}/keypress/{key}",
             get(roku_keypress_handler),
         )
+        .route("/roku/icons/{cid}", get(roku_icon_handler))
         .route("/mish/blob.dag-json", post(upload_dag_json_file))
         .route("/mish/blob.raw", post(upload_raw_file))
         .route("/mish/state", post(update_mish_state_handler))
@@ -89,15 +119,14 @@ async fn main() {
         .leptos_routes_with_context(
             &leptos_options,
             routes,
-            {
-                let mish_state_modification_bus_sender = mish_state_modification_bus_sender.clone();
-                move || {
-                    provide_context(app_state.ring_rest_client.clone());
-                    provide_context(app_state.pool.clone());
-                    provide_context(app_state.cron_client.clone());
-                    provide_context(app_state.control_senders.clone());
-                    provide_context(mish_state_modification_bus_sender.clone());
-                }
+            move || {
+                provide_context(app_state.ring_rest_client.clone());
+                provide_context(app_state.pool.clone());
+                provide_context(app_state.cron_client.clone());
+                provide_context(app_state.secrets.clone());
+                provide_context(app_state.supervisor.clone());
+                provide_context(app_state.driver_registry.clone());
+                provide_context(app_state.event_bus.clone());
             },
             {
                 let leptos_options = leptos_options.clone();
@@ -108,35 +137,73 @@ async fn main() {
         .fallback(leptos_axum::file_and_error_handler(shell::shell))
         .with_state(leptos_options);
 
-    run_devices_tasks(ring_rest_client, &shared_pool, control_senders)
-        .await
-        .unwrap();
+    run_devices_tasks(
+        ring_rest_client,
+        &shared_pool,
+        &supervisor,
+        event_bus.clone(),
+        secrets,
+    )
+    .await
+    .unwrap();
 
-    tokio::spawn(async move {
-        register_native_queries(
-            &shared_pool,
-            mish_state_modification_bus_receiver,
-            mish_state_modification_bus_sender,
-        )
-        .await;
+    let mish = tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            register_native_queries(&shared_pool, &driver_registry, event_bus, shutdown).await;
+        }
     });
 
-    // tplink_kasa_get_energy_usage("10.0.0.223", "1")
-    //     .await
-    //     .unwrap();
-    // tplink_kasa_get_energy_usage("10.0.0.223", "1")
-    //     .await
-    //     .unwrap();
-
-    let http_server = {
+    let mut http_server = {
         let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
         log::info!("listening on http://{}", &addr);
-        axum::serve(listener, app.into_make_service())
+        tokio::spawn(
+            axum::serve(listener, app.into_make_service())
+                .with_graceful_shutdown(shutdown.requested())
+                .into_future(),
+        )
+    };
+
+    // A finished JoinHandle panics when polled again, so remember whether the
+    // server is already done
+    let server_done = tokio::select! {
+        e = &mut http_server => {
+            error!("HTTP server exiting with error {e:?}");
+            true
+        }
+        _ = shutdown::signal() => false,
     };
 
-    tokio::select! {
-        e = http_server => error!("HTTP server exiting with error {e:?}")
+    // Stop accepting requests and new work, then give what's running until
+    // the deadline to finish
+    info!("Shutting down");
+    let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+    shutdown.begin();
+    if let Err(e) = cron_client.shutdown().await {
+        error!("Failed to shut down the action scheduler: {e}");
+    }
+    if !server_done && timeout_at(deadline, &mut http_server).await.is_err() {
+        warn!("Abandoned open HTTP connections");
+        http_server.abort();
+    }
+    for name in supervisor.shutdown(deadline).await {
+        warn!("Abandoned {name}, it was still polling");
+    }
+    if timeout_at(deadline, mish).await.is_err() {
+        warn!("Abandoned the mish scheduler");
+    }
+    if timeout_at(deadline, audit_log).await.is_err() {
+        warn!("Abandoned the audit log");
+    }
+    let abandoned = shutdown.drain(deadline).await;
+    for label in &abandoned {
+        warn!("Abandoned {label}");
+    }
+    if !abandoned.is_empty() {
+        // The runtime would wait for blocking scripts on drop
+        std::process::exit(1);
     }
+    info!("Shut down cleanly");
 }
 
 #[cfg(not(feature = "ssr"))]
@@ -1,7 +1,9 @@
 use {
-    crate::{
-        integrations::iron_nest::{AppState, mish::MishStateModification},
-        ipld_codecs,
+    crate::integrations::iron_nest::{
+        AppState,
+        events::{Event, EventBus},
+        insert_ipld_blob,
+        mish::MishStateModification,
     },
     axum::{Json, extract::State},
     bytes::Bytes,
@@ -11,7 +13,6 @@ use {
     multihash_codetable::{Code, MultihashDigest},
     serde::Deserialize,
     serde_ipld_dagjson::codec::DagJsonCodec,
-    tokio::sync::mpsc::UnboundedSender,
 };
 
 pub async fn upload_dag_json_file(
@@ -40,18 +41,9 @@ pub async fn upload_raw_file(
     State(state): State<AppState>,
     content: Bytes,
 ) -> Result<Json<String>, ()> {
-    let cid = Cid::new_v1(ipld_codecs::RAW, Code::Sha2_256.digest(&content));
-    let query = "
-        INSERT INTO ipld_blobs (cid, content)
-        VALUES ($1, $2)
-        ON CONFLICT (cid) DO NOTHING
-    ";
-    sqlx::query(query)
-        .bind(cid.to_bytes())
-        .bind(content.to_vec())
-        .execute(&state.pool)
+    let cid = insert_ipld_blob(&state.pool, &content)
         .await
-        .unwrap();
+        .map_err(|e| log::error!("Failed to store raw blob: {e}"))?;
     Ok(Json(cid.to_string()))
 }
 
@@ -71,7 +63,7 @@ pub async fn update_mish_state_handler(
     State(state): State<AppState>,
     Json(body): Json<UpdateMishStateBody>,
 ) -> Result<(), String> {
-    update_mish_state(&state.pool, &state.mish_state_modification_bus_sender, body)
+    update_mish_state(&state.pool, &state.event_bus, body)
         .await
         .map_err(|e| e.to_string())?;
     Ok(())
@@ -79,7 +71,7 @@ pub async fn update_mish_state_handler(
 
 pub async fn update_mish_state(
     pool: &sqlx::PgPool,
-    mish_state_modification_bus_sender: &UnboundedSender<MishStateModification>,
+    event_bus: &EventBus,
     body: UpdateMishStateBody,
 ) -> Result<(), anyhow::Error> {
     let query = "
@@ -106,10 +98,12 @@ pub async fn update_mish_state(
             .bind(&body.mish_state_name)
             .execute(pool)
             .await?;
-        mish_state_modification_bus_sender.send(MishStateModification::CreateOrUpdate {
-            name: body.mish_state_name,
-            state: mish_state.state,
-        })?;
+        event_bus.publish(Event::MishStateChanged(
+            MishStateModification::CreateOrUpdate {
+                name: body.mish_state_name,
+                state: mish_state.state,
+            },
+        ));
     } else {
         // If the state doesn't exist, create a new one
         let mut new_state = serde_json::json!({});
@@ -123,10 +117,12 @@ pub async fn update_mish_state(
             .bind(&new_state)
             .execute(pool)
             .await?;
-        mish_state_modification_bus_sender.send(MishStateModification::CreateOrUpdate {
-            name: body.mish_state_name,
-            state: new_state,
-        })?;
+        event_bus.publish(Event::MishStateChanged(
+            MishStateModification::CreateOrUpdate {
+                name: body.mish_state_name,
+                state: new_state,
+            },
+        ));
     }
     Ok(())
 }
@@ -117,14 +117,21 @@ pub async fn delete_action_query(pool: &sqlx::PgPool, id: Uuid) -> Result<(), sq
 
 #[server(RunAction)]
 pub async fn run_action(id: Uuid) -> Result<(), ServerFnError> {
+    use crate::integrations::iron_nest::{driver::DriverRegistry, events::EventBus};
+
     let pool = use_context::<sqlx::PgPool>().unwrap();
+    let driver_registry = use_context::<DriverRegistry>().unwrap();
+    let event_bus = use_context::<EventBus>().unwrap();
     let actions = get_actions_query(&pool).await?;
     let action = actions.iter().find(|a| a.id == id).unwrap();
     crate::integrations::iron_nest::execute_function(
+        &pool,
+        &driver_registry,
+        &event_bus,
         action.fields.function_name.clone(),
         action.fields.function_args.clone(),
     )
-    .await;
+    .await?;
 
     Ok(())
 }
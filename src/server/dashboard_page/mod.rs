@@ -10,9 +10,9 @@ pub async fn get_devices() -> Result<Vec<Device>, ServerFnError> {
     let pool = use_context::<PgPool>().unwrap();
 
     let query = "
-        SELECT id, name, device_type, ip, power_state, battery_percentage, last_seen, mac_address, child_id 
+        SELECT id, name, device_type, ip, power_state, battery_percentage, last_seen, mac_address, child_id, room_id, tags, display_name, icon, online
         FROM device
-        ORDER BY name
+        ORDER BY COALESCE(display_name, name)
     ";
     sqlx::query_as::<Postgres, Device>(query)
         .fetch_all(&pool)
@@ -22,9 +22,23 @@ pub async fn get_devices() -> Result<Vec<Device>, ServerFnError> {
 
 #[server(RefreshDevices)]
 pub async fn refresh_devices() -> Result<(), ServerFnError> {
-    use {crate::integrations::iron_nest::refresh_tplink_devices, sqlx::PgPool};
+    use {
+        crate::integrations::iron_nest::{
+            events::EventBus, get_integrations, refresh_tplink_devices,
+        },
+        sqlx::PgPool,
+    };
 
     let pool = use_context::<PgPool>().unwrap();
-    refresh_tplink_devices(pool).await;
-    Ok(())
+    let event_bus = use_context::<EventBus>().unwrap();
+    let scan_hosts = get_integrations(&pool)
+        .await?
+        .into_iter()
+        .find(|integration| integration.name == "tplink")
+        .map(|integration| integration.settings.scan_hosts())
+        .unwrap_or_default();
+    refresh_tplink_devices(pool, &event_bus, &scan_hosts)
+        .await
+        .map(|_| ())
+        .map_err(ServerFnError::new)
 }
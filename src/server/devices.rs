@@ -0,0 +1,106 @@
+use {
+    crate::integrations::iron_nest::types::{DeviceCommand, DeviceStateHistory},
+    chrono::{DateTime, Utc},
+    leptos::prelude::*,
+};
+
+#[cfg(feature = "ssr")]
+pub async fn dispatch_device_command(
+    ip: &str,
+    child_id: Option<&str>,
+    command: DeviceCommand,
+) -> Result<(), ServerFnError> {
+    use {
+        crate::integrations::iron_nest::{
+            driver::DriverRegistry, events::EventBus, execute_device_command, get_device_by_ip,
+        },
+        sqlx::PgPool,
+    };
+
+    let pool = use_context::<PgPool>().unwrap();
+    let driver_registry = use_context::<DriverRegistry>().unwrap();
+    let event_bus = use_context::<EventBus>().unwrap();
+    let device = get_device_by_ip(&pool, ip, child_id).await?;
+    execute_device_command(&pool, &driver_registry, &event_bus, &device, command).await?;
+    Ok(())
+}
+
+#[server(name = HandleDeviceCommand, encoding = "cbor")]
+pub async fn handle_device_command(
+    device_id: i64,
+    command: DeviceCommand,
+) -> Result<(), ServerFnError> {
+    use {
+        crate::integrations::iron_nest::{
+            driver::DriverRegistry, events::EventBus, execute_device_command, get_device_by_id,
+        },
+        sqlx::PgPool,
+    };
+
+    let pool = use_context::<PgPool>().unwrap();
+    let driver_registry = use_context::<DriverRegistry>().unwrap();
+    let event_bus = use_context::<EventBus>().unwrap();
+    let device = get_device_by_id(&pool, device_id).await?;
+    execute_device_command(&pool, &driver_registry, &event_bus, &device, command).await?;
+    Ok(())
+}
+
+/// State changes for a device between `start` and `end`, defaulting to the
+/// last 24 hours. The first entry is the state the device was in at `start`.
+#[server(GetDeviceStateHistory)]
+pub async fn get_device_state_history(
+    device_id: i64,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+) -> Result<Vec<DeviceStateHistory>, ServerFnError> {
+    use {
+        crate::integrations::iron_nest::{get_device_state_at, get_device_state_history},
+        sqlx::PgPool,
+    };
+
+    let pool = use_context::<PgPool>().unwrap();
+    let end = end.unwrap_or_else(Utc::now);
+    let start = start.unwrap_or(end - chrono::Duration::hours(24));
+
+    let mut history = Vec::new();
+    if let Some(mut initial) = get_device_state_at(&pool, device_id, start).await? {
+        initial.recorded_at = start;
+        history.push(initial);
+    }
+    history.extend(get_device_state_history(&pool, device_id, start, end).await?);
+    Ok(history)
+}
+
+/// Sets the display name and icon shown for a device. With `push_to_device`
+/// the name is also written to the device itself, which is only supported for
+/// Kasa devices.
+#[server(name = RenameDevice, encoding = "cbor")]
+pub async fn rename_device(
+    device_id: i64,
+    display_name: Option<String>,
+    icon: Option<String>,
+    push_to_device: bool,
+) -> Result<(), ServerFnError> {
+    use {
+        crate::{
+            integrations::iron_nest::{get_device_by_id, set_device_display_name},
+            server::tplink::set_kasa_alias,
+        },
+        sqlx::PgPool,
+    };
+
+    let pool = use_context::<PgPool>().unwrap();
+    let device = get_device_by_id(&pool, device_id).await?;
+
+    if push_to_device {
+        let alias = display_name
+            .as_deref()
+            .map(str::trim)
+            .filter(|alias| !alias.is_empty())
+            .ok_or_else(|| ServerFnError::new("A name is required to rename the device"))?;
+        set_kasa_alias(&pool, &device, alias).await?;
+    }
+
+    set_device_display_name(&pool, device_id, display_name, icon).await?;
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use {
+    crate::integrations::iron_nest::types::{EnergyPeriod, EnergyReading, EnergyUsage},
+    chrono::{DateTime, Utc},
+    leptos::prelude::*,
+};
+
+#[server(GetLatestEnergyReadings)]
+pub async fn get_latest_energy_readings() -> Result<Vec<EnergyReading>, ServerFnError> {
+    use sqlx::PgPool;
+
+    let pool = use_context::<PgPool>().unwrap();
+    crate::integrations::iron_nest::get_latest_energy_readings(&pool)
+        .await
+        .map_err(Into::into)
+}
+
+/// Energy used per device and period since `start`, defaulting to the last 30
+/// days for daily usage and the last year for monthly usage.
+#[server(name = GetEnergyUsage, encoding = "cbor")]
+pub async fn get_energy_usage(
+    period: EnergyPeriod,
+    start: Option<DateTime<Utc>>,
+) -> Result<Vec<EnergyUsage>, ServerFnError> {
+    use sqlx::PgPool;
+
+    let pool = use_context::<PgPool>().unwrap();
+    let start = start.unwrap_or_else(|| {
+        Utc::now()
+            - match period {
+                EnergyPeriod::Day => chrono::Duration::days(30),
+                EnergyPeriod::Month => chrono::Duration::days(365),
+            }
+    });
+    crate::integrations::iron_nest::get_energy_usage(&pool, period, start)
+        .await
+        .map_err(Into::into)
+}
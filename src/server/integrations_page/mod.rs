@@ -1,4 +1,8 @@
-use {crate::integrations::iron_nest::types::Integration, leptos::prelude::*};
+use {
+    crate::integrations::iron_nest::types::{Integration, IntegrationSettings, IntegrationStatus},
+    leptos::prelude::*,
+    std::collections::HashMap,
+};
 
 #[server(GetIntegrations)]
 pub async fn get_integrations() -> Result<Vec<Integration>, ServerFnError> {
@@ -10,7 +14,7 @@ pub async fn get_integrations() -> Result<Vec<Integration>, ServerFnError> {
     let pool = use_context::<PgPool>().unwrap();
 
     let query = "
-        SELECT id, name, enabled, image
+        SELECT id, name, enabled, image, offline_after_secs, settings
         FROM integration
         ORDER BY id
     ";
@@ -23,15 +27,12 @@ pub async fn get_integrations() -> Result<Vec<Integration>, ServerFnError> {
 #[server(ToggleIntegration)]
 pub async fn toggle_integration(id: i64, enabled: bool, name: String) -> Result<(), ServerFnError> {
     use {
-        crate::integrations::iron_nest::types::ControlMessage,
+        crate::integrations::iron_nest::{supervisor::Supervisor, types::ControlMessage},
         sqlx::PgPool,
-        std::{collections::HashMap, sync::Arc},
-        tokio::sync::{RwLock, mpsc::Sender},
     };
 
     let pool = use_context::<PgPool>().unwrap();
-    let control_senders =
-        use_context::<Arc<RwLock<HashMap<String, Sender<ControlMessage>>>>>().unwrap();
+    let supervisor = use_context::<Supervisor>().unwrap();
 
     // Update the integration status in the database
     let query = "
@@ -45,23 +46,124 @@ pub async fn toggle_integration(id: i64, enabled: bool, name: String) -> Result<
         .execute(&pool)
         .await?;
 
-    let senders = control_senders.read().await;
-    println!(
-        "Available senders: {:?}",
-        senders.keys().collect::<Vec<&String>>()
-    );
-
-    if let Some(sender) = senders.get(&name.clone()) {
-        let message = if enabled {
-            ControlMessage::Start
-        } else {
-            ControlMessage::Stop
-        };
-        println!("Sending message: {message:?}");
-        sender.send(message).await.unwrap();
+    let message = if enabled {
+        ControlMessage::Start
     } else {
-        println!("No sender found for {}", &name.clone());
+        ControlMessage::Stop
+    };
+    if !supervisor.send(&name, message).await {
+        log::warn!(
+            "{name} has no background job to {}",
+            if enabled { "start" } else { "stop" }
+        );
+    }
+
+    Ok(())
+}
+
+/// Stores the integration's settings and hands them to its running job.
+#[server(name = UpdateIntegrationSettings, encoding = "cbor")]
+pub async fn update_integration_settings(
+    id: i64,
+    settings: IntegrationSettings,
+) -> Result<(), ServerFnError> {
+    use {
+        crate::integrations::iron_nest::supervisor::Supervisor,
+        sqlx::{PgPool, types::Json},
+    };
+
+    settings.validate().map_err(ServerFnError::new)?;
+    let pool = use_context::<PgPool>().unwrap();
+    let supervisor = use_context::<Supervisor>().unwrap();
+
+    let query = "
+        UPDATE integration
+        SET settings = $1
+        WHERE id = $2
+        RETURNING name
+    ";
+    let name = sqlx::query_scalar::<_, String>(query)
+        .bind(Json(&settings))
+        .bind(id)
+        .fetch_optional(&pool)
+        .await?
+        .ok_or_else(|| ServerFnError::new(format!("No integration with id {id}")))?;
+
+    if !supervisor.configure(&name, settings).await {
+        log::warn!("{name} has no background job to configure");
     }
 
     Ok(())
 }
+
+/// Status of the background job of every integration that has one, by name.
+#[server(GetIntegrationStatuses)]
+pub async fn get_integration_statuses() -> Result<HashMap<String, IntegrationStatus>, ServerFnError>
+{
+    use crate::integrations::iron_nest::supervisor::Supervisor;
+
+    let supervisor = use_context::<Supervisor>().unwrap();
+    Ok(supervisor.statuses().await)
+}
+
+/// Has the integration poll right away. Returns the number of devices found or
+/// updated.
+#[server(RefreshIntegration)]
+pub async fn refresh_integration(name: String) -> Result<usize, ServerFnError> {
+    use crate::integrations::iron_nest::supervisor::Supervisor;
+
+    let supervisor = use_context::<Supervisor>().unwrap();
+    supervisor.refresh(&name).await.map_err(ServerFnError::new)
+}
+
+/// Restarts the integration and has it poll right away. Returns the number of
+/// devices found or updated.
+#[server(RestartIntegration)]
+pub async fn restart_integration(name: String) -> Result<usize, ServerFnError> {
+    use crate::integrations::iron_nest::supervisor::Supervisor;
+
+    let supervisor = use_context::<Supervisor>().unwrap();
+    supervisor.restart(&name).await.map_err(ServerFnError::new)
+}
+
+/// Which of the integration's `secret_fields` have been entered. Values are
+/// never sent to the browser.
+#[server(GetIntegrationSecretNames)]
+pub async fn get_integration_secret_names(
+    integration: String,
+) -> Result<Vec<String>, ServerFnError> {
+    use crate::integrations::iron_nest::secrets::SecretStore;
+
+    let secrets = use_context::<SecretStore>().unwrap();
+    secrets
+        .names(&integration)
+        .await
+        .map_err(ServerFnError::new)
+}
+
+/// Stores one of the integration's `secret_fields`, or removes it when
+/// `value` is empty.
+#[server(SetIntegrationSecret)]
+pub async fn set_integration_secret(
+    integration: String,
+    name: String,
+    value: String,
+) -> Result<(), ServerFnError> {
+    use crate::integrations::iron_nest::{secrets::SecretStore, types::secret_fields};
+
+    if !secret_fields(&integration)
+        .iter()
+        .any(|field| field.name == name)
+    {
+        return Err(ServerFnError::new(format!(
+            "{integration} has no secret {name}"
+        )));
+    }
+    let secrets = use_context::<SecretStore>().unwrap();
+    if value.is_empty() {
+        secrets.delete(&integration, &name).await
+    } else {
+        secrets.set(&integration, &name, &value).await
+    }
+    .map_err(ServerFnError::new)
+}
@@ -1,6 +1,10 @@
 pub mod actions;
 pub mod dashboard_page;
+pub mod devices;
+pub mod energy;
 pub mod integrations_page;
 pub mod openai;
 pub mod roku;
+pub mod rooms;
+pub mod scenes;
 pub mod tplink;
@@ -2,7 +2,15 @@ use leptos::prelude::*;
 
 #[server(HandleAssistantCommand)]
 pub async fn handle_assistant_command(text: String) -> Result<String, ServerFnError> {
-    use {crate::integrations::openai::open_api_command, sqlx::PgPool};
+    use {
+        crate::integrations::{
+            iron_nest::{driver::DriverRegistry, events::EventBus},
+            openai::open_api_command,
+        },
+        sqlx::PgPool,
+    };
     let pool = use_context::<PgPool>().unwrap();
-    open_api_command(text, &pool).await
+    let driver_registry = use_context::<DriverRegistry>().unwrap();
+    let event_bus = use_context::<EventBus>().unwrap();
+    open_api_command(text, &pool, &driver_registry, &event_bus).await
 }
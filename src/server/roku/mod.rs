@@ -2,11 +2,33 @@ use leptos::prelude::*;
 
 #[server(HandleRokuTvToggle)]
 pub async fn handle_roku_tv_toggle(state: bool, ip: String) -> Result<(), ServerFnError> {
-    use crate::integrations::roku::roku_send_keypress;
-    if state {
-        roku_send_keypress(&ip, "PowerOff").await;
-    } else {
-        roku_send_keypress(&ip, "PowerOn").await;
-    }
-    Ok(())
+    use crate::{
+        integrations::iron_nest::types::DeviceCommand, server::devices::dispatch_device_command,
+    };
+    dispatch_device_command(&ip, None, DeviceCommand::SetPower { on: state }).await
+}
+
+/// What each Roku was last polled playing.
+#[server(name = GetNowPlaying, encoding = "cbor")]
+pub async fn get_now_playing()
+-> Result<Vec<crate::integrations::roku::types::RokuNowPlaying>, ServerFnError> {
+    use sqlx::PgPool;
+
+    let pool = use_context::<PgPool>().unwrap();
+    crate::integrations::iron_nest::get_roku_now_playing(&pool)
+        .await
+        .map_err(Into::into)
+}
+
+/// The channels of the Roku `device_id`, as last refreshed by the Roku job.
+#[server(name = GetRokuApps, encoding = "cbor")]
+pub async fn get_roku_apps(
+    device_id: i64,
+) -> Result<Vec<crate::integrations::roku::types::RokuApp>, ServerFnError> {
+    use sqlx::PgPool;
+
+    let pool = use_context::<PgPool>().unwrap();
+    crate::integrations::iron_nest::get_roku_apps(&pool, device_id)
+        .await
+        .map_err(Into::into)
 }
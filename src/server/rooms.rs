@@ -0,0 +1,80 @@
+use {
+    crate::integrations::iron_nest::types::{DeviceCommand, DeviceGroup, GroupCommandResult, Room},
+    leptos::prelude::*,
+};
+
+#[server(GetRooms)]
+pub async fn get_rooms() -> Result<Vec<Room>, ServerFnError> {
+    use sqlx::PgPool;
+
+    let pool = use_context::<PgPool>().unwrap();
+    crate::integrations::iron_nest::get_rooms(&pool)
+        .await
+        .map_err(Into::into)
+}
+
+#[server(AddRoom)]
+pub async fn add_room(name: String, zone: String) -> Result<Room, ServerFnError> {
+    use {crate::integrations::iron_nest::insert_room, sqlx::PgPool};
+
+    let pool = use_context::<PgPool>().unwrap();
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(ServerFnError::new("Room name is required"));
+    }
+    let zone = Some(zone.trim()).filter(|zone| !zone.is_empty());
+    insert_room(&pool, name, zone).await.map_err(Into::into)
+}
+
+#[server(DeleteRoom)]
+pub async fn delete_room(id: i64) -> Result<(), ServerFnError> {
+    use sqlx::PgPool;
+
+    let pool = use_context::<PgPool>().unwrap();
+    crate::integrations::iron_nest::delete_room(&pool, id)
+        .await
+        .map_err(Into::into)
+}
+
+#[server(name = SetDeviceRoom, encoding = "cbor")]
+pub async fn set_device_room(device_id: i64, room_id: Option<i64>) -> Result<(), ServerFnError> {
+    use sqlx::PgPool;
+
+    let pool = use_context::<PgPool>().unwrap();
+    crate::integrations::iron_nest::set_device_room(&pool, device_id, room_id)
+        .await
+        .map_err(Into::into)
+}
+
+#[server(name = SetDeviceTags, encoding = "cbor")]
+pub async fn set_device_tags(
+    device_id: i64,
+    tags: Vec<String>,
+) -> Result<Vec<String>, ServerFnError> {
+    use sqlx::PgPool;
+
+    let pool = use_context::<PgPool>().unwrap();
+    crate::integrations::iron_nest::set_device_tags(&pool, device_id, tags)
+        .await
+        .map_err(Into::into)
+}
+
+#[server(name = HandleGroupCommand, encoding = "cbor")]
+pub async fn handle_group_command(
+    group: DeviceGroup,
+    command: DeviceCommand,
+) -> Result<GroupCommandResult, ServerFnError> {
+    use {
+        crate::integrations::iron_nest::{
+            driver::DriverRegistry, events::EventBus, execute_group_command,
+        },
+        sqlx::PgPool,
+    };
+
+    let pool = use_context::<PgPool>().unwrap();
+    let driver_registry = use_context::<DriverRegistry>().unwrap();
+    let event_bus = use_context::<EventBus>().unwrap();
+    execute_group_command(&pool, &driver_registry, &event_bus, &group, command)
+        .await
+        .map_err(Into::into)
+}
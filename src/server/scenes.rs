@@ -0,0 +1,54 @@
+use {
+    crate::integrations::iron_nest::types::{GroupCommandResult, Scene},
+    leptos::prelude::*,
+};
+
+#[server(GetScenes)]
+pub async fn get_scenes() -> Result<Vec<Scene>, ServerFnError> {
+    use sqlx::PgPool;
+
+    let pool = use_context::<PgPool>().unwrap();
+    crate::integrations::iron_nest::get_scenes(&pool)
+        .await
+        .map_err(Into::into)
+}
+
+/// Captures the current state of `device_ids` as the scene `name`.
+#[server(name = SaveScene, encoding = "cbor")]
+pub async fn save_scene(name: String, device_ids: Vec<i64>) -> Result<Scene, ServerFnError> {
+    use {crate::integrations::iron_nest::driver::DriverRegistry, sqlx::PgPool};
+
+    let pool = use_context::<PgPool>().unwrap();
+    let driver_registry = use_context::<DriverRegistry>().unwrap();
+    crate::integrations::iron_nest::save_scene(&pool, &driver_registry, &name, &device_ids)
+        .await
+        .map_err(Into::into)
+}
+
+#[server(DeleteScene)]
+pub async fn delete_scene(id: i64) -> Result<(), ServerFnError> {
+    use sqlx::PgPool;
+
+    let pool = use_context::<PgPool>().unwrap();
+    crate::integrations::iron_nest::delete_scene(&pool, id)
+        .await
+        .map_err(Into::into)
+}
+
+#[server(ActivateScene)]
+pub async fn activate_scene(id: i64) -> Result<GroupCommandResult, ServerFnError> {
+    use {
+        crate::integrations::iron_nest::{
+            driver::DriverRegistry, events::EventBus, get_scene_by_id,
+        },
+        sqlx::PgPool,
+    };
+
+    let pool = use_context::<PgPool>().unwrap();
+    let driver_registry = use_context::<DriverRegistry>().unwrap();
+    let event_bus = use_context::<EventBus>().unwrap();
+    let scene = get_scene_by_id(&pool, id).await?;
+    crate::integrations::iron_nest::activate_scene(&pool, &driver_registry, &event_bus, &scene)
+        .await
+        .map_err(Into::into)
+}
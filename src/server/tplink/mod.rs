@@ -1,29 +1,17 @@
-use leptos::prelude::*;
+use {
+    crate::integrations::tplink::types::{
+        CountdownRule, KasaDeviceInfo, KasaLight, KasaRules, PowerStripOutlet, ScheduleRule,
+        WifiNetwork,
+    },
+    leptos::prelude::*,
+};
 
 #[server(HandleSmartPlugToggle)]
 pub async fn handle_smart_plug_toggle(state: bool, ip: String) -> Result<(), ServerFnError> {
-    use {
-        crate::integrations::tplink::{tplink_turn_plug_off, tplink_turn_plug_on},
-        sqlx::PgPool,
+    use crate::{
+        integrations::iron_nest::types::DeviceCommand, server::devices::dispatch_device_command,
     };
-
-    let pool = use_context::<PgPool>().unwrap();
-    let query = "
-        UPDATE device
-        SET power_state = $1
-        WHERE ip = $2
-    ";
-    sqlx::query(query)
-        .bind(if state { 1 } else { 0 })
-        .bind(&ip)
-        .execute(&pool)
-        .await?;
-    if state {
-        tplink_turn_plug_on(&ip).await;
-    } else {
-        tplink_turn_plug_off(&ip).await;
-    }
-    Ok(())
+    dispatch_device_command(&ip, None, DeviceCommand::SetPower { on: state }).await
 }
 
 #[server(HandleSmartPowerStripToggle)]
@@ -32,53 +20,18 @@ pub async fn handle_smart_power_strip_toggle(
     ip: String,
     child_id: String,
 ) -> Result<(), ServerFnError> {
-    use {
-        crate::integrations::tplink::{
-            tplink_turn_smart_strip_socket_off, tplink_turn_smart_strip_socket_on,
-        },
-        sqlx::PgPool,
+    use crate::{
+        integrations::iron_nest::types::DeviceCommand, server::devices::dispatch_device_command,
     };
-
-    let pool = use_context::<PgPool>().unwrap();
-    let query = "
-        UPDATE device
-        SET power_state = $1
-        WHERE ip = $2
-    ";
-    sqlx::query(query)
-        .bind(if state { 1 } else { 0 })
-        .bind(&ip)
-        .execute(&pool)
-        .await?;
-    if state {
-        tplink_turn_smart_strip_socket_on(&ip, &child_id).await;
-    } else {
-        tplink_turn_smart_strip_socket_off(&ip, &child_id).await;
-    }
-    Ok(())
+    dispatch_device_command(&ip, Some(&child_id), DeviceCommand::SetPower { on: state }).await
 }
 
 #[server(HandleSmartLightToggle)]
 pub async fn handle_smart_light_toggle(state: bool, ip: String) -> Result<(), ServerFnError> {
-    use {crate::integrations::tplink::tplink_turn_light_on_off, sqlx::PgPool};
-
-    let state = if state { 1 } else { 0 };
-
-    let pool = use_context::<PgPool>().unwrap();
-    let query = "
-        UPDATE device
-        SET power_state = $1
-        WHERE ip = $2
-    ";
-    println!("state: {state}");
-    println!("ip: {ip}");
-    sqlx::query(query)
-        .bind(state as i32)
-        .bind(&ip)
-        .execute(&pool)
-        .await?;
-    tplink_turn_light_on_off(&ip, state).await;
-    Ok(())
+    use crate::{
+        integrations::iron_nest::types::DeviceCommand, server::devices::dispatch_device_command,
+    };
+    dispatch_device_command(&ip, None, DeviceCommand::SetPower { on: state }).await
 }
 
 #[server(HandleSmartLightBrightness)]
@@ -86,9 +39,10 @@ pub async fn handle_smart_light_brightness(
     brightness: u8,
     ip: String,
 ) -> Result<(), ServerFnError> {
-    use crate::integrations::tplink::tplink_set_light_brightness;
-    tplink_set_light_brightness(&ip, brightness).await;
-    Ok(())
+    use crate::{
+        integrations::iron_nest::types::DeviceCommand, server::devices::dispatch_device_command,
+    };
+    dispatch_device_command(&ip, None, DeviceCommand::SetBrightness { brightness }).await
 }
 
 #[server(HandleSmartDimmerBrightness)]
@@ -96,14 +50,453 @@ pub async fn handle_smart_dimmer_brightness(
     brightness: u8,
     ip: String,
 ) -> Result<(), ServerFnError> {
-    use crate::integrations::tplink::tplink_set_dimmer_brightness;
-    tplink_set_dimmer_brightness(&ip, &brightness).await;
-    Ok(())
+    use crate::{
+        integrations::iron_nest::types::DeviceCommand, server::devices::dispatch_device_command,
+    };
+    dispatch_device_command(&ip, None, DeviceCommand::SetBrightness { brightness }).await
 }
 
 #[server(HandleSmartLightSaturation)]
 pub async fn handle_smart_light_hsl(ip: String, color: String) -> Result<(), ServerFnError> {
-    use crate::integrations::tplink::tplink_set_light_hsl;
-    tplink_set_light_hsl(&ip, color).await;
+    use crate::{
+        integrations::iron_nest::types::DeviceCommand, server::devices::dispatch_device_command,
+    };
+    dispatch_device_command(&ip, None, DeviceCommand::SetColor { color }).await
+}
+
+/// The address of a device that keeps its own schedule and countdown rules,
+/// with the outlet's id for power strips.
+#[cfg(feature = "ssr")]
+async fn kasa_rule_target(device_id: i64) -> Result<(String, Option<String>), ServerFnError> {
+    use {
+        crate::integrations::iron_nest::{get_device_by_id, types::DeviceType},
+        sqlx::PgPool,
+    };
+
+    let pool = use_context::<PgPool>().unwrap();
+    let device = get_device_by_id(&pool, device_id).await?;
+    match (device.device_type, device.child_id) {
+        (DeviceType::KasaPlug | DeviceType::KasaDimmer, _) => Ok((device.ip, None)),
+        (DeviceType::KasaPowerStrip, Some(child_id)) => Ok((device.ip, Some(child_id))),
+        (device_type, _) => Err(ServerFnError::new(format!(
+            "On-device schedules aren't supported for {device_type} devices"
+        ))),
+    }
+}
+
+/// Schedule and countdown rules stored on a Kasa plug, dimmer or power strip
+/// outlet. Devices without a countdown module report none.
+#[server(name = GetKasaRules, encoding = "cbor")]
+pub async fn get_kasa_rules(device_id: i64) -> Result<KasaRules, ServerFnError> {
+    use crate::integrations::tplink::{COUNT_DOWN, SCHEDULE, tplink_get_rules};
+
+    let (ip, child_id) = kasa_rule_target(device_id).await?;
+    let schedule = tplink_get_rules(&ip, child_id.as_deref(), SCHEDULE).await?;
+    let count_down = match tplink_get_rules(&ip, child_id.as_deref(), COUNT_DOWN).await {
+        Err(e) if e.is_unsupported() => Vec::new(),
+        res => res?,
+    };
+    Ok(KasaRules {
+        schedule,
+        count_down,
+    })
+}
+
+/// Adds `rule` to the device, or replaces the rule with its id.
+#[server(name = SaveKasaScheduleRule, encoding = "cbor")]
+pub async fn save_kasa_schedule_rule(
+    device_id: i64,
+    rule: ScheduleRule,
+) -> Result<(), ServerFnError> {
+    use crate::integrations::tplink::{SCHEDULE, tplink_add_rule, tplink_edit_rule};
+
+    if rule.smin >= 24 * 60 {
+        return Err(ServerFnError::new("The time must be before midnight"));
+    }
+    if rule.wday.iter().all(|day| *day == 0) {
+        return Err(ServerFnError::new("Pick at least one day"));
+    }
+    let (ip, child_id) = kasa_rule_target(device_id).await?;
+    match rule.id {
+        Some(_) => tplink_edit_rule(&ip, child_id.as_deref(), SCHEDULE, &rule).await?,
+        None => {
+            tplink_add_rule(&ip, child_id.as_deref(), SCHEDULE, &rule).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Starts a countdown, or replaces the one with the rule's id.
+#[server(name = SaveKasaCountdownRule, encoding = "cbor")]
+pub async fn save_kasa_countdown_rule(
+    device_id: i64,
+    rule: CountdownRule,
+) -> Result<(), ServerFnError> {
+    use crate::integrations::tplink::{COUNT_DOWN, tplink_add_rule, tplink_edit_rule};
+
+    if rule.delay == 0 {
+        return Err(ServerFnError::new("The delay must be at least a second"));
+    }
+    // Only reported by the device
+    let rule = CountdownRule {
+        remain: None,
+        ..rule
+    };
+    let (ip, child_id) = kasa_rule_target(device_id).await?;
+    match rule.id {
+        Some(_) => tplink_edit_rule(&ip, child_id.as_deref(), COUNT_DOWN, &rule).await?,
+        None => {
+            tplink_add_rule(&ip, child_id.as_deref(), COUNT_DOWN, &rule).await?;
+        }
+    }
+    Ok(())
+}
+
+#[server(name = DeleteKasaRule, encoding = "cbor")]
+pub async fn delete_kasa_rule(
+    device_id: i64,
+    countdown: bool,
+    id: String,
+) -> Result<(), ServerFnError> {
+    use crate::integrations::tplink::{COUNT_DOWN, SCHEDULE, tplink_delete_rule};
+
+    let (ip, child_id) = kasa_rule_target(device_id).await?;
+    let module = if countdown { COUNT_DOWN } else { SCHEDULE };
+    tplink_delete_rule(&ip, child_id.as_deref(), module, &id).await?;
+    Ok(())
+}
+
+#[cfg(feature = "ssr")]
+async fn kasa_light_sysinfo(
+    device_id: i64,
+) -> Result<
+    (
+        String,
+        crate::integrations::tplink::types::TPLinkSmartLightData,
+    ),
+    ServerFnError,
+> {
+    use crate::integrations::tplink::{tplink_get_sysinfo, types::GetSysInfo};
+
+    let (device, _) = kasa_target(device_id).await?;
+    match tplink_get_sysinfo(&device.ip).await? {
+        GetSysInfo::TPLinkSmartLightData(sysinfo) => Ok((device.ip, sysinfo)),
+        _ => Err(ServerFnError::new(format!(
+            "{} isn't a Kasa bulb",
+            device.label()
+        ))),
+    }
+}
+
+/// The kelvin range and saved presets of a Kasa bulb.
+#[server(name = GetKasaLight, encoding = "cbor")]
+pub async fn get_kasa_light(device_id: i64) -> Result<KasaLight, ServerFnError> {
+    let (_, sysinfo) = kasa_light_sysinfo(device_id).await?;
+    Ok(KasaLight {
+        color_temp_range: sysinfo.color_temp_range(),
+        presets: sysinfo.preferred_state,
+        color_temp: sysinfo.light_state.color_temp,
+    })
+}
+
+/// Saves what the bulb shows now, or turns on to while it's off, over the
+/// preset at `index`.
+#[server(name = SaveKasaLightPreset, encoding = "cbor")]
+pub async fn save_kasa_light_preset(device_id: i64, index: u8) -> Result<(), ServerFnError> {
+    use crate::integrations::tplink::{tplink_set_light_preset, types::PreferredState};
+
+    let (ip, sysinfo) = kasa_light_sysinfo(device_id).await?;
+    if !sysinfo
+        .preferred_state
+        .iter()
+        .any(|preset| preset.index == index)
+    {
+        return Err(ServerFnError::new(format!("There's no preset {index}")));
+    }
+    let light_state = sysinfo.light_state;
+    let preset = match light_state.dft_on_state {
+        Some(dft) if light_state.on_off == 0 => PreferredState {
+            index,
+            hue: dft.hue,
+            saturation: dft.saturation,
+            color_temp: dft.color_temp,
+            brightness: dft.brightness,
+        },
+        _ => PreferredState {
+            index,
+            hue: light_state.hue.unwrap_or_default().into(),
+            saturation: light_state.saturation.unwrap_or_default().into(),
+            color_temp: light_state.color_temp.unwrap_or_default().into(),
+            brightness: light_state.brightness.unwrap_or(100).into(),
+        },
+    };
+    tplink_set_light_preset(&ip, &preset).await?;
+    Ok(())
+}
+
+/// The address of the power strip an outlet belongs to.
+#[cfg(feature = "ssr")]
+async fn power_strip_ip(device_id: i64) -> Result<String, ServerFnError> {
+    use {
+        crate::integrations::iron_nest::{get_device_by_id, types::DeviceType},
+        sqlx::PgPool,
+    };
+
+    let pool = use_context::<PgPool>().unwrap();
+    let device = get_device_by_id(&pool, device_id).await?;
+    match device.device_type {
+        DeviceType::KasaPowerStrip => Ok(device.ip),
+        _ => Err(ServerFnError::new(format!(
+            "{} isn't a power strip outlet",
+            device.label()
+        ))),
+    }
+}
+
+/// Every outlet of the power strip the outlet `device_id` belongs to, with
+/// its latest power draw for strips that meter each outlet.
+#[server(name = GetPowerStripOutlets, encoding = "cbor")]
+pub async fn get_power_strip_outlets(
+    device_id: i64,
+) -> Result<Vec<PowerStripOutlet>, ServerFnError> {
+    use {
+        crate::integrations::iron_nest::{get_latest_energy_readings, get_power_strip_outlets},
+        sqlx::PgPool,
+    };
+
+    let pool = use_context::<PgPool>().unwrap();
+    let ip = power_strip_ip(device_id).await?;
+    let readings = get_latest_energy_readings(&pool).await?;
+    let outlets = get_power_strip_outlets(&pool, &ip)
+        .await?
+        .into_iter()
+        .map(|device| PowerStripOutlet {
+            power: readings
+                .iter()
+                .find(|reading| reading.device_id == device.id)
+                .and_then(|reading| reading.power),
+            device,
+        })
+        .collect();
+    Ok(outlets)
+}
+
+/// Turns every outlet of the power strip the outlet `device_id` belongs to on
+/// or off, then stores what each outlet reports.
+#[server(name = SetPowerStripOutlets, encoding = "cbor")]
+pub async fn set_power_strip_outlets(device_id: i64, on: bool) -> Result<(), ServerFnError> {
+    use {
+        crate::integrations::{
+            iron_nest::{events::EventBus, update_power_strip_outlets},
+            tplink::{tplink_get_sysinfo, tplink_set_all_smart_strip_sockets, types::GetSysInfo},
+        },
+        sqlx::PgPool,
+    };
+
+    let pool = use_context::<PgPool>().unwrap();
+    let event_bus = use_context::<EventBus>().unwrap();
+    let ip = power_strip_ip(device_id).await?;
+    tplink_set_all_smart_strip_sockets(&ip, on).await?;
+    let GetSysInfo::TPLinkSmartPowerStripData(strip) = tplink_get_sysinfo(&ip).await? else {
+        return Err(ServerFnError::new(format!(
+            "{ip} didn't report its outlets"
+        )));
+    };
+    update_power_strip_outlets(&pool, &event_bus, &ip, &strip).await?;
+    Ok(())
+}
+
+/// Writes `alias` to the device itself and stores it as the device's name.
+#[cfg(feature = "ssr")]
+pub async fn set_kasa_alias(
+    pool: &sqlx::PgPool,
+    device: &crate::integrations::iron_nest::types::Device,
+    alias: &str,
+) -> Result<(), ServerFnError> {
+    use crate::integrations::{
+        iron_nest::types::DeviceType,
+        tplink::{tplink_set_alias, tplink_set_light_alias, tplink_set_smart_strip_socket_alias},
+    };
+
+    match (device.device_type, device.child_id.as_deref()) {
+        (DeviceType::KasaPlug | DeviceType::KasaDimmer, _) => {
+            tplink_set_alias(&device.ip, alias).await?
+        }
+        (DeviceType::KasaLight, _) => tplink_set_light_alias(&device.ip, alias).await?,
+        (DeviceType::KasaPowerStrip, Some(child_id)) => {
+            tplink_set_smart_strip_socket_alias(&device.ip, child_id, alias).await?
+        }
+        (device_type, _) => {
+            return Err(ServerFnError::new(format!(
+                "Renaming {device_type} devices on the device isn't supported"
+            )));
+        }
+    }
+    sqlx::query("UPDATE device SET name = $1 WHERE id = $2")
+        .bind(alias)
+        .bind(device.id)
+        .execute(pool)
+        .await?;
     Ok(())
 }
+
+#[cfg(feature = "ssr")]
+use crate::integrations::tplink::types::KasaFamily;
+
+/// The address of a Kasa device and which module names it answers to.
+#[cfg(feature = "ssr")]
+async fn kasa_target(
+    device_id: i64,
+) -> Result<(crate::integrations::iron_nest::types::Device, KasaFamily), ServerFnError> {
+    use {crate::integrations::iron_nest::get_device_by_id, sqlx::PgPool};
+
+    let pool = use_context::<PgPool>().unwrap();
+    let device = get_device_by_id(&pool, device_id).await?;
+    let family = KasaFamily::of(device.device_type)
+        .ok_or_else(|| ServerFnError::new(format!("{} isn't a Kasa device", device.label())))?;
+    Ok((device, family))
+}
+
+/// Name, model, status LED and cloud binding of a Kasa device, for the
+/// management panel. Outlets report their own name and the strip's settings.
+#[server(name = GetKasaDeviceInfo, encoding = "cbor")]
+pub async fn get_kasa_device_info(device_id: i64) -> Result<KasaDeviceInfo, ServerFnError> {
+    use crate::integrations::tplink::{
+        tplink_get_cloud_info, tplink_get_sysinfo, types::GetSysInfo,
+    };
+
+    let (device, family) = kasa_target(device_id).await?;
+    let (alias, model, led_off) = match tplink_get_sysinfo(&device.ip).await? {
+        GetSysInfo::TPLinkDiscoveryData(sysinfo) => {
+            (sysinfo.alias, sysinfo.model, Some(sysinfo.led_off != 0))
+        }
+        GetSysInfo::TPLinkSmartLightData(sysinfo) => (sysinfo.alias, sysinfo.model, None),
+        GetSysInfo::TPLinkSmartPowerStripData(sysinfo) => {
+            let alias = device
+                .child_id
+                .as_deref()
+                .and_then(|child_id| sysinfo.outlet(child_id))
+                .map_or_else(|| sysinfo.alias.clone(), |outlet| outlet.alias.clone());
+            (alias, sysinfo.model, Some(sysinfo.led_off != 0))
+        }
+        _ => {
+            return Err(ServerFnError::new(format!(
+                "{} didn't report its sysinfo",
+                device.label()
+            )));
+        }
+    };
+    let cloud = match tplink_get_cloud_info(&device.ip, family).await {
+        Err(e) if e.is_unsupported() => None,
+        res => Some(res?),
+    };
+    Ok(KasaDeviceInfo {
+        alias,
+        model,
+        led_off,
+        cloud,
+    })
+}
+
+/// Renames the device itself, or the outlet of a power strip.
+#[server(name = RenameKasaDevice, encoding = "cbor")]
+pub async fn rename_kasa_device(device_id: i64, alias: String) -> Result<(), ServerFnError> {
+    use sqlx::PgPool;
+
+    let alias = alias.trim();
+    if alias.is_empty() {
+        return Err(ServerFnError::new("A name is required"));
+    }
+    let pool = use_context::<PgPool>().unwrap();
+    let (device, _) = kasa_target(device_id).await?;
+    set_kasa_alias(&pool, &device, alias).await
+}
+
+/// Reboots the device, or the whole strip for an outlet. It's unreachable for
+/// a few seconds.
+#[server(name = RebootKasaDevice, encoding = "cbor")]
+pub async fn reboot_kasa_device(device_id: i64) -> Result<(), ServerFnError> {
+    use crate::integrations::tplink::tplink_reboot;
+
+    let (device, family) = kasa_target(device_id).await?;
+    tplink_reboot(&device.ip, family).await?;
+    Ok(())
+}
+
+#[server(name = SetKasaLed, encoding = "cbor")]
+pub async fn set_kasa_led(device_id: i64, on: bool) -> Result<(), ServerFnError> {
+    use crate::integrations::tplink::tplink_set_led_off;
+
+    let (device, family) = kasa_target(device_id).await?;
+    if family == KasaFamily::Light {
+        return Err(ServerFnError::new("Bulbs don't have a status LED"));
+    }
+    tplink_set_led_off(&device.ip, !on).await?;
+    Ok(())
+}
+
+/// Removes the device from its TP-Link account. IronNest controls it locally
+/// either way.
+#[server(name = UnbindKasaCloud, encoding = "cbor")]
+pub async fn unbind_kasa_cloud(device_id: i64) -> Result<(), ServerFnError> {
+    use crate::integrations::tplink::tplink_cloud_unbind;
+
+    let (device, family) = kasa_target(device_id).await?;
+    tplink_cloud_unbind(&device.ip, family).await?;
+    Ok(())
+}
+
+/// Networks the Kasa device can see.
+#[server(name = GetKasaWifiNetworks, encoding = "cbor")]
+pub async fn get_kasa_wifi_networks(device_id: i64) -> Result<Vec<WifiNetwork>, ServerFnError> {
+    use crate::integrations::tplink::tplink_get_wifi_networks;
+
+    let (device, family) = kasa_target(device_id).await?;
+    Ok(tplink_get_wifi_networks(&device.ip, family).await?)
+}
+
+/// Has the Kasa device, or the whole strip for an outlet, join `network`.
+#[server(name = SetKasaWifi, encoding = "cbor")]
+pub async fn set_kasa_wifi(
+    device_id: i64,
+    network: WifiNetwork,
+    password: String,
+) -> Result<(), ServerFnError> {
+    use {
+        crate::integrations::tplink::{TplinkError, tplink_set_wifi},
+        std::io::ErrorKind,
+    };
+
+    if network.ssid.is_empty() {
+        return Err(ServerFnError::new("A network name is required"));
+    }
+    // WPA and WPA2 need at least 8 characters, WEP keys can be shorter
+    match network.key_type {
+        0 => {}
+        2 | 3 if password.len() < 8 => {
+            return Err(ServerFnError::new(
+                "The password must be at least 8 characters",
+            ));
+        }
+        _ if password.is_empty() => {
+            return Err(ServerFnError::new("A password is required"));
+        }
+        _ => {}
+    }
+    let (device, family) = kasa_target(device_id).await?;
+    match tplink_set_wifi(&device.ip, family, &network, &password).await {
+        Ok(()) => Ok(()),
+        // It often leaves its access point to join the network before
+        // answering, but after taking the request
+        Err(TplinkError::Timeout("waiting for a response")) => Ok(()),
+        Err(TplinkError::Io(e))
+            if matches!(
+                e.kind(),
+                ErrorKind::UnexpectedEof | ErrorKind::ConnectionReset
+            ) =>
+        {
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}